@@ -3,7 +3,7 @@ use std::{
     fmt::Display,
 };
 
-use frostmark::{MarkState, MarkWidget, UpdateMsg};
+use frostmark::{FindOptions, Heading, MarkState, MarkWidget, TocWidget, UpdateMsg};
 use iced::{
     widget::{self, image, svg},
     Alignment, Element, Task,
@@ -18,9 +18,12 @@ fn main() -> iced::Result{
     iced::application(
         || {
             let page = Page::TestSuite;
+            let state = MarkState::with_html_and_markdown(page.get_contents());
             let mut app = App {
                 page,
-                state: MarkState::with_html_and_markdown(page.get_contents()),
+                search: String::new(),
+                outline: state.outline(),
+                state,
                 images_normal: HashMap::new(),
                 images_svg: HashMap::new(),
                 images_in_progress: HashSet::new(),
@@ -33,10 +36,15 @@ fn main() -> iced::Result{
     ).run()
 }
 
+const SCROLL_ID: &str = "document";
+
 #[derive(Debug, Clone)]
 enum Message {
     UpdateState(UpdateMsg),
     OpenLink(String),
+    JumpTo(String),
+    Search(String),
+    NextMatch,
     ChangePage(Page),
     ImageDownloaded(Result<Image, String>),
 }
@@ -44,6 +52,8 @@ enum Message {
 struct App {
     page: Page,
     state: MarkState,
+    search: String,
+    outline: Vec<Heading>,
     images_normal: HashMap<String, image::Handle>,
     images_svg: HashMap<String, svg::Handle>,
     images_in_progress: HashSet<String>,
@@ -56,6 +66,18 @@ impl App {
             Message::OpenLink(link) => {
                 _ = open::that(&link);
             }
+            Message::JumpTo(anchor) => {
+                return frostmark::scroll_to_anchor(SCROLL_ID, &anchor);
+            }
+            Message::Search(query) => {
+                self.state.find(&query, FindOptions::default());
+                self.search = query;
+                return self.state.scroll_to_match(SCROLL_ID);
+            }
+            Message::NextMatch => {
+                self.state.next_match();
+                return self.state.scroll_to_match(SCROLL_ID);
+            }
             Message::ChangePage(page) => {
                 self.page = page;
                 return self.reload();
@@ -81,28 +103,44 @@ impl App {
     fn view<'a>(&'a self) -> Element<'a, Message> {
         let page_selector = widget::row![
             "Page:",
-            widget::pick_list(Page::ALL, Some(self.page), |s| Message::ChangePage(s))
+            widget::pick_list(Page::ALL, Some(self.page), |s| Message::ChangePage(s)),
+            widget::text_input("Find...", &self.search)
+                .on_input(Message::Search)
+                .on_submit(Message::NextMatch)
+                .width(200),
         ]
         .align_y(Alignment::Center)
         .spacing(10);
 
-        widget::scrollable(
+        let toc = widget::scrollable(
+            widget::container(TocWidget::new(&self.outline).on_selecting(Message::JumpTo))
+                .padding(10),
+        )
+        .width(200);
+
+        let document = widget::scrollable(
             widget::column![
                 page_selector,
                 widget::rule::horizontal(2),
                 MarkWidget::new(&self.state)
                     .on_updating_state(|msg| Message::UpdateState(msg))
                     .on_clicking_link(Message::OpenLink)
-                    .on_drawing_image(|info| self.draw_image(info)),
+                    .on_jumping_to_anchor(Message::JumpTo)
+                    .on_drawing_image(|info| self.draw_image(info))
+                    .virtualized(true),
             ]
             .spacing(10)
             .padding(10),
         )
-        .into()
+        .id(SCROLL_ID);
+
+        widget::row![toc, widget::rule::vertical(1), document].into()
     }
 
     fn reload(&mut self) -> Task<Message> {
         self.state = MarkState::with_html_and_markdown(self.page.get_contents());
+        self.outline = self.state.outline();
+        self.state.find(&self.search, FindOptions::default());
         self.download_images()
     }
 
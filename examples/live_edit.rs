@@ -43,7 +43,8 @@ impl App {
                 let is_edit = a.is_edit();
                 self.editor.perform(a);
                 if is_edit {
-                    self.reparse();
+                    // Keeps open dropdowns and such
+                    self.state.reparse(&self.editor.text());
                 }
             }
             Message::UpdateState(msg) => {
@@ -83,7 +84,7 @@ impl App {
             widget::row![
                 editor,
                 widget::scrollable(
-                    MarkWidget::new(&self.state).on_updating_state(|msg| Message::UpdateState(msg))
+                    MarkWidget::new(&self.state).on_updating_state(Message::UpdateState)
                 )
                 .width(Length::Fill),
             ]
@@ -25,6 +25,8 @@ impl App {
                     text_color: Some(iced::Color::from_rgb8(255, 0, 0)),
                     link_color: Some(iced::Color::from_rgb8(255, 0, 255)),
                     highlight_color: Some(iced::Color::from_rgb8(0, 255, 0)),
+                    heading_color: Some(iced::Color::from_rgb8(0, 128, 255)),
+                    ..Default::default()
                 })
                 // Difference between link buttons and link text:
                 // Link buttons are links with non-text content (eg: images)
@@ -48,6 +50,8 @@ fn main() {
 }
 
 const YOUR_TEXT: &str = r#"
+# This heading will be blue
+
 This text will be red
 
 [This text will be purple](https://example.com)
@@ -0,0 +1,175 @@
+use iced::{
+    advanced::widget::{
+        self,
+        operation::{
+            scrollable::{self, AbsoluteOffset},
+            Outcome, Scrollable,
+        },
+        Id, Operation,
+    },
+    Rectangle, Task, Vector,
+};
+
+/// The id of the container wrapping the element with this anchor.
+pub(crate) fn anchor_id(anchor: &str) -> Id {
+    Id::from(format!("frostmark-anchor#{anchor}"))
+}
+
+/// Scrolls the [`iced::widget::Scrollable`] with the given id
+/// so that the heading with the given anchor is at the top.
+/// Other elements with an `id` (eg: footnotes) work too.
+///
+/// Use this to handle [`crate::MarkWidget::on_jumping_to_anchor`],
+/// after giving the scrollable containing your [`crate::MarkWidget`] an id.
+///
+/// ```no_run
+/// # use frostmark::{MarkState, MarkWidget};
+/// # use iced::{widget, Task};
+/// # #[derive(Clone)]
+/// enum Message { JumpTo(String) }
+/// # struct App { state: MarkState }
+/// const SCROLL_ID: &str = "docs";
+///
+/// impl App {
+///     fn view(&self) -> iced::Element<'_, Message> {
+///         widget::scrollable(
+///             MarkWidget::new(&self.state).on_jumping_to_anchor(Message::JumpTo),
+///         )
+///         .id(SCROLL_ID)
+///         .into()
+///     }
+///
+///     fn update(&mut self, msg: Message) -> Task<Message> {
+///         match msg {
+///             Message::JumpTo(anchor) => frostmark::scroll_to_anchor(SCROLL_ID, &anchor),
+///         }
+///     }
+/// }
+/// ```
+///
+/// Does nothing if the anchor or scrollable can't be found.
+pub fn scroll_to_anchor<M: Send + 'static>(scrollable: impl Into<Id>, anchor: &str) -> Task<M> {
+    widget::operate(FindAnchor {
+        scrollable: scrollable.into(),
+        anchor: anchor_id(anchor),
+        content: None,
+        entering: false,
+        inside: false,
+        target: None,
+    })
+    .discard()
+}
+
+struct FindAnchor {
+    scrollable: Id,
+    anchor: Id,
+    content: Option<Rectangle>,
+    /// Whether the next [`Operation::traverse`] goes
+    /// into the content of the target scrollable.
+    entering: bool,
+    /// Whether we're inside the target scrollable.
+    inside: bool,
+    target: Option<Rectangle>,
+}
+
+impl Operation for FindAnchor {
+    fn traverse(&mut self, operate: &mut dyn FnMut(&mut dyn Operation)) {
+        let inside = self.inside;
+        self.inside |= std::mem::take(&mut self.entering);
+        operate(self);
+        self.inside = inside;
+    }
+
+    fn scrollable(
+        &mut self,
+        id: Option<&Id>,
+        _bounds: Rectangle,
+        content_bounds: Rectangle,
+        _translation: Vector,
+        _state: &mut dyn Scrollable,
+    ) {
+        if id == Some(&self.scrollable) {
+            self.content = Some(content_bounds);
+            self.entering = true;
+        }
+    }
+
+    fn container(&mut self, id: Option<&Id>, bounds: Rectangle) {
+        // Only look inside the target scrollable,
+        // in case other documents have the same anchors.
+        if self.inside && self.target.is_none() && id == Some(&self.anchor) {
+            self.target = Some(bounds);
+        }
+    }
+
+    fn finish(&self) -> Outcome<()> {
+        match (self.content, self.target) {
+            (Some(content), Some(target)) => Outcome::Chain(Box::new(scrollable::scroll_to(
+                self.scrollable.clone(),
+                AbsoluteOffset {
+                    x: None,
+                    y: Some(target.y - content.y),
+                },
+            ))),
+            _ => Outcome::None,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "iced-tiny-skia"))]
+mod tests {
+    use iced::{
+        advanced::{
+            layout,
+            widget::{operation::Outcome, Operation, Tree},
+            Layout,
+        },
+        widget, Element, Size,
+    };
+
+    use super::{anchor_id, FindAnchor};
+
+    /// Whether scrolling to `anchor` in the scrollable `docs` would do anything.
+    fn finds(mut element: Element<'_, ()>, anchor: &str) -> bool {
+        let renderer = crate::testing::renderer();
+        let mut tree = Tree::new(&element);
+        let limits = layout::Limits::new(Size::ZERO, Size::new(800.0, 600.0));
+        let node = element
+            .as_widget_mut()
+            .layout(&mut tree, &renderer, &limits);
+
+        let mut find = FindAnchor {
+            scrollable: "docs".into(),
+            anchor: anchor_id(anchor),
+            content: None,
+            entering: false,
+            inside: false,
+            target: None,
+        };
+        element
+            .as_widget_mut()
+            .operate(&mut tree, Layout::new(&node), &renderer, &mut find);
+        matches!(find.finish(), Outcome::Chain(_))
+    }
+
+    fn anchor(anchor: &str) -> widget::Container<'_, ()> {
+        widget::container(widget::text(anchor)).id(anchor_id(anchor))
+    }
+
+    #[test]
+    fn only_inside_scrollable() {
+        let page = || {
+            widget::column![
+                anchor("before"),
+                widget::scrollable(widget::column![anchor("inside")]).id("docs"),
+                widget::scrollable(anchor("other")).id("other"),
+                anchor("after"),
+            ]
+            .into()
+        };
+        assert!(finds(page(), "inside"));
+        for outside in ["before", "other", "after"] {
+            assert!(!finds(page(), outside), "{outside}");
+        }
+    }
+}
@@ -0,0 +1,701 @@
+//! A small subset of CSS, for the `style` attribute
+//! and [`Stylesheet`]s.
+//!
+//! Only the properties that map onto how frostmark
+//! renders text are supported, the rest are ignored.
+
+use iced::Color;
+
+use crate::structs::{ChildAlignment, ChildData, ChildDataFlags, ListStyle};
+
+/// Styling from CSS declarations (eg: `color: red; font-weight: bold`).
+///
+/// Every field is `None` unless the declarations set it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct CssStyle {
+    pub color: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: Option<bool>,
+    pub italic: Option<bool>,
+    pub underline: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub text_align: Option<TextAlign>,
+    pub font_size: Option<FontSize>,
+    pub list_style: Option<ListStyle>,
+    /// `display: none`
+    pub hidden: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FontSize {
+    /// In pixels.
+    Absolute(f32),
+    /// Multiple of the parent's size (`em`, `%`, `larger`).
+    Relative(f32),
+    /// Multiple of the base text size (`rem`, `small`, `large`).
+    Root(f32),
+}
+
+impl CssStyle {
+    /// Parses a list of declarations, like in a `style` attribute.
+    pub fn parse(css: &str) -> Self {
+        let mut style = Self::default();
+        for declaration in css.split(';') {
+            if let Some((property, value)) = declaration.split_once(':') {
+                style.declare(property, value);
+            }
+        }
+        style
+    }
+
+    fn declare(&mut self, property: &str, value: &str) {
+        let property = property.trim().to_ascii_lowercase();
+        let value = value.trim().trim_end_matches("!important").trim();
+        let lower = value.to_ascii_lowercase();
+
+        match property.as_str() {
+            "color" => self.color = parse_color(&lower),
+            "background-color" => self.background = parse_color(&lower),
+            // The shorthand can have images and such in it,
+            // just look for a color
+            "background" => {
+                self.background = split_values(&lower).find_map(parse_color);
+            }
+            "font-weight" => {
+                self.bold = match lower.as_str() {
+                    "bold" | "bolder" => Some(true),
+                    "normal" | "lighter" => Some(false),
+                    n => n.parse::<u16>().ok().map(|n| n >= 600),
+                };
+            }
+            "font-style" => {
+                self.italic = match lower.as_str() {
+                    "italic" | "oblique" => Some(true),
+                    "normal" => Some(false),
+                    _ => None,
+                };
+            }
+            "text-decoration" | "text-decoration-line" => {
+                let words: Vec<&str> = lower.split_whitespace().collect();
+                if words.contains(&"none") {
+                    self.underline = Some(false);
+                    self.strikethrough = Some(false);
+                } else {
+                    if words.contains(&"underline") {
+                        self.underline = Some(true);
+                    }
+                    if words.contains(&"line-through") {
+                        self.strikethrough = Some(true);
+                    }
+                }
+            }
+            "text-align" => {
+                self.text_align = match lower.as_str() {
+                    "left" | "start" | "justify" => Some(TextAlign::Left),
+                    "center" => Some(TextAlign::Center),
+                    "right" | "end" => Some(TextAlign::Right),
+                    _ => None,
+                };
+            }
+            "font-size" => self.font_size = parse_font_size(&lower),
+            "list-style-type" => self.list_style = parse_list_style(&lower),
+            "list-style" => {
+                self.list_style = split_values(&lower).find_map(parse_list_style);
+            }
+            "display" => self.hidden = Some(lower == "none"),
+            _ => {}
+        }
+    }
+
+    /// Puts `other` on top of this (eg: a more specific rule),
+    /// keeping what it doesn't set.
+    pub fn cascade(self, other: Self) -> Self {
+        Self {
+            color: other.color.or(self.color),
+            background: other.background.or(self.background),
+            bold: other.bold.or(self.bold),
+            italic: other.italic.or(self.italic),
+            underline: other.underline.or(self.underline),
+            strikethrough: other.strikethrough.or(self.strikethrough),
+            text_align: other.text_align.or(self.text_align),
+            font_size: other.font_size.or(self.font_size),
+            list_style: other.list_style.or(self.list_style),
+            hidden: other.hidden.or(self.hidden),
+        }
+    }
+
+    /// Applies the style to the data passed down to children.
+    ///
+    /// `size` is the current text size (for `em` and `%`)
+    /// and `base` the document's text size (for `rem`).
+    pub fn apply(&self, data: &mut ChildData, size: f32, base: f32, block_element: bool) {
+        if let Some(color) = self.color {
+            data.color = Some(color);
+        }
+        if let Some(color) = self.background {
+            data.background = Some(color);
+            // Whatever's inside a `<mark>` gets this instead
+            data.flags.remove(ChildDataFlags::HIGHLIGHT);
+        }
+
+        for (value, flag) in [
+            (self.bold, ChildDataFlags::BOLD),
+            (self.italic, ChildDataFlags::ITALIC),
+            (self.underline, ChildDataFlags::UNDERLINE),
+            (self.strikethrough, ChildDataFlags::STRIKETHROUGH),
+        ] {
+            if let Some(value) = value {
+                data.flags.set(flag, value);
+            }
+        }
+
+        if let (true, Some(align)) = (block_element, self.text_align) {
+            data.alignment = match align {
+                TextAlign::Left => None,
+                TextAlign::Center => Some(ChildAlignment::Center),
+                TextAlign::Right => Some(ChildAlignment::Right),
+            };
+        }
+
+        if let Some(font_size) = self.font_size {
+            data.font_size = Some(match font_size {
+                FontSize::Absolute(n) => n,
+                FontSize::Relative(n) => size * n,
+                FontSize::Root(n) => base * n,
+            });
+        }
+    }
+}
+
+/// Splits a value by whitespace, except inside brackets
+/// (eg: `url(a b) rgb(1 2 3)` is two values).
+fn split_values(value: &str) -> impl Iterator<Item = &str> {
+    let mut depth = 0_usize;
+    value
+        .split(move |c: char| {
+            match c {
+                '(' => depth += 1,
+                ')' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            depth == 0 && c.is_whitespace()
+        })
+        .filter(|n| !n.is_empty())
+}
+
+pub(crate) fn parse_list_style(value: &str) -> Option<ListStyle> {
+    Some(match value {
+        "disc" => ListStyle::Disc,
+        "circle" => ListStyle::Circle,
+        "square" => ListStyle::Square,
+        "decimal" => ListStyle::Decimal,
+        "lower-alpha" | "lower-latin" => ListStyle::LowerAlpha,
+        "upper-alpha" | "upper-latin" => ListStyle::UpperAlpha,
+        "lower-roman" => ListStyle::LowerRoman,
+        "upper-roman" => ListStyle::UpperRoman,
+        "none" => ListStyle::None,
+        _ => return None,
+    })
+}
+
+fn parse_font_size(value: &str) -> Option<FontSize> {
+    let keyword = match value {
+        "xx-small" => Some(0.6),
+        "x-small" => Some(0.75),
+        "small" => Some(0.89),
+        "medium" => Some(1.0),
+        "large" => Some(1.2),
+        "x-large" => Some(1.5),
+        "xx-large" => Some(2.0),
+        "xxx-large" => Some(3.0),
+        _ => None,
+    };
+    if let Some(n) = keyword {
+        return Some(FontSize::Root(n));
+    }
+    match value {
+        "larger" => return Some(FontSize::Relative(1.2)),
+        "smaller" => return Some(FontSize::Relative(1.0 / 1.2)),
+        _ => {}
+    }
+
+    let number = |suffix: &str| {
+        value
+            .strip_suffix(suffix)
+            .and_then(|n| n.trim().parse::<f32>().ok())
+            .filter(|n| n.is_finite() && *n > 0.0)
+    };
+    // `rem` before `em`, since it ends with it
+    if let Some(n) = number("rem") {
+        Some(FontSize::Root(n))
+    } else if let Some(n) = number("em") {
+        Some(FontSize::Relative(n))
+    } else if let Some(n) = number("%") {
+        Some(FontSize::Relative(n / 100.0))
+    } else if let Some(n) = number("px") {
+        Some(FontSize::Absolute(n))
+    } else {
+        number("pt").map(|n| FontSize::Absolute(n * 4.0 / 3.0))
+    }
+}
+
+/// Parses a CSS color (`#rgb`, `#rrggbb`, `rgb(..)`, `rgba(..)`, or a common name).
+/// Expects it in lowercase.
+pub(crate) fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+    if let Some(args) = value
+        .strip_prefix("rgba(")
+        .or_else(|| value.strip_prefix("rgb("))
+        .and_then(|n| n.strip_suffix(')'))
+    {
+        return parse_rgb(args);
+    }
+
+    let (r, g, b) = match value {
+        "transparent" => return Some(Color::TRANSPARENT),
+        "black" => (0x00, 0x00, 0x00),
+        "white" => (0xFF, 0xFF, 0xFF),
+        "red" => (0xFF, 0x00, 0x00),
+        "green" => (0x00, 0x80, 0x00),
+        "lime" => (0x00, 0xFF, 0x00),
+        "blue" => (0x00, 0x00, 0xFF),
+        "yellow" => (0xFF, 0xFF, 0x00),
+        "orange" => (0xFF, 0xA5, 0x00),
+        "purple" => (0x80, 0x00, 0x80),
+        "gray" | "grey" => (0x80, 0x80, 0x80),
+        "darkgray" | "darkgrey" => (0xA9, 0xA9, 0xA9),
+        "lightgray" | "lightgrey" => (0xD3, 0xD3, 0xD3),
+        "silver" => (0xC0, 0xC0, 0xC0),
+        "maroon" => (0x80, 0x00, 0x00),
+        "navy" => (0x00, 0x00, 0x80),
+        "teal" => (0x00, 0x80, 0x80),
+        "olive" => (0x80, 0x80, 0x00),
+        "aqua" | "cyan" => (0x00, 0xFF, 0xFF),
+        "fuchsia" | "magenta" => (0xFF, 0x00, 0xFF),
+        "pink" => (0xFF, 0xC0, 0xCB),
+        "brown" => (0xA5, 0x2A, 0x2A),
+        "gold" => (0xFF, 0xD7, 0x00),
+        "crimson" => (0xDC, 0x14, 0x3C),
+        "darkred" => (0x8B, 0x00, 0x00),
+        "darkgreen" => (0x00, 0x64, 0x00),
+        "darkblue" => (0x00, 0x00, 0x8B),
+        "lightblue" => (0xAD, 0xD8, 0xE6),
+        "lightgreen" => (0x90, 0xEE, 0x90),
+        _ => return None,
+    };
+    Some(Color::from_rgb8(r, g, b))
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let digit = |i: usize| u8::from_str_radix(&hex[i..=i], 16).ok();
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+
+    let (r, g, b, a) = match hex.len() {
+        3 | 4 => {
+            let short = |i: usize| digit(i).map(|n| n * 17);
+            let a = if hex.len() == 4 { short(3)? } else { 255 };
+            (short(0)?, short(1)?, short(2)?, a)
+        }
+        6 | 8 => {
+            let a = if hex.len() == 8 { byte(6)? } else { 255 };
+            (byte(0)?, byte(2)?, byte(4)?, a)
+        }
+        _ => return None,
+    };
+    Some(Color::from_rgba8(r, g, b, f32::from(a) / 255.0))
+}
+
+/// Parses the inside of `rgb(..)`, both the old `r, g, b, a`
+/// and the new `r g b / a` syntax.
+fn parse_rgb(args: &str) -> Option<Color> {
+    let parts: Vec<&str> = args
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|n| !n.is_empty())
+        .collect();
+    if !(3..=4).contains(&parts.len()) {
+        return None;
+    }
+
+    let channel = |n: &str| -> Option<f32> {
+        let value = if let Some(percent) = n.strip_suffix('%') {
+            percent.parse::<f32>().ok()? / 100.0
+        } else {
+            n.parse::<f32>().ok()? / 255.0
+        };
+        Some(value.clamp(0.0, 1.0))
+    };
+    let alpha = |n: &str| -> Option<f32> {
+        let value = if let Some(percent) = n.strip_suffix('%') {
+            percent.parse::<f32>().ok()? / 100.0
+        } else {
+            n.parse::<f32>().ok()?
+        };
+        Some(value.clamp(0.0, 1.0))
+    };
+
+    Some(Color::from_rgba(
+        channel(parts[0])?,
+        channel(parts[1])?,
+        channel(parts[2])?,
+        parts.get(3).map_or(Some(1.0), |n| alpha(n))?,
+    ))
+}
+
+/// A set of CSS rules for styling documents,
+/// see [`crate::MarkState::with_stylesheet`].
+///
+/// ```
+/// # use frostmark::Stylesheet;
+/// let stylesheet = Stylesheet::parse(r#"
+///     h1 { color: #3d7eff; }
+///     blockquote p { font-style: italic; }
+///     .warning { background: #ffcc00; color: black; }
+///     code.language-rust { font-weight: bold; }
+/// "#);
+/// ```
+///
+/// # Supported selectors
+/// - Tags (`h1`), classes (`.warning`), ids (`#intro`) and `*`,
+///   as well as combinations of them (`code.language-rust`)
+/// - Descendants (`blockquote p`)
+/// - Lists of selectors (`h1, h2`)
+///
+/// Rules with other selectors (eg: `a:hover`, `ul > li`)
+/// and at-rules (eg: `@media`) are skipped.
+///
+/// # Supported properties
+/// `color`, `background-color` (and colors in `background`),
+/// `font-weight`, `font-style`, `font-size`, `text-decoration`,
+/// `text-align`, `list-style-type` and `display: none`. The rest are ignored.
+///
+/// The `style` attribute of an element takes priority over
+/// any rule, and otherwise more specific rules win
+/// (then later ones), like in a browser.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stylesheet {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rule {
+    selector: Selector,
+    style: CssStyle,
+}
+
+/// A chain of descendant selectors (eg: `blockquote p`),
+/// with the element itself last.
+#[derive(Debug, Clone, PartialEq)]
+struct Selector {
+    parts: Vec<Compound>,
+}
+
+/// One part of a selector, like `code.language-rust`.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Compound {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+}
+
+/// An element, as far as selectors care.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SelectorTarget {
+    pub tag: String,
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+}
+
+impl Stylesheet {
+    /// Parses a stylesheet, skipping anything
+    /// it doesn't understand (see [`Stylesheet`]).
+    #[must_use]
+    pub fn parse(css: &str) -> Self {
+        let css = strip_comments(css);
+        let mut rules = Vec::new();
+
+        let mut rest = css.as_str();
+        while let Some(open) = rest.find('{') {
+            let prelude = rest[..open].trim();
+            let Some(len) = block_len(&rest[open..]) else {
+                break;
+            };
+            let body = &rest[open + 1..open + len - 1];
+            rest = &rest[open + len..];
+
+            if prelude.starts_with('@') {
+                continue;
+            }
+            let style = CssStyle::parse(body);
+            for selector in prelude.split(',').filter_map(Selector::parse) {
+                rules.push(Rule { selector, style });
+            }
+        }
+
+        Self { rules }
+    }
+
+    /// Adds the rules of another stylesheet after the ones
+    /// in this one, so they win when equally specific.
+    pub fn extend(&mut self, other: Self) {
+        self.rules.extend(other.rules);
+    }
+
+    /// Returns `true` if there are no rules.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// The style of an element with the given ancestors
+    /// (outermost first), from all matching rules.
+    pub(crate) fn style_for(
+        &self,
+        element: &SelectorTarget,
+        ancestors: &[SelectorTarget],
+    ) -> CssStyle {
+        let mut matching: Vec<(_, &Rule)> = self
+            .rules
+            .iter()
+            .filter(|n| n.selector.matches(element, ancestors))
+            .map(|n| (n.selector.specificity(), n))
+            .collect();
+        // Stable, so later rules still win ties
+        matching.sort_by_key(|(specificity, _)| *specificity);
+
+        matching
+            .into_iter()
+            .fold(CssStyle::default(), |style, (_, rule)| {
+                style.cascade(rule.style)
+            })
+    }
+}
+
+impl Selector {
+    fn parse(selector: &str) -> Option<Self> {
+        let parts = selector
+            .split_whitespace()
+            .map(Compound::parse)
+            .collect::<Option<Vec<_>>>()?;
+        (!parts.is_empty()).then_some(Self { parts })
+    }
+
+    fn matches(&self, element: &SelectorTarget, ancestors: &[SelectorTarget]) -> bool {
+        let Some((last, rest)) = self.parts.split_last() else {
+            return false;
+        };
+        if !last.matches(element) {
+            return false;
+        }
+        // Find each part in the ancestors, going outwards
+        let mut ancestors = ancestors.iter().rev();
+        rest.iter()
+            .rev()
+            .all(|part| ancestors.any(|n| part.matches(n)))
+    }
+
+    /// (ids, classes, tags), like in CSS.
+    fn specificity(&self) -> (usize, usize, usize) {
+        self.parts
+            .iter()
+            .fold((0, 0, 0), |(ids, classes, tags), n| {
+                (
+                    ids + usize::from(n.id.is_some()),
+                    classes + n.classes.len(),
+                    tags + usize::from(n.tag.is_some()),
+                )
+            })
+    }
+}
+
+impl Compound {
+    fn parse(text: &str) -> Option<Self> {
+        let mut compound = Self::default();
+
+        // Split before every `.` and `#`
+        let mut pieces = Vec::new();
+        let mut start = 0;
+        for (i, c) in text.char_indices() {
+            if (c == '.' || c == '#') && i > 0 {
+                pieces.push(&text[start..i]);
+                start = i;
+            }
+        }
+        pieces.push(&text[start..]);
+
+        for piece in pieces {
+            if let Some(class) = piece.strip_prefix('.') {
+                compound.classes.push(valid_name(class)?.to_owned());
+            } else if let Some(id) = piece.strip_prefix('#') {
+                compound.id = Some(valid_name(id)?.to_owned());
+            } else if piece != "*" {
+                compound.tag = Some(valid_name(piece)?.to_ascii_lowercase());
+            }
+        }
+        Some(compound)
+    }
+
+    fn matches(&self, element: &SelectorTarget) -> bool {
+        self.tag.as_ref().is_none_or(|n| *n == element.tag)
+            && self
+                .id
+                .as_ref()
+                .is_none_or(|n| element.id.as_ref() == Some(n))
+            && self.classes.iter().all(|n| element.classes.contains(n))
+    }
+}
+
+impl SelectorTarget {
+    pub fn new(tag: &str, attrs: &[html5ever::Attribute]) -> Self {
+        let attr = |name: &str| {
+            attrs
+                .iter()
+                .find(|n| &*n.name.local == name)
+                .map(|n| &*n.value)
+        };
+        Self {
+            tag: tag.to_owned(),
+            id: attr("id").map(str::to_owned),
+            classes: attr("class")
+                .map(|n| n.split_whitespace().map(str::to_owned).collect())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Checks that a name doesn't have anything unsupported
+/// in it (like `:hover`, `[href]` or `>`).
+fn valid_name(name: &str) -> Option<&str> {
+    (!name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_'))
+    .then_some(name)
+}
+
+fn strip_comments(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = rest[start + 2..]
+            .find("*/")
+            .map_or("", |end| &rest[start + 2 + end + 2..]);
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Length of a `{ .. }` block (including the brackets),
+/// counting nested blocks (eg: in `@media`).
+fn block_len(text: &str) -> Option<usize> {
+    let mut depth = 0_usize;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use iced::Color;
+
+    use super::{CssStyle, FontSize, SelectorTarget, Stylesheet, TextAlign};
+
+    fn target(tag: &str, class: Option<&str>) -> SelectorTarget {
+        SelectorTarget {
+            tag: tag.to_owned(),
+            id: None,
+            classes: class.into_iter().map(str::to_owned).collect(),
+        }
+    }
+
+    #[test]
+    fn declarations() {
+        let style = CssStyle::parse(
+            "COLOR: #f00; font-weight: 700 !important; text-decoration: underline line-through;\
+             text-align: end; font-size: 2em; margin: 0; nonsense",
+        );
+        assert_eq!(
+            style,
+            CssStyle {
+                color: Some(Color::from_rgb8(0xff, 0, 0)),
+                bold: Some(true),
+                underline: Some(true),
+                strikethrough: Some(true),
+                text_align: Some(TextAlign::Right),
+                font_size: Some(FontSize::Relative(2.0)),
+                ..CssStyle::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rules_and_comments() {
+        let sheet = Stylesheet::parse(
+            "/* a { color: #f00 } */ p, li { font-style: italic }
+             @media print { p { display: none } }
+             a:hover { font-weight: bold }
+             blockquote .note /* inside */ { display: none }",
+        );
+        let p = sheet.style_for(&target("p", None), &[]);
+        assert_eq!(p.italic, Some(true));
+        assert_eq!(p.hidden, None);
+        assert_eq!(sheet.style_for(&target("li", None), &[]).italic, Some(true));
+        // Commented out, and unsupported selectors
+        assert_eq!(
+            sheet.style_for(&target("a", None), &[]),
+            CssStyle::default()
+        );
+
+        let note = target("span", Some("note"));
+        assert_eq!(sheet.style_for(&note, &[]).hidden, None);
+        let ancestors = [target("blockquote", None), target("p", None)];
+        assert_eq!(sheet.style_for(&note, &ancestors).hidden, Some(true));
+    }
+
+    #[test]
+    fn unbalanced_braces() {
+        let sheet = Stylesheet::parse("p { font-style: italic } li { font-weight: bold");
+        assert_eq!(sheet.style_for(&target("p", None), &[]).italic, Some(true));
+        assert_eq!(sheet.style_for(&target("li", None), &[]).bold, None);
+
+        let sheet = Stylesheet::parse("@media print { p { color: #f00 } li { color: #f00 }");
+        assert!(sheet.is_empty());
+
+        // An unclosed comment hides the rest
+        let sheet = Stylesheet::parse("p { font-style: italic } /* li { font-weight: bold }");
+        assert_eq!(sheet.style_for(&target("li", None), &[]).bold, None);
+        assert_eq!(sheet.style_for(&target("p", None), &[]).italic, Some(true));
+    }
+
+    #[test]
+    fn specific_rules_win() {
+        let sheet = Stylesheet::parse(
+            "p.a { font-style: italic } p { font-style: normal; font-weight: bold }",
+        );
+        let style = sheet.style_for(&target("p", Some("a")), &[]);
+        assert_eq!(style.italic, Some(true));
+        assert_eq!(style.bold, Some(true));
+    }
+}
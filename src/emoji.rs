@@ -0,0 +1,222 @@
+//! Finding emoji in text, for [`crate::MarkWidget::on_drawing_emoji`].
+
+const VARIATION_SELECTOR: char = '\u{FE0F}';
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+const KEYCAP: char = '\u{20E3}';
+
+/// Splits text into pieces, with whether each one is an emoji.
+///
+/// Emoji made of several characters (eg: `👨‍💻`, `🇬🇧`, `👍🏽` or `#️⃣`)
+/// are kept together, so they can be swapped out for one image.
+pub(crate) fn split_emoji(text: &str) -> Vec<(&str, bool)> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+
+    while let Some(c) = text[i..].chars().next() {
+        if let Some(len) = emoji_len(&text[i..]) {
+            if start < i {
+                pieces.push((&text[start..i], false));
+            }
+            pieces.push((&text[i..i + len], true));
+            i += len;
+            start = i;
+        } else {
+            i += c.len_utf8();
+        }
+    }
+    if start < text.len() {
+        pieces.push((&text[start..], false));
+    }
+    pieces
+}
+
+/// The length (in bytes) of the emoji at the start of `text`, if there is one.
+fn emoji_len(text: &str) -> Option<usize> {
+    let mut chars = text.chars().peekable();
+    let first = chars.next()?;
+    let mut len = first.len_utf8();
+
+    // Flags are a pair of letters
+    if is_regional_indicator(first) {
+        let second = chars.next().filter(|c| is_regional_indicator(*c))?;
+        return Some(len + second.len_utf8());
+    }
+    // Keycaps (`#️⃣`, `1️⃣`) are plain characters
+    // with a box around them
+    if first.is_ascii_digit() || first == '#' || first == '*' {
+        if chars.next_if_eq(&VARIATION_SELECTOR).is_some() {
+            len += VARIATION_SELECTOR.len_utf8();
+        }
+        return chars.next_if_eq(&KEYCAP).map(|_| len + KEYCAP.len_utf8());
+    }
+    // Symbols like `❤` are only emoji when asked to be (`❤️`)
+    let is_start =
+        is_emoji(first) || (is_pictographic(first) && chars.peek() == Some(&VARIATION_SELECTOR));
+    if !is_start {
+        return None;
+    }
+
+    loop {
+        match chars.peek().copied() {
+            Some(c) if c == VARIATION_SELECTOR || is_skin_tone(c) || is_tag(c) => {
+                len += c.len_utf8();
+                chars.next();
+            }
+            // Joined emoji, like `👨‍💻` (man + laptop)
+            Some(ZERO_WIDTH_JOINER) => {
+                let mut rest = chars.clone();
+                rest.next();
+                match rest.next() {
+                    Some(c) if is_emoji(c) || is_pictographic(c) => {
+                        len += ZERO_WIDTH_JOINER.len_utf8() + c.len_utf8();
+                        chars = rest;
+                    }
+                    _ => break,
+                }
+            }
+            _ => break,
+        }
+    }
+    Some(len)
+}
+
+/// Characters that are shown as emoji by default.
+fn is_emoji(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x231A..=0x231B
+            | 0x23E9..=0x23EC
+            | 0x23F0
+            | 0x23F3
+            | 0x25FD..=0x25FE
+            | 0x2614..=0x2615
+            | 0x2648..=0x2653
+            | 0x267F
+            | 0x2693
+            | 0x26A1
+            | 0x26AA..=0x26AB
+            | 0x26BD..=0x26BE
+            | 0x26C4..=0x26C5
+            | 0x26CE
+            | 0x26D4
+            | 0x26EA
+            | 0x26F2..=0x26F3
+            | 0x26F5
+            | 0x26FA
+            | 0x26FD
+            | 0x2705
+            | 0x270A..=0x270B
+            | 0x2728
+            | 0x274C
+            | 0x274E
+            | 0x2753..=0x2755
+            | 0x2757
+            | 0x2795..=0x2797
+            | 0x27B0
+            | 0x27BF
+            | 0x2B1B..=0x2B1C
+            | 0x2B50
+            | 0x2B55
+            | 0x1F004
+            | 0x1F0CF
+            | 0x1F18E
+            | 0x1F191..=0x1F19A
+            | 0x1F201
+            | 0x1F21A
+            | 0x1F22F
+            | 0x1F232..=0x1F23A
+            | 0x1F250..=0x1F251
+            | 0x1F300..=0x1F64F
+            | 0x1F680..=0x1F6FF
+            | 0x1F7E0..=0x1F7EB
+            | 0x1F900..=0x1F9FF
+            | 0x1FA70..=0x1FAFF
+    )
+}
+
+/// Symbols that can be emoji (when followed by `U+FE0F`
+/// or joined to another emoji), but are text by default.
+fn is_pictographic(c: char) -> bool {
+    matches!(
+        c as u32,
+        0xA9 | 0xAE
+            | 0x203C
+            | 0x2049
+            | 0x2122
+            | 0x2139
+            | 0x2194..=0x21AA
+            | 0x2300..=0x23FF
+            | 0x24C2
+            | 0x25AA..=0x25FE
+            | 0x2600..=0x27BF
+            | 0x2934..=0x2935
+            | 0x2B05..=0x2B55
+            | 0x3030
+            | 0x303D
+            | 0x3297
+            | 0x3299
+            | 0x1F000..=0x1FAFF
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c as u32, 0x1F1E6..=0x1F1FF)
+}
+
+fn is_skin_tone(c: char) -> bool {
+    matches!(c as u32, 0x1F3FB..=0x1F3FF)
+}
+
+/// Tags spell out subdivision flags, like 🏴󠁧󠁢󠁳󠁣󠁴󠁿 (Scotland).
+fn is_tag(c: char) -> bool {
+    matches!(c as u32, 0xE0020..=0xE007F)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_emoji;
+
+    /// The emoji found in `text`.
+    fn emoji(text: &str) -> Vec<&str> {
+        split_emoji(text)
+            .into_iter()
+            .filter_map(|(piece, is_emoji)| is_emoji.then_some(piece))
+            .collect()
+    }
+
+    #[test]
+    fn multi_char_emoji() {
+        // Man + laptop, joined
+        assert_eq!(emoji("a \u{1F468}\u{200D}\u{1F4BB} b"), ["👨‍💻"]);
+        assert_eq!(emoji("🇬🇧🇫🇷"), ["🇬🇧", "🇫🇷"]);
+        assert_eq!(emoji("👍🏽👍"), ["👍🏽", "👍"]);
+        assert_eq!(emoji("🏴󠁧󠁢󠁳󠁣󠁴󠁿!"), ["🏴󠁧󠁢󠁳󠁣󠁴󠁿"]);
+    }
+
+    #[test]
+    fn text_around_emoji() {
+        assert_eq!(
+            split_emoji("hi 👋 there"),
+            [("hi ", false), ("👋", true), (" there", false)]
+        );
+        assert_eq!(split_emoji("plain"), [("plain", false)]);
+        assert_eq!(split_emoji(""), []);
+    }
+
+    #[test]
+    fn symbols_need_variation_selector() {
+        assert_eq!(emoji("I \u{2764} it"), Vec::<&str>::new());
+        assert_eq!(emoji("I \u{2764}\u{FE0F} it"), ["❤️"]);
+        // Emoji by default, with or without it
+        assert_eq!(emoji("\u{26A1}\u{26A1}\u{FE0F}"), ["⚡", "⚡️"]);
+    }
+
+    #[test]
+    fn keycaps() {
+        assert_eq!(emoji("#\u{FE0F}\u{20E3} 1\u{20E3}"), ["#️⃣", "1⃣"]);
+        assert_eq!(split_emoji("123 #tag *"), [("123 #tag *", false)]);
+        // A digit followed by a variation selector alone isn't a keycap
+        assert_eq!(emoji("1\u{FE0F}"), Vec::<&str>::new());
+    }
+}
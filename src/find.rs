@@ -0,0 +1,405 @@
+use std::{collections::HashMap, ops::Range};
+
+use iced::{
+    advanced::widget::{
+        self,
+        operation::{
+            scrollable::{self, AbsoluteOffset},
+            Outcome, Scrollable,
+        },
+        Id, Operation,
+    },
+    Rectangle, Task, Vector,
+};
+use markup5ever_rcdom::Node;
+
+use crate::ir::{Ir, IrKind};
+
+/// Options for [`crate::MarkState::find`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FindOptions {
+    /// Whether `Foo` should only match `Foo`, not `foo`.
+    pub case_sensitive: bool,
+    /// Whether to skip matches that are part of a bigger word
+    /// (eg: `cat` in `concatenate`).
+    pub whole_word: bool,
+}
+
+/// A match found by [`crate::MarkState::find`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FindMatch {
+    /// The matched text, as it appears in the document.
+    pub text: String,
+    /// Text nodes (see [`node_key`]) the match is spread across,
+    /// and the byte range of the match within each.
+    pub(crate) pieces: Vec<(usize, Range<usize>)>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct FindState {
+    pub query: String,
+    pub options: FindOptions,
+    pub matches: Vec<FindMatch>,
+    pub current: Option<usize>,
+    /// For every text node with matches in it:
+    /// byte ranges of the matches, and the match index.
+    pub by_node: HashMap<usize, Vec<(Range<usize>, usize)>>,
+}
+
+impl FindState {
+    pub fn new(ir: &Ir, query: &str, options: FindOptions) -> Self {
+        let mut blocks = vec![Vec::new()];
+        find_text(ir, Ir::ROOT, &mut blocks);
+
+        let folded: Vec<char> = query.chars().map(|c| fold(c, options)).collect();
+        let mut matches = Vec::new();
+        if !folded.is_empty() {
+            for block in &blocks {
+                search_block(block, &folded, options, &mut matches);
+            }
+        }
+
+        let mut by_node: HashMap<usize, Vec<(Range<usize>, usize)>> = HashMap::new();
+        for (i, m) in matches.iter().enumerate() {
+            for (node, range) in &m.pieces {
+                by_node.entry(*node).or_default().push((range.clone(), i));
+            }
+        }
+
+        Self {
+            query: query.to_owned(),
+            options,
+            current: (!matches.is_empty()).then_some(0),
+            matches,
+            by_node,
+        }
+    }
+
+    /// Searches a new version of the document for the same thing,
+    /// keeping the current match if it still exists.
+    pub fn refresh(&self, ir: &Ir) -> Self {
+        let mut new = Self::new(ir, &self.query, self.options);
+        if let (Some(current), false) = (self.current, new.matches.is_empty()) {
+            new.current = Some(current.min(new.matches.len() - 1));
+        }
+        new
+    }
+
+    /// Splits the text of a node into pieces,
+    /// along with which match each piece is part of.
+    pub fn segments<'a>(&self, node: &Node, text: &'a str) -> Vec<(&'a str, Option<usize>)> {
+        let Some(ranges) = self.by_node.get(&node_key(node)) else {
+            return vec![(text, None)];
+        };
+        let mut segments = Vec::new();
+        let mut last = 0;
+        for (range, i) in ranges {
+            let (Some(before), Some(matched)) =
+                (text.get(last..range.start), text.get(range.clone()))
+            else {
+                continue;
+            };
+            if !before.is_empty() {
+                segments.push((before, None));
+            }
+            segments.push((matched, Some(*i)));
+            last = range.end;
+        }
+        if let Some(rest) = text.get(last..).filter(|n| !n.is_empty()) {
+            segments.push((rest, None));
+        }
+        segments
+    }
+}
+
+/// Identifies a DOM node for as long as the document lives.
+pub(crate) fn node_key(node: &Node) -> usize {
+    std::ptr::from_ref(node) as usize
+}
+
+fn fold(c: char, options: FindOptions) -> char {
+    if options.case_sensitive {
+        c
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+/// A text node, along with its text
+/// as the renderer shows it.
+struct Piece<'a> {
+    node: usize,
+    text: &'a str,
+}
+
+/// Collects the text nodes of the document,
+/// grouped by the block they're in (a paragraph, heading, etc).
+fn find_text<'a>(ir: &'a Ir, index: usize, blocks: &mut Vec<Vec<Piece<'a>>>) {
+    let node = &ir.nodes[index];
+    if let IrKind::Text(text) = &node.kind {
+        if let Some(block) = blocks.last_mut() {
+            block.push(Piece {
+                node: node_key(&node.handle),
+                text,
+            });
+        }
+        return;
+    }
+
+    if node.block {
+        blocks.push(Vec::new());
+    }
+    for child in ir.children(index) {
+        find_text(ir, child, blocks);
+    }
+    if node.block {
+        blocks.push(Vec::new());
+    }
+}
+
+fn search_block(block: &[Piece], query: &[char], options: FindOptions, out: &mut Vec<FindMatch>) {
+    // Every char in the block, with the piece
+    // it came from and its byte range in there.
+    let chars: Vec<(char, usize, Range<usize>)> = block
+        .iter()
+        .enumerate()
+        .flat_map(|(i, piece)| {
+            piece
+                .text
+                .char_indices()
+                .map(move |(start, c)| (c, i, start..start + c.len_utf8()))
+        })
+        .collect();
+
+    let is_word = |i: usize| {
+        chars
+            .get(i)
+            .is_some_and(|(c, _, _)| c.is_alphanumeric() || *c == '_')
+    };
+
+    let mut start = 0;
+    while start + query.len() <= chars.len() {
+        let end = start + query.len();
+        let found = chars[start..end]
+            .iter()
+            .zip(query)
+            .all(|((c, _, _), q)| fold(*c, options) == *q);
+        let whole = !options.whole_word
+            || !((start > 0 && is_word(start - 1) && is_word(start))
+                || (is_word(end - 1) && is_word(end)));
+        if !(found && whole) {
+            start += 1;
+            continue;
+        }
+
+        let mut pieces: Vec<(usize, Range<usize>)> = Vec::new();
+        for (_, piece, range) in &chars[start..end] {
+            let node = block[*piece].node;
+            match pieces.last_mut() {
+                Some((last, last_range)) if *last == node => last_range.end = range.end,
+                _ => pieces.push((node, range.clone())),
+            }
+        }
+        out.push(FindMatch {
+            text: chars[start..end].iter().map(|(c, _, _)| *c).collect(),
+            pieces,
+        });
+        start = end;
+    }
+}
+
+/// Scrolls the [`iced::widget::Scrollable`] with the given id
+/// so the text node is visible (if it isn't already).
+pub(crate) fn scroll_to_node<M: Send + 'static>(scrollable: impl Into<Id>, node: usize) -> Task<M> {
+    widget::operate(FindNode {
+        scrollable: scrollable.into(),
+        node,
+        viewport: None,
+        target: None,
+    })
+    .discard()
+}
+
+struct FindNode {
+    scrollable: Id,
+    node: usize,
+    /// Bounds, content bounds and scroll offset
+    /// of the target scrollable.
+    viewport: Option<(Rectangle, Rectangle, Vector)>,
+    target: Option<Rectangle>,
+}
+
+impl Operation for FindNode {
+    fn traverse(&mut self, operate: &mut dyn FnMut(&mut dyn Operation)) {
+        operate(self);
+    }
+
+    fn scrollable(
+        &mut self,
+        id: Option<&Id>,
+        bounds: Rectangle,
+        content_bounds: Rectangle,
+        translation: Vector,
+        _state: &mut dyn Scrollable,
+    ) {
+        if id == Some(&self.scrollable) {
+            self.viewport = Some((bounds, content_bounds, translation));
+        }
+    }
+
+    fn custom(&mut self, _id: Option<&Id>, bounds: Rectangle, state: &mut dyn std::any::Any) {
+        if self.viewport.is_none() || self.target.is_some() {
+            return;
+        }
+        // Blocks of text expose the nodes they were made from
+        // (see `crate::selection::SelectableBlock`)
+        if let Some(block) = state.downcast_ref::<crate::selection::Block>() {
+            if block.nodes.iter().any(|n| node_key(n) == self.node) {
+                self.target = Some(bounds);
+            }
+        }
+    }
+
+    fn finish(&self) -> Outcome<()> {
+        let (Some((bounds, content, translation)), Some(target)) = (self.viewport, self.target)
+        else {
+            return Outcome::None;
+        };
+        let top = target.y - content.y;
+        if top >= translation.y && top + target.height <= translation.y + bounds.height {
+            return Outcome::None;
+        }
+        // Put it in the middle of the screen
+        let y = (top - (bounds.height - target.height).max(0.0) / 2.0).max(0.0);
+        Outcome::Chain(Box::new(scrollable::scroll_to(
+            self.scrollable.clone(),
+            AbsoluteOffset {
+                x: None,
+                y: Some(y),
+            },
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{search_block, FindOptions, FindState, Piece};
+    use crate::{ir::IrKind, MarkState};
+
+    fn find(html: &str, query: &str, options: FindOptions) -> Vec<String> {
+        let state = MarkState::with_html(html);
+        FindState::new(&state.ir, query, options)
+            .matches
+            .into_iter()
+            .map(|n| n.text)
+            .collect()
+    }
+
+    #[test]
+    fn options() {
+        let html = "<p>Cat concatenate CAT cat_food cat.</p>";
+        let case_sensitive = FindOptions {
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let whole_word = FindOptions {
+            whole_word: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            find(html, "cat", FindOptions::default()),
+            ["Cat", "cat", "CAT", "cat", "cat"]
+        );
+        assert_eq!(find(html, "cat", case_sensitive), ["cat", "cat", "cat"]);
+        assert_eq!(find(html, "CAT", whole_word), ["Cat", "CAT", "cat"]);
+        assert!(find(html, "", FindOptions::default()).is_empty());
+    }
+
+    #[test]
+    fn matches_across_nodes() {
+        let state = MarkState::with_html("<p>foo<b>bar</b></p><p>foo</p><p>bar</p>");
+        let find = FindState::new(&state.ir, "obar", FindOptions::default());
+        assert_eq!(find.matches.len(), 1);
+        let m = &find.matches[0];
+        assert_eq!(m.text, "obar");
+        assert_eq!(m.pieces.len(), 2);
+        assert_eq!(m.pieces[0].1, 2..3);
+        assert_eq!(m.pieces[1].1, 0..3);
+
+        // Paragraphs are searched separately,
+        // so the second `foo` and `bar` don't match
+        let find = FindState::new(&state.ir, "foobar", FindOptions::default());
+        assert_eq!(find.matches.len(), 1);
+    }
+
+    #[test]
+    fn search_block_pieces() {
+        let block = [
+            Piece {
+                node: 1,
+                text: "ab",
+            },
+            Piece {
+                node: 2, text: "é"
+            },
+            Piece {
+                node: 3,
+                text: "ab",
+            },
+        ];
+        let query: Vec<char> = "bé".chars().collect();
+        let mut out = Vec::new();
+        search_block(&block, &query, FindOptions::default(), &mut out);
+        assert_eq!(out.len(), 1);
+        assert_eq!(out[0].pieces, [(1, 1..2), (2, 0..2)]);
+
+        // Matches don't overlap
+        let block = [Piece {
+            node: 1,
+            text: "aaaa",
+        }];
+        let mut out = Vec::new();
+        search_block(&block, &['a', 'a'], FindOptions::default(), &mut out);
+        assert_eq!(out.len(), 2);
+        assert_eq!(out[1].pieces, [(1, 2..4)]);
+    }
+
+    #[test]
+    fn segments() {
+        let state = MarkState::with_html("<p>one two one</p>");
+        let find = FindState::new(&state.ir, "one", FindOptions::default());
+        let text = state
+            .ir
+            .nodes
+            .iter()
+            .find(|n| matches!(n.kind, IrKind::Text(_)))
+            .map(|n| n.handle.clone())
+            .unwrap();
+
+        assert_eq!(
+            find.segments(&text, "one two one"),
+            [("one", Some(0)), (" two ", None), ("one", Some(1))]
+        );
+        // Nodes without matches are left whole
+        let none = FindState::new(&state.ir, "three", FindOptions::default());
+        assert_eq!(none.segments(&text, "one two one"), [("one two one", None)]);
+    }
+
+    #[test]
+    fn refresh_keeps_current() {
+        let mut state = MarkState::with_html("<p>a a a</p>");
+        let mut find = FindState::new(&state.ir, "a", FindOptions::default());
+        find.current = Some(2);
+
+        state = MarkState::with_html("<p>a a a a</p>");
+        assert_eq!(find.refresh(&state.ir).current, Some(2));
+
+        // Clamped to the last match left
+        state = MarkState::with_html("<p>a</p>");
+        assert_eq!(find.refresh(&state.ir).current, Some(0));
+
+        state = MarkState::with_html("<p>b</p>");
+        assert_eq!(find.refresh(&state.ir).current, None);
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::HashMap;
+
+use iced::widget::text_editor;
+use markup5ever_rcdom::{Node, NodeData};
+
+use crate::{
+    find::node_key,
+    state::{get_text, StableIds},
+    structs::StableId,
+};
+
+/// A form that was submitted, sent to
+/// [`crate::MarkWidget::on_submitting_form`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormSubmit {
+    /// The `action` attribute of the `<form>`
+    /// (where it should be sent), if any.
+    pub action: Option<String>,
+    /// The `method` attribute of the `<form>`,
+    /// in lowercase (`get` if it has none).
+    pub method: String,
+    /// The name and value of every field, in document order.
+    ///
+    /// Like in a browser, fields without a `name`, disabled fields
+    /// and unchecked checkboxes/radio buttons are left out.
+    /// The button that submitted the form is included if it has a `name`.
+    pub fields: Vec<(String, String)>,
+}
+
+/// The forms in a document and their fields.
+#[derive(Default)]
+pub(crate) struct Forms {
+    pub forms: Vec<Form>,
+    pub fields: HashMap<StableId, Field>,
+}
+
+pub(crate) struct Form {
+    pub action: Option<String>,
+    pub method: String,
+    /// In document order.
+    pub fields: Vec<StableId>,
+}
+
+/// An `<input>`, `<button>`, `<select>` or `<textarea>`.
+pub(crate) struct Field {
+    pub name: String,
+    pub kind: FieldKind,
+    /// The `value` attribute, or the text of a `<textarea>`.
+    pub value: String,
+    /// For checkboxes and radio buttons.
+    pub checked: bool,
+    pub disabled: bool,
+    /// Index of the `<form>` it's in.
+    pub form: Option<usize>,
+}
+
+pub(crate) enum FieldKind {
+    Text {
+        secure: bool,
+        placeholder: String,
+    },
+    Hidden,
+    Checkbox,
+    Radio,
+    Select {
+        options: Vec<SelectOption>,
+        selected: Option<usize>,
+    },
+    TextArea,
+    Submit,
+    Reset,
+    Button,
+}
+
+/// An `<option>` of a `<select>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SelectOption {
+    pub label: String,
+    pub value: String,
+}
+
+impl std::fmt::Display for SelectOption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.label)
+    }
+}
+
+/// The current value of a [`Field`], kept in [`crate::MarkState`].
+pub(crate) enum FieldValue {
+    Text(String),
+    Checked(bool),
+    Selected(Option<usize>),
+    TextArea(text_editor::Content),
+}
+
+impl Field {
+    /// The value the field starts with
+    /// (and goes back to when its form is reset).
+    pub fn initial(&self) -> Option<FieldValue> {
+        match &self.kind {
+            FieldKind::Text { .. } => Some(FieldValue::Text(self.value.clone())),
+            FieldKind::Checkbox | FieldKind::Radio => Some(FieldValue::Checked(self.checked)),
+            FieldKind::Select { selected, .. } => Some(FieldValue::Selected(*selected)),
+            FieldKind::TextArea => Some(FieldValue::TextArea(text_editor::Content::with_text(
+                &self.value,
+            ))),
+            FieldKind::Hidden | FieldKind::Submit | FieldKind::Reset | FieldKind::Button => None,
+        }
+    }
+
+    fn submitted_value(&self, value: Option<&FieldValue>, is_submitter: bool) -> Option<String> {
+        match (&self.kind, value) {
+            (FieldKind::Text { .. }, Some(FieldValue::Text(text))) => Some(text.clone()),
+            (FieldKind::Checkbox | FieldKind::Radio, Some(FieldValue::Checked(checked))) => checked
+                .then(|| {
+                    if self.value.is_empty() {
+                        "on".to_owned()
+                    } else {
+                        self.value.clone()
+                    }
+                }),
+            (FieldKind::Select { options, .. }, Some(FieldValue::Selected(selected))) => selected
+                .and_then(|n| options.get(n))
+                .map(|n| n.value.clone()),
+            (FieldKind::TextArea, Some(FieldValue::TextArea(content))) => Some(content.text()),
+            (FieldKind::Hidden, _) => Some(self.value.clone()),
+            (FieldKind::Submit, _) if is_submitter => Some(self.value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Forms {
+    /// Collects the fields of a form for submitting it,
+    /// optionally by the button `submitter`.
+    pub fn submit(
+        &self,
+        form: usize,
+        values: &HashMap<StableId, FieldValue>,
+        submitter: Option<StableId>,
+    ) -> FormSubmit {
+        let form = &self.forms[form];
+        let fields = form
+            .fields
+            .iter()
+            .filter_map(|id| {
+                let field = self.fields.get(id)?;
+                if field.disabled || field.name.is_empty() {
+                    return None;
+                }
+                let value = field.submitted_value(values.get(id), submitter == Some(*id))?;
+                Some((field.name.clone(), value))
+            })
+            .collect();
+
+        FormSubmit {
+            action: form.action.clone(),
+            method: form.method.clone(),
+            fields,
+        }
+    }
+
+    /// Other radio buttons in the same group as `id`.
+    pub fn radio_group(&self, id: StableId) -> Vec<StableId> {
+        let Some(field) = self.fields.get(&id) else {
+            return Vec::new();
+        };
+        if !matches!(field.kind, FieldKind::Radio) || field.name.is_empty() {
+            return Vec::new();
+        }
+        self.fields
+            .iter()
+            .filter(|(other, n)| {
+                **other != id
+                    && matches!(n.kind, FieldKind::Radio)
+                    && n.name == field.name
+                    && n.form == field.form
+            })
+            .map(|(other, _)| *other)
+            .collect()
+    }
+}
+
+/// Finds the forms and fields in the document.
+///
+/// Checkboxes in `skip` are task list items
+/// (see [`crate::MarkState::task_progress`]), not fields.
+pub(crate) fn find_forms(
+    node: &Node,
+    form: Option<usize>,
+    ids: &mut StableIds,
+    forms: &mut Forms,
+    skip: &HashMap<usize, usize>,
+) {
+    let NodeData::Element { name, attrs, .. } = &node.data else {
+        for child in &*node.children.borrow() {
+            find_forms(child, form, ids, forms, skip);
+        }
+        return;
+    };
+    let attrs = attrs.borrow();
+    let attr = |n: &str| {
+        attrs
+            .iter()
+            .find(|a| &*a.name.local == n)
+            .map(|a| a.value.to_string())
+    };
+
+    let kind = match &*name.local {
+        "form" => {
+            forms.forms.push(Form {
+                action: attr("action"),
+                method: attr("method").map_or_else(|| "get".to_owned(), |n| n.to_ascii_lowercase()),
+                fields: Vec::new(),
+            });
+            let form = Some(forms.forms.len() - 1);
+            for child in &*node.children.borrow() {
+                find_forms(child, form, ids, forms, skip);
+            }
+            return;
+        }
+        "input" if skip.contains_key(&node_key(node)) => return,
+        "input" => {
+            let kind = attr("type").unwrap_or_default().to_ascii_lowercase();
+            match kind.as_str() {
+                "hidden" => FieldKind::Hidden,
+                "checkbox" => FieldKind::Checkbox,
+                "radio" => FieldKind::Radio,
+                "submit" | "image" => FieldKind::Submit,
+                "reset" => FieldKind::Reset,
+                "button" => FieldKind::Button,
+                _ => FieldKind::Text {
+                    secure: kind == "password",
+                    placeholder: attr("placeholder").unwrap_or_default(),
+                },
+            }
+        }
+        "button" => match attr("type").map(|n| n.to_ascii_lowercase()).as_deref() {
+            Some("reset") => FieldKind::Reset,
+            Some("button") => FieldKind::Button,
+            _ => FieldKind::Submit,
+        },
+        "select" => {
+            let mut options = Vec::new();
+            let mut selected = None;
+            find_options(node, &mut options, &mut selected);
+            if selected.is_none() && !options.is_empty() {
+                selected = Some(0);
+            }
+            FieldKind::Select { options, selected }
+        }
+        "textarea" => FieldKind::TextArea,
+        _ => {
+            for child in &*node.children.borrow() {
+                find_forms(child, form, ids, forms, skip);
+            }
+            return;
+        }
+    };
+
+    let value = if let FieldKind::TextArea = kind {
+        let mut text = String::new();
+        get_text(node, &mut text);
+        text
+    } else {
+        attr("value").unwrap_or_default()
+    };
+    let field = Field {
+        name: attr("name").unwrap_or_default(),
+        kind,
+        checked: attr("checked").is_some(),
+        disabled: attr("disabled").is_some(),
+        value,
+        form,
+    };
+    drop(attrs);
+
+    // Checkboxes and radio buttons with the same name
+    // are told apart by their value
+    let content = match field.kind {
+        FieldKind::Checkbox | FieldKind::Radio => format!("{}={}", field.name, field.value),
+        _ => field.name.clone(),
+    };
+    let id = ids.make(
+        node,
+        &format!("{}:{}", name.local, form.unwrap_or(usize::MAX)),
+        &content,
+    );
+
+    if let Some(value) = field.initial() {
+        ids.form_state.insert(id, value);
+    }
+    if let Some(form) = form {
+        forms.forms[form].fields.push(id);
+    }
+    forms.fields.insert(id, field);
+}
+
+fn find_options(node: &Node, options: &mut Vec<SelectOption>, selected: &mut Option<usize>) {
+    for child in &*node.children.borrow() {
+        let NodeData::Element { name, attrs, .. } = &child.data else {
+            continue;
+        };
+        match &*name.local {
+            "option" => {
+                let attrs = attrs.borrow();
+                let attr = |n: &str| attrs.iter().find(|a| &*a.name.local == n);
+
+                let mut text = String::new();
+                get_text(child, &mut text);
+                let label = attr("label").map_or_else(
+                    || text.split_whitespace().collect::<Vec<_>>().join(" "),
+                    |n| n.value.to_string(),
+                );
+                if attr("selected").is_some() {
+                    *selected = Some(options.len());
+                }
+                options.push(SelectOption {
+                    value: attr("value").map_or_else(|| label.clone(), |n| n.value.to_string()),
+                    label,
+                });
+            }
+            "optgroup" => find_options(child, options, selected),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::{FieldKind, FieldValue, FormSubmit};
+    use crate::{structs::StableId, MarkState};
+
+    /// The id of the field with this name (and value).
+    fn field(state: &MarkState, name: &str, value: &str) -> StableId {
+        *state
+            .forms
+            .fields
+            .iter()
+            .find(|(_, n)| n.name == name && n.value == value)
+            .unwrap()
+            .0
+    }
+
+    fn fields(submit: &FormSubmit) -> Vec<(&str, &str)> {
+        submit
+            .fields
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn submitted_fields() {
+        let state = MarkState::with_html(
+            r#"<form action="/search">
+                <input name="q" value="cats">
+                <input value="no name">
+                <input name="off" value="x" disabled>
+                <input type="hidden" name="page" value="2">
+                <input type="checkbox" name="safe" checked>
+                <input type="checkbox" name="fuzzy" value="yes">
+                <select name="sort">
+                    <option value="new">Newest</option>
+                    <option>Oldest</option>
+                </select>
+                <textarea name="notes">some
+notes</textarea>
+                <button name="go" value="1">Go</button>
+                <button name="lucky" value="1">Lucky</button>
+            </form>"#,
+        );
+        let submit = state
+            .forms
+            .submit(0, &state.form_state, Some(field(&state, "lucky", "1")));
+
+        assert_eq!(submit.action.as_deref(), Some("/search"));
+        assert_eq!(submit.method, "get");
+        assert_eq!(
+            fields(&submit),
+            [
+                ("q", "cats"),
+                ("page", "2"),
+                ("safe", "on"),
+                ("sort", "new"),
+                ("notes", "some\nnotes"),
+                ("lucky", "1"),
+            ]
+        );
+
+        // Without a submitter (eg: pressing enter), no button is sent
+        let submit = state.forms.submit(0, &state.form_state, None);
+        assert!(submit.fields.iter().all(|(name, _)| name != "lucky"));
+    }
+
+    #[test]
+    fn selected_options() {
+        let state = MarkState::with_html(
+            r#"<form method="POST">
+                <select name="a"><option>x</option><option selected>y</option></select>
+                <select name="b"><optgroup><option label="Z" value="z">zed</option></optgroup></select>
+                <select name="c"></select>
+            </form>"#,
+        );
+        let submit = state.forms.submit(0, &state.form_state, None);
+        assert_eq!(submit.method, "post");
+        assert_eq!(fields(&submit), [("a", "y"), ("b", "z")]);
+
+        let values = HashMap::from([(field(&state, "a", ""), FieldValue::Selected(Some(0)))]);
+        assert_eq!(fields(&state.forms.submit(0, &values, None)), [("a", "x")]);
+
+        let FieldKind::Select { options, .. } = &state.forms.fields[&field(&state, "b", "")].kind
+        else {
+            panic!("not a select");
+        };
+        assert_eq!((&*options[0].label, &*options[0].value), ("Z", "z"));
+    }
+
+    #[test]
+    fn radio_groups() {
+        let state = MarkState::with_html(
+            r#"<form>
+                <input type="radio" name="size" value="s" checked>
+                <input type="radio" name="size" value="m">
+                <input type="radio" name="color" value="red">
+            </form>
+            <form><input type="radio" name="size" value="l"></form>"#,
+        );
+        let small = field(&state, "size", "s");
+        assert_eq!(state.forms.radio_group(small), [field(&state, "size", "m")]);
+        assert!(state
+            .forms
+            .radio_group(field(&state, "color", "red"))
+            .is_empty());
+
+        let submit = state.forms.submit(0, &state.form_state, None);
+        assert_eq!(fields(&submit), [("size", "s")]);
+    }
+}
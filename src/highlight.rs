@@ -0,0 +1,718 @@
+//! A small, dependency-free syntax highlighter for code blocks.
+//!
+//! It doesn't try to be a real parser, just a lexer that knows about
+//! comments, strings, numbers and keywords for a handful of languages.
+
+use std::ops::Range;
+
+use iced::{advanced::text::highlighter, Color, Font};
+
+/// The color scheme used to highlight code blocks.
+///
+/// See [`crate::MarkWidget::highlight_theme`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HighlightTheme {
+    /// For light backgrounds.
+    Light,
+    /// For dark backgrounds.
+    #[default]
+    Dark,
+}
+
+impl HighlightTheme {
+    fn color(self, token: Token) -> Color {
+        let rgb = match (self, token) {
+            (Self::Light, Token::Comment) => 0x6A_73_7D,
+            (Self::Light, Token::String) => 0x0A_30_69,
+            (Self::Light, Token::Number | Token::Literal) => 0x00_5C_C5,
+            (Self::Light, Token::Keyword) => 0xCF_22_2E,
+            (Self::Light, Token::Type) => 0x95_38_00,
+            (Self::Light, Token::Function) => 0x82_50_DF,
+
+            (Self::Dark, Token::Comment) => 0x7F_84_8E,
+            (Self::Dark, Token::String) => 0x98_C3_79,
+            (Self::Dark, Token::Number | Token::Literal) => 0xD1_9A_66,
+            (Self::Dark, Token::Keyword) => 0xC6_78_DD,
+            (Self::Dark, Token::Type) => 0xE5_C0_7B,
+            (Self::Dark, Token::Function) => 0x61_AF_EF,
+        };
+        Color::from_rgb8((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Comment,
+    String,
+    Number,
+    Literal,
+    Keyword,
+    Type,
+    Function,
+}
+
+struct Language {
+    names: &'static [&'static str],
+    keywords: &'static [&'static str],
+    /// Builtin types. Capitalized identifiers are
+    /// also treated as types if `capitalized_types` is set.
+    types: &'static [&'static str],
+    literals: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    /// String delimiters, longest first.
+    strings: &'static [&'static str],
+    /// `'x'` is a character literal, but a lone `'` isn't a string
+    /// (eg: Rust lifetimes).
+    char_literals: bool,
+    capitalized_types: bool,
+}
+
+const C_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "const", "continue", "default", "do", "else", "enum", "extern", "for",
+    "goto", "if", "inline", "register", "restrict", "return", "sizeof", "static", "struct",
+    "switch", "typedef", "union", "volatile", "while", "#include", "#define", "#if", "#ifdef",
+    "#ifndef", "#endif", "#else", "#pragma",
+];
+const C_TYPES: &[&str] = &[
+    "void", "char", "short", "int", "long", "float", "double", "signed", "unsigned", "bool",
+    "size_t", "int8_t", "int16_t", "int32_t", "int64_t", "uint8_t", "uint16_t", "uint32_t",
+    "uint64_t",
+];
+
+static LANGUAGES: &[Language] = &[
+    Language {
+        names: &["rust", "rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+            "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+            "mut", "pub", "ref", "return", "static", "struct", "super", "trait", "type", "unsafe",
+            "use", "where", "while", "yield",
+        ],
+        types: &[
+            "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+            "i64", "i128", "isize", "f32", "f64", "self",
+        ],
+        literals: &["true", "false", "None", "Some", "Ok", "Err", "Self"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        strings: &["\""],
+        char_literals: true,
+        capitalized_types: true,
+    },
+    Language {
+        names: &["c", "h"],
+        keywords: C_KEYWORDS,
+        types: C_TYPES,
+        literals: &["NULL", "true", "false"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        strings: &["\""],
+        char_literals: true,
+        capitalized_types: false,
+    },
+    Language {
+        names: &["cpp", "c++", "cc", "cxx", "hpp"],
+        keywords: &[
+            "auto",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "constexpr",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "else",
+            "enum",
+            "explicit",
+            "extern",
+            "for",
+            "friend",
+            "goto",
+            "if",
+            "inline",
+            "namespace",
+            "new",
+            "operator",
+            "private",
+            "protected",
+            "public",
+            "return",
+            "sizeof",
+            "static",
+            "struct",
+            "switch",
+            "template",
+            "this",
+            "throw",
+            "try",
+            "typedef",
+            "typename",
+            "union",
+            "using",
+            "virtual",
+            "volatile",
+            "while",
+            "#include",
+            "#define",
+            "#if",
+            "#ifdef",
+            "#ifndef",
+            "#endif",
+            "#else",
+            "#pragma",
+        ],
+        types: C_TYPES,
+        literals: &["nullptr", "NULL", "true", "false"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        strings: &["\""],
+        char_literals: true,
+        capitalized_types: true,
+    },
+    Language {
+        names: &["java", "kotlin", "kt", "cs", "csharp", "c#"],
+        keywords: &[
+            "abstract",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "continue",
+            "default",
+            "do",
+            "else",
+            "enum",
+            "extends",
+            "final",
+            "finally",
+            "for",
+            "fun",
+            "if",
+            "implements",
+            "import",
+            "interface",
+            "internal",
+            "is",
+            "namespace",
+            "new",
+            "override",
+            "package",
+            "private",
+            "protected",
+            "public",
+            "return",
+            "static",
+            "switch",
+            "this",
+            "throw",
+            "throws",
+            "try",
+            "using",
+            "val",
+            "var",
+            "void",
+            "when",
+            "while",
+        ],
+        types: &[
+            "boolean", "bool", "byte", "char", "double", "float", "int", "long", "short", "string",
+        ],
+        literals: &["true", "false", "null"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        strings: &["\"\"\"", "\""],
+        char_literals: true,
+        capitalized_types: true,
+    },
+    Language {
+        names: &["javascript", "js", "jsx", "mjs", "typescript", "ts", "tsx"],
+        keywords: &[
+            "async",
+            "await",
+            "break",
+            "case",
+            "catch",
+            "class",
+            "const",
+            "continue",
+            "default",
+            "delete",
+            "do",
+            "else",
+            "export",
+            "extends",
+            "finally",
+            "for",
+            "from",
+            "function",
+            "if",
+            "import",
+            "in",
+            "instanceof",
+            "interface",
+            "let",
+            "new",
+            "of",
+            "return",
+            "static",
+            "switch",
+            "this",
+            "throw",
+            "try",
+            "type",
+            "typeof",
+            "var",
+            "void",
+            "while",
+            "yield",
+        ],
+        types: &["number", "string", "boolean", "any", "unknown", "never"],
+        literals: &["true", "false", "null", "undefined", "NaN"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        strings: &["\"", "'", "`"],
+        char_literals: false,
+        capitalized_types: true,
+    },
+    Language {
+        names: &["python", "py"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+            "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in",
+            "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+            "with", "yield",
+        ],
+        types: &[
+            "int", "float", "str", "bool", "list", "dict", "set", "tuple", "bytes", "self",
+        ],
+        literals: &["True", "False", "None"],
+        line_comments: &["#"],
+        block_comment: None,
+        strings: &["\"\"\"", "'''", "\"", "'"],
+        char_literals: false,
+        capitalized_types: true,
+    },
+    Language {
+        names: &["go", "golang"],
+        keywords: &[
+            "break",
+            "case",
+            "chan",
+            "const",
+            "continue",
+            "default",
+            "defer",
+            "else",
+            "fallthrough",
+            "for",
+            "func",
+            "go",
+            "goto",
+            "if",
+            "import",
+            "interface",
+            "map",
+            "package",
+            "range",
+            "return",
+            "select",
+            "struct",
+            "switch",
+            "type",
+            "var",
+        ],
+        types: &[
+            "bool", "byte", "error", "float32", "float64", "int", "int8", "int16", "int32",
+            "int64", "rune", "string", "uint", "uint8", "uint16", "uint32", "uint64",
+        ],
+        literals: &["true", "false", "nil", "iota"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        strings: &["\"", "`"],
+        char_literals: true,
+        capitalized_types: false,
+    },
+    Language {
+        names: &["lua"],
+        keywords: &[
+            "and", "break", "do", "else", "elseif", "end", "for", "function", "goto", "if", "in",
+            "local", "not", "or", "repeat", "return", "then", "until", "while",
+        ],
+        types: &[],
+        literals: &["true", "false", "nil"],
+        line_comments: &["--"],
+        block_comment: None,
+        strings: &["\"", "'"],
+        char_literals: false,
+        capitalized_types: false,
+    },
+    Language {
+        names: &["sh", "bash", "shell", "zsh", "console"],
+        keywords: &[
+            "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case",
+            "esac", "in", "function", "return", "export", "local", "sudo", "cd", "echo",
+        ],
+        types: &[],
+        literals: &["true", "false"],
+        line_comments: &["#"],
+        block_comment: None,
+        strings: &["\"", "'"],
+        char_literals: false,
+        capitalized_types: false,
+    },
+    Language {
+        names: &["toml", "ini"],
+        keywords: &[],
+        types: &[],
+        literals: &["true", "false"],
+        line_comments: &["#", ";"],
+        block_comment: None,
+        strings: &["\"\"\"", "'''", "\"", "'"],
+        char_literals: false,
+        capitalized_types: false,
+    },
+    Language {
+        names: &["json", "jsonc"],
+        keywords: &[],
+        types: &[],
+        literals: &["true", "false", "null"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        strings: &["\""],
+        char_literals: false,
+        capitalized_types: false,
+    },
+    Language {
+        names: &["html", "xml", "svg"],
+        keywords: &[],
+        types: &[],
+        literals: &[],
+        line_comments: &[],
+        block_comment: Some(("<!--", "-->")),
+        strings: &["\"", "'"],
+        char_literals: false,
+        capitalized_types: false,
+    },
+];
+
+/// A language known to the highlighter.
+#[derive(Clone, Copy)]
+pub struct Syntax(&'static Language);
+
+impl Syntax {
+    /// Finds the language for a code block from its
+    /// name (eg: `rust`) or file extension (eg: `rs`).
+    pub fn find(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        LANGUAGES
+            .iter()
+            .find(|n| n.names.contains(&name.as_str()))
+            .map(Self)
+    }
+}
+
+impl PartialEq for Syntax {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl std::fmt::Debug for Syntax {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Syntax({})", self.0.names[0])
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub syntax: Syntax,
+    pub theme: HighlightTheme,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Normal,
+    Comment(&'static str),
+    String(&'static str),
+}
+
+pub struct Highlighter {
+    settings: Settings,
+    /// The state at the start of each line seen so far.
+    states: Vec<State>,
+    current_line: usize,
+}
+
+impl highlighter::Highlighter for Highlighter {
+    type Settings = Settings;
+    type Highlight = Color;
+    type Iterator<'a> = std::vec::IntoIter<(Range<usize>, Color)>;
+
+    fn new(settings: &Self::Settings) -> Self {
+        Self {
+            settings: settings.clone(),
+            states: vec![State::Normal],
+            current_line: 0,
+        }
+    }
+
+    fn update(&mut self, new_settings: &Self::Settings) {
+        *self = Self::new(new_settings);
+    }
+
+    fn change_line(&mut self, line: usize) {
+        self.current_line = self.current_line.min(line);
+        self.states.truncate(self.current_line + 1);
+    }
+
+    fn highlight_line(&mut self, line: &str) -> Self::Iterator<'_> {
+        let mut state = self
+            .states
+            .get(self.current_line)
+            .copied()
+            .unwrap_or(State::Normal);
+        let tokens = lex_line(self.settings.syntax.0, line, &mut state);
+
+        self.current_line += 1;
+        self.states.truncate(self.current_line);
+        self.states.push(state);
+
+        let theme = self.settings.theme;
+        tokens
+            .into_iter()
+            .map(|(range, token)| (range, theme.color(token)))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    fn current_line(&self) -> usize {
+        self.current_line
+    }
+}
+
+pub fn to_format<T>(color: &Color, _theme: &T) -> highlighter::Format<Font> {
+    highlighter::Format {
+        color: Some(*color),
+        font: None,
+    }
+}
+
+fn lex_line(lang: &Language, line: &str, state: &mut State) -> Vec<(Range<usize>, Token)> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < line.len() {
+        let rest = &line[i..];
+        match *state {
+            State::Comment(end) => {
+                let stop = rest.find(end).map_or(line.len(), |n| {
+                    *state = State::Normal;
+                    i + n + end.len()
+                });
+                out.push((i..stop, Token::Comment));
+                i = stop;
+                continue;
+            }
+            State::String(quote) => {
+                let stop = find_string_end(rest, quote).map_or(line.len(), |n| {
+                    *state = State::Normal;
+                    i + n
+                });
+                out.push((i..stop, Token::String));
+                i = stop;
+                continue;
+            }
+            State::Normal => {}
+        }
+
+        if lang.line_comments.iter().any(|n| rest.starts_with(n)) {
+            out.push((i..line.len(), Token::Comment));
+            break;
+        }
+        if let Some((start, end)) = lang.block_comment.filter(|(n, _)| rest.starts_with(n)) {
+            out.push((i..i + start.len(), Token::Comment));
+            i += start.len();
+            *state = State::Comment(end);
+            continue;
+        }
+        if let Some(quote) = lang.strings.iter().find(|n| rest.starts_with(*n)) {
+            out.push((i..i + quote.len(), Token::String));
+            i += quote.len();
+            *state = State::String(quote);
+            continue;
+        }
+        if lang.char_literals && rest.starts_with('\'') {
+            if let Some(len) = char_literal_len(rest) {
+                out.push((i..i + len, Token::String));
+                i += len;
+                continue;
+            }
+        }
+
+        let c = rest.chars().next().unwrap_or_default();
+        if c.is_ascii_digit() {
+            let len = rest
+                .find(|n: char| !(n.is_ascii_alphanumeric() || n == '_' || n == '.'))
+                .unwrap_or(rest.len());
+            out.push((i..i + len, Token::Number));
+            i += len;
+        } else if c.is_alphabetic() || c == '_' || c == '#' {
+            let len = rest
+                .char_indices()
+                .skip(1)
+                .find(|(_, n)| !(n.is_alphanumeric() || *n == '_'))
+                .map_or(rest.len(), |(n, _)| n);
+            let word = &rest[..len];
+            let next = rest[len..].trim_start().chars().next();
+
+            let token = if lang.keywords.contains(&word) {
+                Some(Token::Keyword)
+            } else if lang.literals.contains(&word) {
+                Some(Token::Literal)
+            } else if lang.types.contains(&word) {
+                Some(Token::Type)
+            } else if matches!(next, Some('(' | '!')) {
+                Some(Token::Function)
+            } else if lang.capitalized_types && c.is_uppercase() {
+                Some(Token::Type)
+            } else {
+                None
+            };
+            if let Some(token) = token {
+                out.push((i..i + len, token));
+            }
+            i += len;
+        } else {
+            i += c.len_utf8();
+        }
+    }
+
+    out
+}
+
+/// Returns the byte index just past the closing `quote`,
+/// skipping over backslash escapes.
+fn find_string_end(input: &str, quote: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in input.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if input[i..].starts_with(quote) {
+            return Some(i + quote.len());
+        }
+    }
+    None
+}
+
+/// Length of a `'x'` or `'\n'` style character literal
+/// at the start of `input`, if there is one.
+fn char_literal_len(input: &str) -> Option<usize> {
+    let mut chars = input.char_indices().skip(1);
+    let (_, c) = chars.next()?;
+    if c == '\\' {
+        let end = input[2..].find('\'')?;
+        (end <= 8).then_some(end + 3)
+    } else {
+        let (i, close) = chars.next()?;
+        (close == '\'').then_some(i + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lex_line, State, Syntax, Token};
+
+    /// Lexes lines one after another, like a code block.
+    fn lex<'a>(lang: &str, lines: &[&'a str]) -> Vec<Vec<(&'a str, Token)>> {
+        let lang = Syntax::find(lang).unwrap().0;
+        let mut state = State::Normal;
+        lines
+            .iter()
+            .map(|line| {
+                lex_line(lang, line, &mut state)
+                    .into_iter()
+                    .map(|(range, token)| (&line[range], token))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn block_comments_across_lines() {
+        let lines = lex("rust", &["let a = /* one", "two", "three */ b()"]);
+        assert_eq!(
+            lines,
+            [
+                vec![
+                    ("let", Token::Keyword),
+                    ("/*", Token::Comment),
+                    (" one", Token::Comment)
+                ],
+                vec![("two", Token::Comment)],
+                vec![("three */", Token::Comment), ("b", Token::Function)],
+            ]
+        );
+    }
+
+    #[test]
+    fn escaped_quotes() {
+        let lines = lex("js", &[r#"let s = "a \"b\" c"; f()"#]);
+        assert_eq!(
+            lines[0],
+            [
+                ("let", Token::Keyword),
+                ("\"", Token::String),
+                (r#"a \"b\" c""#, Token::String),
+                ("f", Token::Function),
+            ]
+        );
+
+        // A string doesn't end at an escaped quote on the end of the line
+        let lines = lex("python", &[r#"s = "a \""#, r#"b" + 1"#]);
+        assert_eq!(lines[1], [("b\"", Token::String), ("1", Token::Number)]);
+    }
+
+    #[test]
+    fn char_literals_and_lifetimes() {
+        let lines = lex("rust", &[r"fn f<'a>(x: &'a str) -> char { '\n' }", "'x'"]);
+        assert_eq!(
+            lines,
+            [
+                vec![
+                    ("fn", Token::Keyword),
+                    ("str", Token::Type),
+                    ("char", Token::Type),
+                    (r"'\n'", Token::String),
+                ],
+                vec![("'x'", Token::String)],
+            ]
+        );
+    }
+
+    #[test]
+    fn theme_follows_palette() {
+        use super::HighlightTheme;
+        use crate::Style;
+
+        let theme = |theme: &iced::Theme| Style::from(theme).highlight_theme;
+        assert_eq!(theme(&iced::Theme::Light), Some(HighlightTheme::Light));
+        assert_eq!(theme(&iced::Theme::Dracula), Some(HighlightTheme::Dark));
+        assert_eq!(
+            theme(&iced::Theme::SolarizedLight),
+            Some(HighlightTheme::Light)
+        );
+    }
+
+    #[test]
+    fn unknown_languages() {
+        assert_eq!(Syntax::find("RS"), Syntax::find("rust"));
+        // Left as plain text by the renderer
+        assert_eq!(Syntax::find("klingon"), None);
+    }
+}
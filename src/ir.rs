@@ -0,0 +1,468 @@
+use std::{collections::HashMap, rc::Rc};
+
+use html5ever::{Attribute, LocalName};
+use markup5ever_rcdom::{Handle, Node, NodeData};
+
+use crate::css::{CssStyle, SelectorTarget, Stylesheet};
+
+/// The document, lowered from the DOM once when it's parsed,
+/// so that rendering every frame doesn't have to walk
+/// `RefCell`s and clean up text again.
+///
+/// Nodes are stored flat, in document order. Every node knows
+/// where its subtree ends, so its children can be found without
+/// any pointers (see [`Ir::children`]).
+pub(crate) struct Ir {
+    pub nodes: Vec<IrNode>,
+    /// Top-level chunks of the document, see [`IrBlock`].
+    pub blocks: Vec<IrBlock>,
+    /// Quotes starting with `[!KIND]` (GitHub-style alerts),
+    /// with the kind as written.
+    pub alerts: HashMap<usize, String>,
+    /// The text that starts each quote in [`Ir::alerts`], with the quote
+    /// it's in and where the text after the `[!KIND]` starts.
+    /// Only that part is shown if the kind is known.
+    pub alert_texts: HashMap<usize, (usize, usize)>,
+}
+
+pub(crate) struct IrNode {
+    pub kind: IrKind,
+    /// The DOM node this was made from.
+    pub handle: Handle,
+    /// Index right after the last node in this subtree.
+    pub end: usize,
+    /// See [`is_block_element`].
+    pub block: bool,
+    /// From the stylesheet and `style` attribute.
+    pub css: CssStyle,
+    /// The `id`s that scroll to this node (see [`crate::scroll_to_anchor`]).
+    /// Block elements get their own, and those of inline
+    /// elements in them (eg: footnote references).
+    pub anchors: Vec<String>,
+}
+
+/// A top-level chunk of the document (a paragraph, list, table, etc),
+/// for only rendering the visible parts of it
+/// (see [`crate::MarkWidget::virtualized`]).
+pub(crate) struct IrBlock {
+    /// Nodes rendered together as this block.
+    /// Several if they're inline (eg: text directly in `<body>`).
+    pub items: Vec<usize>,
+    /// Number of characters of text.
+    pub chars: usize,
+    /// Number of lines, not counting wrapping.
+    pub lines: usize,
+    pub images: usize,
+    /// Anchors of elements in this block.
+    pub anchors: Vec<String>,
+    /// Text nodes in this block.
+    pub texts: Rc<[Handle]>,
+}
+
+pub(crate) enum IrKind {
+    Document,
+    /// Text as it should be shown, with whitespace
+    /// already cleaned up (except in `<pre>` and `<code>`).
+    Text(String),
+    Element {
+        name: LocalName,
+        attrs: Vec<Attribute>,
+    },
+}
+
+impl Ir {
+    pub const ROOT: usize = 0;
+
+    pub fn new(document: &Handle, stylesheet: &Stylesheet) -> Self {
+        let mut ir = Self {
+            nodes: Vec::new(),
+            blocks: Vec::new(),
+            alerts: HashMap::new(),
+            alert_texts: HashMap::new(),
+        };
+        ir.lower(document, false, stylesheet, &mut Vec::new());
+        ir.find_anchors();
+        ir.find_alerts();
+        ir.blocks = ir.find_blocks();
+        ir
+    }
+
+    /// Splits the children of `<body>` into blocks,
+    /// the same way [`crate::MarkWidget`] lays them out.
+    fn find_blocks(&self) -> Vec<IrBlock> {
+        let mut body = Self::ROOT;
+        for name in ["html", "body"] {
+            if let Some(n) = self.children(body).find(|&n| self.name(n) == Some(name)) {
+                body = n;
+            }
+        }
+
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut inline = false;
+        for child in self.children(body) {
+            let block = self.nodes[child].block;
+            match groups.last_mut() {
+                Some(group) if inline && !block => group.push(child),
+                _ => groups.push(vec![child]),
+            }
+            inline = !block;
+        }
+
+        groups
+            .into_iter()
+            .map(|items| {
+                let mut block = IrBlock {
+                    items,
+                    chars: 0,
+                    lines: 0,
+                    images: 0,
+                    anchors: Vec::new(),
+                    texts: Rc::from([]),
+                };
+                let mut texts = Vec::new();
+                for &item in &block.items {
+                    for node in &self.nodes[item..self.nodes[item].end] {
+                        match &node.kind {
+                            IrKind::Text(text) => {
+                                block.chars += text.chars().count();
+                                block.lines += text.trim_end().matches('\n').count();
+                                texts.push(node.handle.clone());
+                            }
+                            IrKind::Element { name, .. } => {
+                                if node.block {
+                                    block.lines += 1;
+                                }
+                                if &**name == "img" {
+                                    block.images += 1;
+                                }
+                                block.anchors.extend(node.anchors.iter().cloned());
+                            }
+                            IrKind::Document => {}
+                        }
+                    }
+                }
+                block.texts = texts.into();
+                block
+            })
+            .collect()
+    }
+
+    fn lower(
+        &mut self,
+        node: &Handle,
+        keep_whitespace: bool,
+        stylesheet: &Stylesheet,
+        ancestors: &mut Vec<SelectorTarget>,
+    ) {
+        let mut css = CssStyle::default();
+        let (kind, keep_whitespace) = match &node.data {
+            NodeData::Document => (IrKind::Document, keep_whitespace),
+            NodeData::Text { contents } => {
+                let contents = contents.borrow();
+                // Whitespace between elements, not worth showing
+                if contents.trim().is_empty() {
+                    return;
+                }
+                let text = if keep_whitespace {
+                    contents.to_string()
+                } else {
+                    clean_whitespace(&contents)
+                };
+                (IrKind::Text(text), keep_whitespace)
+            }
+            NodeData::Element { name, attrs, .. } => {
+                if matches!(&*name.local, "head" | "title" | "meta" | "style" | "script") {
+                    return;
+                }
+                let attrs = attrs.borrow().clone();
+
+                if !stylesheet.is_empty() {
+                    let target = SelectorTarget::new(&name.local, &attrs);
+                    css = stylesheet.style_for(&target, ancestors);
+                    ancestors.push(target);
+                }
+                if let Some(style) = attrs.iter().find(|n| &*n.name.local == "style") {
+                    css = css.cascade(CssStyle::parse(&style.value));
+                }
+                if css.hidden == Some(true) {
+                    if !stylesheet.is_empty() {
+                        ancestors.pop();
+                    }
+                    return;
+                }
+
+                (
+                    IrKind::Element {
+                        name: name.local.clone(),
+                        attrs,
+                    },
+                    keep_whitespace || matches!(&*name.local, "pre" | "code"),
+                )
+            }
+            NodeData::Doctype { .. }
+            | NodeData::Comment { .. }
+            | NodeData::ProcessingInstruction { .. } => return,
+        };
+
+        let index = self.nodes.len();
+        self.nodes.push(IrNode {
+            kind,
+            handle: node.clone(),
+            end: index + 1,
+            block: is_block_element(node),
+            css,
+            anchors: Vec::new(),
+        });
+        for child in node.children.borrow().iter() {
+            self.lower(child, keep_whitespace, stylesheet, ancestors);
+        }
+        self.nodes[index].end = self.nodes.len();
+
+        if matches!(node.data, NodeData::Element { .. }) && !stylesheet.is_empty() {
+            ancestors.pop();
+        }
+    }
+
+    /// Fills in [`IrNode::anchors`].
+    fn find_anchors(&mut self) {
+        // Block elements containing the current node
+        let mut blocks: Vec<usize> = Vec::new();
+        for index in 0..self.nodes.len() {
+            while blocks.last().is_some_and(|&n| self.nodes[n].end <= index) {
+                blocks.pop();
+            }
+            let node = &self.nodes[index];
+            let IrKind::Element { attrs, .. } = &node.kind else {
+                continue;
+            };
+            if node.block {
+                blocks.push(index);
+            }
+            let Some(id) = attrs.iter().find(|n| &*n.name.local == "id") else {
+                continue;
+            };
+            let id = id.value.to_string();
+            if let Some(&block) = blocks.last() {
+                self.nodes[block].anchors.push(id);
+            }
+        }
+    }
+
+    /// Fills in [`Ir::alerts`] and [`Ir::alert_texts`].
+    fn find_alerts(&mut self) {
+        for index in 0..self.nodes.len() {
+            if self.name(index) != Some("blockquote") {
+                continue;
+            }
+            let Some(text) = self
+                .children(index)
+                .next()
+                .filter(|&n| self.name(n) == Some("p"))
+                .and_then(|p| self.children(p).next())
+            else {
+                continue;
+            };
+
+            let NodeData::Text { contents } = &self.nodes[text].handle.data else {
+                continue;
+            };
+            let Some((kind, start)) = alert_start(&contents.borrow()) else {
+                continue;
+            };
+
+            self.alerts.insert(index, kind);
+            self.alert_texts.insert(text, (index, start));
+        }
+    }
+
+    /// Indices of the direct children of a node.
+    pub fn children(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        let end = self.nodes.get(index).map_or(index, |n| n.end);
+        let mut next = index + 1;
+        std::iter::from_fn(move || {
+            let n = next;
+            (n < end).then(|| {
+                next = self.nodes[n].end;
+                n
+            })
+        })
+    }
+
+    /// The tag name of a node, if it's an element.
+    pub fn name(&self, index: usize) -> Option<&str> {
+        if let IrKind::Element { name, .. } = &self.nodes[index].kind {
+            Some(name)
+        } else {
+            None
+        }
+    }
+}
+
+/// The kind of alert a quote's text starts with (see [`alert_marker`]),
+/// and where the rest starts once the text is cleaned up
+/// (see [`clean_whitespace`]).
+pub(crate) fn alert_start(contents: &str) -> Option<(String, usize)> {
+    let (kind, _) = alert_marker(contents.trim_start())?;
+    let cleaned = clean_whitespace(contents);
+    let after = cleaned.find(']')? + 1;
+    Some((
+        kind.to_owned(),
+        cleaned.len() - cleaned[after..].trim_start().len(),
+    ))
+}
+
+/// Splits `[!KIND]` off the start of text. Like on GitHub,
+/// it has to be on its own line.
+fn alert_marker(text: &str) -> Option<(&str, &str)> {
+    let (kind, rest) = text.strip_prefix("[!")?.split_once(']')?;
+    let valid = !kind.is_empty()
+        && kind
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    let alone = rest.trim_start_matches([' ', '\t']).is_empty()
+        || rest
+            .trim_start_matches([' ', '\t'])
+            .starts_with(['\n', '\r']);
+    (valid && alone).then_some((kind, rest))
+}
+
+pub(crate) fn is_block_element(node: &Node) -> bool {
+    let markup5ever_rcdom::NodeData::Element { name, .. } = &node.data else {
+        return false;
+    };
+    let n: &str = &name.local;
+
+    matches!(
+        n,
+        "address"
+            | "article"
+            | "aside"
+            | "blockquote"
+            | "canvas"
+            | "dd"
+            | "div"
+            | "dl"
+            | "dt"
+            | "fieldset"
+            | "figcaption"
+            | "figure"
+            | "footer"
+            | "form"
+            | "h1"
+            | "h2"
+            | "h3"
+            | "h4"
+            | "h5"
+            | "h6"
+            | "header"
+            | "hr"
+            | "li"
+            | "main"
+            | "nav"
+            | "noscript"
+            | "ol"
+            | "p"
+            | "pre"
+            | "section"
+            | "table"
+            | "tfoot"
+            | "ul"
+            | "video"
+            | "br"
+            | "summary" // not really block but acts like it
+    )
+}
+
+pub(crate) fn clean_whitespace(input: &str) -> String {
+    let mut s = input.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if let Some(last) = input.chars().last() {
+        if last.is_whitespace() && last != '\n' {
+            s.push(last);
+        }
+    }
+    if let Some(first) = input.chars().next() {
+        if first.is_whitespace() && first != '\n' {
+            s.insert(0, first);
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clean_whitespace, Ir, IrKind};
+    use crate::MarkState;
+
+    /// The tag names (or text) of the children of a node.
+    fn children(ir: &Ir, index: usize) -> Vec<String> {
+        ir.children(index)
+            .map(|n| match &ir.nodes[n].kind {
+                IrKind::Text(text) => text.clone(),
+                IrKind::Element { name, .. } => name.to_string(),
+                IrKind::Document => String::new(),
+            })
+            .collect()
+    }
+
+    fn body(ir: &Ir) -> usize {
+        (0..ir.nodes.len())
+            .find(|&n| ir.name(n) == Some("body"))
+            .unwrap()
+    }
+
+    #[test]
+    fn children_are_direct() {
+        let state = MarkState::with_html(
+            "<p>one <b>two <i>three</i></b>  four</p>\n\n<ul><li>five</li></ul>",
+        );
+        let ir = &state.ir;
+        let body = body(ir);
+        assert_eq!(children(ir, body), ["p", "ul"]);
+
+        let p = ir.children(body).next().unwrap();
+        assert_eq!(children(ir, p), ["one ", "b", " four"]);
+        assert!(ir.nodes[p].block);
+
+        let b = ir.children(p).nth(1).unwrap();
+        assert_eq!(children(ir, b), ["two ", "i"]);
+        assert!(!ir.nodes[b].block);
+        // Every node inside `b` comes before its end
+        assert_eq!(ir.nodes[b].end, b + 4);
+    }
+
+    #[test]
+    fn skipped_nodes() {
+        let state = MarkState::with_html(
+            "<head><title>Title</title><style>p {}</style></head>\
+             <p>a<!-- comment --><script>alert(1)</script>b</p>\
+             <pre>  keep\n  this </pre>",
+        );
+        let ir = &state.ir;
+        let body = body(ir);
+        let p = ir.children(body).next().unwrap();
+        assert_eq!(children(ir, p), ["a", "b"]);
+        assert!((0..ir.nodes.len()).all(|n| ir.name(n) != Some("head")));
+
+        let pre = ir.children(body).nth(1).unwrap();
+        assert_eq!(children(ir, pre), ["  keep\n  this "]);
+    }
+
+    #[test]
+    fn hidden_nodes() {
+        let state = MarkState::with_html(
+            "<p>shown <span style=\"display: none\">hidden <b>too</b></span>shown</p>\
+             <div style=\"DISPLAY:NONE\"><p>gone</p></div>",
+        );
+        let ir = &state.ir;
+        let body = body(ir);
+        assert_eq!(children(ir, body), ["p"]);
+        let p = ir.children(body).next().unwrap();
+        assert_eq!(children(ir, p), ["shown ", "shown"]);
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(clean_whitespace("  a \n\t b  "), " a b ");
+        assert_eq!(clean_whitespace("\na b\n"), "a b");
+    }
+}
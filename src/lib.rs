@@ -1,11 +1,40 @@
 #![doc = include_str!("../README.md")]
 
+mod anchor;
+mod css;
+mod emoji;
+mod find;
+mod form;
+#[cfg(feature = "highlighting")]
+mod highlight;
+mod ir;
+mod linkify;
+#[cfg(feature = "math")]
+mod math;
 mod renderer;
+mod selection;
 mod state;
 mod structs;
 mod style;
+mod table;
+#[cfg(all(test, feature = "iced-tiny-skia"))]
+mod testing;
+mod toc;
+mod virtualized;
 mod widgets;
 
+pub use anchor::scroll_to_anchor;
+pub use css::Stylesheet;
+pub use find::{FindMatch, FindOptions};
+pub use form::FormSubmit;
+pub use selection::TextFormat;
 pub use state::MarkState;
-pub use structs::{ImageInfo, MarkWidget, UpdateMsg};
-pub use style::Style;
+pub use structs::{
+    AlertKind, CodeBlockInfo, ElementInfo, Heading, ImageInfo, MarkWidget, ResolvedWikilink,
+    TaskToggle, UpdateMsg,
+};
+pub use style::{Catalog, Style, StyleFn};
+pub use toc::TocWidget;
+
+#[cfg(feature = "highlighting")]
+pub use highlight::HighlightTheme;
@@ -0,0 +1,288 @@
+//! Turning bare URLs and e-mail addresses in HTML into links,
+//! like comrak's `autolink` extension does for Markdown.
+
+use std::ops::Range;
+
+use html5ever::{
+    interface::{NodeOrText, TreeSink},
+    namespace_url, ns,
+    tree_builder::create_element,
+    Attribute, QualName,
+};
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+
+/// Elements whose text is left alone.
+const SKIPPED: &[&str] = &[
+    "a", "code", "pre", "kbd", "script", "style", "textarea", "option", "button",
+];
+
+/// Wraps the links found in the text of the document in `<a href>` elements.
+pub(crate) fn linkify(dom: &mut RcDom) {
+    let mut texts = Vec::new();
+    find_texts(&dom.document, &mut texts);
+    for text in texts {
+        linkify_text(dom, &text);
+    }
+}
+
+fn find_texts(node: &Handle, texts: &mut Vec<Handle>) {
+    match &node.data {
+        NodeData::Element { name, .. } if SKIPPED.contains(&&*name.local) => {}
+        NodeData::Text { .. } => texts.push(node.clone()),
+        _ => {
+            for child in &*node.children.borrow() {
+                find_texts(child, texts);
+            }
+        }
+    }
+}
+
+fn linkify_text(dom: &mut RcDom, node: &Handle) {
+    let NodeData::Text { contents } = &node.data else {
+        return;
+    };
+    let text = contents.borrow().to_string();
+    let links = find_links(&text);
+    if links.is_empty() {
+        return;
+    }
+
+    // The text before each link goes in front of the node,
+    // and the node keeps whatever is after the last one
+    let mut last = 0;
+    for (range, href) in links {
+        if last < range.start {
+            dom.append_before_sibling(node, NodeOrText::AppendText(text[last..range.start].into()));
+        }
+        let link = create_element(
+            dom,
+            QualName::new(None, ns!(html), "a".into()),
+            vec![Attribute {
+                name: QualName::new(None, ns!(), "href".into()),
+                value: href.into(),
+            }],
+        );
+        dom.append(&link, NodeOrText::AppendText(text[range.clone()].into()));
+        dom.append_before_sibling(node, NodeOrText::AppendNode(link));
+        last = range.end;
+    }
+
+    if last < text.len() {
+        *contents.borrow_mut() = text[last..].into();
+    } else {
+        dom.remove_from_parent(node);
+    }
+}
+
+/// Finds the links in some text, as where they are
+/// and what they point to (eg: `mailto:` for e-mail addresses).
+///
+/// Follows the GitHub Flavored Markdown rules: links start
+/// at the beginning of a word (or after `*`, `_`, `~` or `(`),
+/// and trailing punctuation and unmatched `)` are left out.
+fn find_links(text: &str) -> Vec<(Range<usize>, String)> {
+    let mut links = Vec::new();
+    let mut prev = None;
+    let mut i = 0;
+
+    while let Some(c) = text[i..].chars().next() {
+        let at_word_start =
+            prev.is_none_or(|p: char| p.is_whitespace() || matches!(p, '*' | '_' | '~' | '('));
+        if at_word_start {
+            if let Some((len, href)) = url_at(&text[i..]).or_else(|| email_at(&text[i..])) {
+                links.push((i..i + len, href));
+                i += len;
+                prev = text[..i].chars().next_back();
+                continue;
+            }
+        }
+        prev = Some(c);
+        i += c.len_utf8();
+    }
+    links
+}
+
+/// A URL (`https://..` or `www...`) at the start of `text`,
+/// as its length and link.
+fn url_at(text: &str) -> Option<(usize, String)> {
+    let (domain_start, prefix, min_len) = if let Some(scheme) = ["http://", "https://", "ftp://"]
+        .into_iter()
+        .find(|s| text.starts_with(s))
+    {
+        (scheme.len(), "", scheme.len())
+    } else if text.starts_with("www.") {
+        (0, "http://", "www.".len())
+    } else {
+        return None;
+    };
+
+    let rest = &text[domain_start..];
+    let domain_len = rest
+        .find(|c: char| !(c.is_alphanumeric() || matches!(c, '-' | '_' | '.')))
+        .unwrap_or(rest.len());
+    // No underscores in the last two parts of the domain
+    let valid_domain = rest[..domain_len]
+        .rsplit('.')
+        .filter(|part| !part.is_empty())
+        .take(2)
+        .all(|part| !part.contains('_'));
+    if domain_len == 0 || !valid_domain {
+        return None;
+    }
+
+    let end = rest
+        .find(|c: char| c.is_whitespace() || c == '<')
+        .map_or(text.len(), |n| domain_start + n);
+    let len = trim_link(&text[..end]);
+    (len > min_len).then(|| (len, format!("{prefix}{}", &text[..len])))
+}
+
+/// An e-mail address at the start of `text`, as its length and link.
+fn email_at(text: &str) -> Option<(usize, String)> {
+    let at =
+        text.find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '+' | '-' | '_')))?;
+    if at == 0 || !text[at..].starts_with('@') {
+        return None;
+    }
+
+    let rest = &text[at + 1..];
+    let domain_len = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_')))
+        .unwrap_or(rest.len());
+    let domain = rest[..domain_len].trim_end_matches('.');
+    if !domain.contains('.') || domain.split('.').any(str::is_empty) || domain.ends_with(['-', '_'])
+    {
+        return None;
+    }
+
+    let len = at + 1 + domain.len();
+    Some((len, format!("mailto:{}", &text[..len])))
+}
+
+/// How much of a link is left after taking off trailing punctuation,
+/// unmatched `)` and things that look like HTML entities (`&amp;`).
+fn trim_link(mut link: &str) -> usize {
+    while let Some(last) = link.chars().next_back() {
+        let trimmed = match last {
+            '?' | '!' | '.' | ',' | ':' | '*' | '_' | '~' => &link[..link.len() - 1],
+            ')' if link.matches(')').count() > link.matches('(').count() => &link[..link.len() - 1],
+            ';' => match link.rfind('&') {
+                Some(amp)
+                    if amp + 2 < link.len()
+                        && link[amp + 1..link.len() - 1]
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric()) =>
+                {
+                    &link[..amp]
+                }
+                _ => break,
+            },
+            _ => break,
+        };
+        link = trimmed;
+    }
+    link.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_links;
+
+    fn links(text: &str) -> Vec<(&str, String)> {
+        find_links(text)
+            .into_iter()
+            .map(|(range, href)| (&text[range], href))
+            .collect()
+    }
+
+    fn link(text: &str) -> Option<&str> {
+        let links = links(text);
+        assert!(links.len() <= 1, "{links:?}");
+        links.first().map(|n| n.0)
+    }
+
+    #[test]
+    fn trailing_punctuation() {
+        assert_eq!(
+            links("Visit www.commonmark.org."),
+            [("www.commonmark.org", "http://www.commonmark.org".to_owned())]
+        );
+        assert_eq!(
+            link("www.commonmark.org/a.b."),
+            Some("www.commonmark.org/a.b")
+        );
+        assert_eq!(
+            link("see https://example.com/x?!:,"),
+            Some("https://example.com/x")
+        );
+        assert_eq!(
+            link("*https://example.com/x*"),
+            Some("https://example.com/x")
+        );
+        assert_eq!(
+            link("www.commonmark.org/he<lp"),
+            Some("www.commonmark.org/he")
+        );
+    }
+
+    #[test]
+    fn parentheses() {
+        assert_eq!(
+            link("www.google.com/search?q=Markup+(business)"),
+            Some("www.google.com/search?q=Markup+(business)")
+        );
+        assert_eq!(
+            link("www.google.com/search?q=Markup+(business)))"),
+            Some("www.google.com/search?q=Markup+(business)")
+        );
+        assert_eq!(
+            link("(www.google.com/search?q=Markup+(business))"),
+            Some("www.google.com/search?q=Markup+(business)")
+        );
+        assert_eq!(
+            link("(www.google.com/search?q=Markup+(business)"),
+            Some("www.google.com/search?q=Markup+(business)")
+        );
+        // Only trailing ones are trimmed
+        assert_eq!(
+            link("www.google.com/search?q=(business))+ok"),
+            Some("www.google.com/search?q=(business))+ok")
+        );
+    }
+
+    #[test]
+    fn entities() {
+        assert_eq!(
+            link("www.google.com/search?q=commonmark&hl=en"),
+            Some("www.google.com/search?q=commonmark&hl=en")
+        );
+        assert_eq!(
+            link("www.google.com/search?q=commonmark&hl;"),
+            Some("www.google.com/search?q=commonmark")
+        );
+        assert_eq!(link("www.example.com/a;"), Some("www.example.com/a;"));
+    }
+
+    #[test]
+    fn word_starts_and_domains() {
+        assert_eq!(link("notwww.example.com"), None);
+        assert_eq!(link("https://"), None);
+        assert_eq!(link("www."), None);
+        assert_eq!(link("www.a_b.example.com"), Some("www.a_b.example.com"));
+        assert_eq!(link("www.example.a_b.com"), None);
+        assert_eq!(links("a www.one.com b http://two.org").len(), 2);
+    }
+
+    #[test]
+    fn emails() {
+        assert_eq!(
+            links("mail foo@bar.baz."),
+            [("foo@bar.baz", "mailto:foo@bar.baz".to_owned())]
+        );
+        assert_eq!(link("a.b-c_d@a.b"), Some("a.b-c_d@a.b"));
+        assert_eq!(link("a.b-c_d@a.b-"), None);
+        assert_eq!(link("a.b-c_d@a.b_"), None);
+        assert_eq!(link("hello@mail+xyz.example"), None);
+        assert_eq!(link("@example.com"), None);
+    }
+}
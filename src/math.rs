@@ -0,0 +1,1898 @@
+use iced::{
+    advanced::{
+        graphics::geometry::Renderer as _,
+        layout, mouse, renderer,
+        text::{self, LineHeight, Paragraph as _, Renderer as _, Shaping, Wrapping},
+        widget::{tree, Tree},
+        Layout, Renderer as _, Widget,
+    },
+    alignment,
+    widget::canvas::{self, LineCap, LineJoin, Path, Stroke},
+    Color, Element, Font, Length, Point, Rectangle, Size,
+};
+
+/// Where the baseline of a glyph is, from the top of its text box
+/// (with a line height of `1.0`). Close enough for most fonts.
+const BASELINE: f32 = 0.85;
+/// Where the baseline of surrounding text is, from the top of its line
+/// (with iced's default line height of `1.3`).
+const INLINE_BASELINE: f32 = 1.0;
+/// Height of the math axis (the middle of `−` and fraction bars).
+const AXIS: f32 = 0.3;
+
+/// A TeX formula laid out with text and a few shapes.
+///
+/// Only a practical subset of TeX is supported
+/// (see [`Math::parse`]).
+pub(crate) struct Math {
+    source: String,
+    node: Node,
+    display: bool,
+    size: f32,
+    font: Font,
+    color: Option<Color>,
+}
+
+impl Math {
+    /// Parses a formula, returning `None` if it uses
+    /// anything that isn't supported (or is malformed).
+    ///
+    /// `display` is for `$$math$$` on its own line,
+    /// which puts limits above and below sums, etc.
+    pub fn parse(source: &str, display: bool) -> Option<Self> {
+        let mut parser = Parser::new(source);
+        let (nodes, stop) = parser.row()?;
+        (stop == Stop::Eof).then(|| Self {
+            source: source.to_owned(),
+            node: Node::Group(nodes),
+            display,
+            size: 16.0,
+            font: Font::DEFAULT,
+            color: None,
+        })
+    }
+
+    pub fn size(mut self, size: f32) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn font(mut self, font: Font) -> Self {
+        self.font = font;
+        self
+    }
+
+    pub fn color(mut self, color: Option<Color>) -> Self {
+        self.color = color;
+        self
+    }
+}
+
+/// The laid out formula, kept until the formula or its size changes.
+#[derive(Default)]
+struct Cache {
+    key: Option<(String, f32, Font, bool)>,
+    math: MathBox,
+    /// Space above the formula, to line
+    /// its baseline up with the text around it.
+    top: f32,
+}
+
+impl<M, T> Widget<M, T, iced::Renderer> for Math {
+    fn size(&self) -> Size<Length> {
+        Size::new(Length::Shrink, Length::Shrink)
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<Cache>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(Cache::default())
+    }
+
+    fn layout(
+        &mut self,
+        tree: &mut Tree,
+        _renderer: &iced::Renderer,
+        _limits: &layout::Limits,
+    ) -> layout::Node {
+        let cache = tree.state.downcast_mut::<Cache>();
+        let key = (self.source.clone(), self.size, self.font, self.display);
+        if cache.key.as_ref() != Some(&key) {
+            let measure = |content: &str, size: f32, font: Font| {
+                <iced::Renderer as text::Renderer>::Paragraph::with_text(text::Text {
+                    content,
+                    bounds: Size::INFINITE,
+                    size: size.into(),
+                    line_height: LineHeight::Relative(1.0),
+                    font,
+                    align_x: text::Alignment::Left,
+                    align_y: alignment::Vertical::Top,
+                    shaping: Shaping::Advanced,
+                    wrapping: Wrapping::None,
+                })
+                .min_bounds()
+                .width
+            };
+            let ctx = Ctx {
+                size: self.size,
+                min_size: self.size * 0.5,
+                font: self.font,
+                display: self.display,
+                script: false,
+                measure: &measure,
+            };
+            cache.math = ctx.layout(&self.node);
+            cache.key = Some(key);
+        }
+
+        let math = &cache.math;
+        let height = if self.display {
+            cache.top = 0.0;
+            math.ascent + math.descent
+        } else {
+            cache.top = (self.size * INLINE_BASELINE - math.ascent).max(0.0);
+            (cache.top + math.ascent + math.descent).max(self.size * 1.3)
+        };
+        layout::Node::new(Size::new(math.width, height))
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut iced::Renderer,
+        _theme: &T,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        let cache = tree.state.downcast_ref::<Cache>();
+        let bounds = layout.bounds();
+        let color = self.color.unwrap_or(style.text_color);
+        let baseline = cache.top + cache.math.ascent;
+
+        let mut shapes = Vec::new();
+        for item in &cache.math.items {
+            match item {
+                Item::Glyph {
+                    x,
+                    y,
+                    text,
+                    size,
+                    font,
+                    width,
+                } => renderer.fill_text(
+                    text::Text {
+                        content: text.clone(),
+                        bounds: Size::new(width + size, *size),
+                        size: (*size).into(),
+                        line_height: LineHeight::Relative(1.0),
+                        font: *font,
+                        align_x: text::Alignment::Left,
+                        align_y: alignment::Vertical::Top,
+                        shaping: Shaping::Advanced,
+                        wrapping: Wrapping::None,
+                    },
+                    Point::new(bounds.x + x, bounds.y + baseline + y - size * BASELINE),
+                    color,
+                    *viewport,
+                ),
+                Item::Rule {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => renderer.fill_quad(
+                    renderer::Quad {
+                        bounds: Rectangle {
+                            x: bounds.x + x,
+                            y: bounds.y + baseline + y,
+                            width: *width,
+                            height: *height,
+                        },
+                        ..renderer::Quad::default()
+                    },
+                    color,
+                ),
+                Item::Shape {
+                    x,
+                    y,
+                    width,
+                    height,
+                    shape,
+                    thickness,
+                } => {
+                    let rect = Rectangle {
+                        x: bounds.x + x,
+                        y: bounds.y + baseline + y,
+                        width: *width,
+                        height: *height,
+                    };
+                    shapes.push((shape_path(*shape, rect, *thickness), *thickness));
+                }
+            }
+        }
+
+        if shapes.is_empty() {
+            return;
+        }
+        // Clipping is left to the layer we're in, since tiny-skia
+        // moves the clip bounds of translated frames twice
+        let mut frame = canvas::Frame::with_bounds(
+            renderer,
+            Rectangle::new(Point::new(-1.0e6, -1.0e6), Size::new(2.0e6, 2.0e6)),
+        );
+        for (path, thickness) in &shapes {
+            frame.stroke(
+                path,
+                Stroke::default()
+                    .with_color(color)
+                    .with_width(*thickness)
+                    .with_line_cap(LineCap::Round)
+                    .with_line_join(LineJoin::Round),
+            );
+        }
+        renderer.draw_geometry(frame.into_geometry());
+    }
+}
+
+impl<'a, M: 'a, T: 'a> From<Math> for Element<'a, M, T> {
+    fn from(value: Math) -> Self {
+        Element::new(value)
+    }
+}
+
+/// How an atom is spaced from its neighbours, like in TeX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    Ord,
+    /// Operators like `\sum` (`large`) and `\sin`.
+    /// With `limits`, their scripts go above and below
+    /// (in display style).
+    Op {
+        large: bool,
+        limits: bool,
+    },
+    Bin,
+    Rel,
+    Open,
+    Close,
+    Punct,
+    /// Fractions and `\left..\right`.
+    Inner,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Variant {
+    /// Letters are italic, everything else is upright.
+    Normal,
+    Upright,
+    Italic,
+    Bold,
+    /// `\mathbb`
+    DoubleStruck,
+    /// `\mathcal`
+    Script,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Delim {
+    None,
+    Paren,
+    Bracket,
+    Brace,
+    Bar,
+    DoubleBar,
+    Angle,
+    Floor,
+    Ceil,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColumnAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Accent {
+    Over,
+    Under,
+    /// A glyph (at a scale), and where its ink
+    /// starts and ends above the baseline (in ems).
+    Glyph(&'static str, f32, f32, f32),
+}
+
+#[derive(Debug)]
+struct Atom {
+    text: String,
+    class: Class,
+    italic: bool,
+    bold: bool,
+}
+
+#[derive(Debug)]
+enum Node {
+    Atom(Atom),
+    Group(Vec<Node>),
+    /// In ems, can be negative.
+    Space(f32),
+    Frac {
+        num: Box<Node>,
+        den: Box<Node>,
+        bar: bool,
+        /// Forced by `\dfrac` or `\tfrac`.
+        display: Option<bool>,
+    },
+    Sqrt {
+        body: Box<Node>,
+        index: Option<Box<Node>>,
+    },
+    Scripts {
+        base: Box<Node>,
+        sup: Option<Box<Node>>,
+        sub: Option<Box<Node>>,
+    },
+    /// `\left( .. \right)`, and matrices.
+    Fenced {
+        open: Delim,
+        close: Delim,
+        body: Box<Node>,
+    },
+    /// `\big(` and friends, with a height in ems.
+    Big {
+        delim: Delim,
+        left: bool,
+        height: f32,
+        class: Class,
+    },
+    Matrix {
+        rows: Vec<Vec<Node>>,
+        columns: Vec<ColumnAlign>,
+        /// Between columns, in ems.
+        spacing: f32,
+    },
+    Accent {
+        body: Box<Node>,
+        accent: Accent,
+    },
+    /// `\displaystyle` and `\textstyle`.
+    Style {
+        display: bool,
+        body: Box<Node>,
+    },
+}
+
+impl Node {
+    fn class(&self) -> Option<Class> {
+        match self {
+            Node::Atom(atom) => Some(atom.class),
+            Node::Space(_) => None,
+            Node::Scripts { base, .. } => Some(base.class().unwrap_or(Class::Ord)),
+            Node::Frac { .. } | Node::Fenced { .. } => Some(Class::Inner),
+            Node::Big { class, .. } => Some(*class),
+            Node::Style { body, .. } => body.class(),
+            Node::Group(_) | Node::Sqrt { .. } | Node::Matrix { .. } | Node::Accent { .. } => {
+                Some(Class::Ord)
+            }
+        }
+    }
+}
+
+/// Why [`Parser::row`] stopped.
+#[derive(Debug, PartialEq, Eq)]
+enum Stop {
+    Eof,
+    Brace,
+    Right(Delim),
+    /// `&`
+    Align,
+    /// `\\`
+    Row,
+    End(String),
+}
+
+struct Parser<'s> {
+    src: &'s str,
+    pos: usize,
+    variant: Variant,
+}
+
+impl<'s> Parser<'s> {
+    fn new(src: &'s str) -> Self {
+        Self {
+            src,
+            pos: 0,
+            variant: Variant::Normal,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    /// The name of a command, after its `\`.
+    fn command_name(&mut self) -> String {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_alphabetic()) {
+            self.bump();
+        }
+        if self.pos == start {
+            self.bump();
+        }
+        self.src[start..self.pos].to_owned()
+    }
+
+    /// Parses nodes until the end of the group (or row, cell, etc).
+    fn row(&mut self) -> Option<(Vec<Node>, Stop)> {
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let Some(c) = self.bump() else {
+                return Some((nodes, Stop::Eof));
+            };
+            let node = match c {
+                '}' => return Some((nodes, Stop::Brace)),
+                '&' => return Some((nodes, Stop::Align)),
+                '^' | '_' => {
+                    let script = Box::new(self.argument()?);
+                    let node = match (nodes.pop(), c == '^') {
+                        (
+                            Some(Node::Scripts {
+                                base,
+                                sup: None,
+                                sub,
+                            }),
+                            true,
+                        ) => Node::Scripts {
+                            base,
+                            sup: Some(script),
+                            sub,
+                        },
+                        (
+                            Some(Node::Scripts {
+                                base,
+                                sup,
+                                sub: None,
+                            }),
+                            false,
+                        ) => Node::Scripts {
+                            base,
+                            sup,
+                            sub: Some(script),
+                        },
+                        // Double superscript
+                        (Some(Node::Scripts { .. }), _) => return None,
+                        (base, sup) => {
+                            let base = Box::new(base.unwrap_or(Node::Group(Vec::new())));
+                            let (sup, sub) = if sup {
+                                (Some(script), None)
+                            } else {
+                                (None, Some(script))
+                            };
+                            Node::Scripts { base, sup, sub }
+                        }
+                    };
+                    nodes.push(node);
+                    continue;
+                }
+                '{' => self.group()?,
+                '\\' => {
+                    let name = self.command_name();
+                    match name.as_str() {
+                        "\\" | "cr" => return Some((nodes, Stop::Row)),
+                        "right" => return Some((nodes, Stop::Right(self.delimiter()?.0))),
+                        "end" => return Some((nodes, Stop::End(self.text_argument()?))),
+                        "limits" | "nolimits" => {
+                            let Some(Node::Atom(Atom {
+                                class: Class::Op { limits, .. },
+                                ..
+                            })) = nodes.last_mut()
+                            else {
+                                return None;
+                            };
+                            *limits = name == "limits";
+                            continue;
+                        }
+                        // These last until the end of the group
+                        "displaystyle" | "textstyle" => {
+                            let (body, stop) = self.row()?;
+                            nodes.push(Node::Style {
+                                display: name == "displaystyle",
+                                body: Box::new(Node::Group(body)),
+                            });
+                            return Some((nodes, stop));
+                        }
+                        _ => self.command(&name)?,
+                    }
+                }
+                c => self.character(c, true)?,
+            };
+            nodes.push(node);
+        }
+    }
+
+    /// After a `{`.
+    fn group(&mut self) -> Option<Node> {
+        let (nodes, stop) = self.row()?;
+        (stop == Stop::Brace).then_some(Node::Group(nodes))
+    }
+
+    /// A single character, command, or `{group}`.
+    fn argument(&mut self) -> Option<Node> {
+        self.skip_whitespace();
+        match self.bump()? {
+            '{' => self.group(),
+            '\\' => {
+                let name = self.command_name();
+                if matches!(
+                    name.as_str(),
+                    "\\" | "cr" | "right" | "end" | "limits" | "nolimits"
+                ) {
+                    return None;
+                }
+                self.command(&name)
+            }
+            '}' | '&' | '^' | '_' => None,
+            c => self.character(c, false),
+        }
+    }
+
+    /// An argument with a different [`Variant`].
+    fn argument_with(&mut self, variant: Variant) -> Option<Node> {
+        let old = std::mem::replace(&mut self.variant, variant);
+        let node = self.argument();
+        self.variant = old;
+        node
+    }
+
+    /// The raw text inside `{..}`, for `\text` and such.
+    fn text_argument(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        if self.bump()? != '{' {
+            return None;
+        }
+        let start = self.pos;
+        let mut depth = 0;
+        loop {
+            match self.bump()? {
+                '\\' => {
+                    self.bump()?;
+                }
+                '{' => depth += 1,
+                '}' if depth == 0 => break,
+                '}' => depth -= 1,
+                _ => {}
+            }
+        }
+        Some(self.src[start..self.pos - 1].to_owned())
+    }
+
+    /// The `[..]` of `\sqrt[n]{..}`, if there is one.
+    fn optional(&mut self) -> Option<Option<Node>> {
+        self.skip_whitespace();
+        if self.peek() != Some('[') {
+            return Some(None);
+        }
+        self.bump();
+        let start = self.pos;
+        let mut depth = 0;
+        loop {
+            match self.bump()? {
+                '{' => depth += 1,
+                '}' => depth -= 1,
+                ']' if depth == 0 => break,
+                _ => {}
+            }
+        }
+        let mut parser = Parser {
+            src: &self.src[start..self.pos - 1],
+            pos: 0,
+            variant: self.variant,
+        };
+        let (nodes, stop) = parser.row()?;
+        (stop == Stop::Eof).then_some(Some(Node::Group(nodes)))
+    }
+
+    /// A delimiter after `\left`, `\right` or `\big`,
+    /// and whether it opens something.
+    fn delimiter(&mut self) -> Option<(Delim, bool)> {
+        self.skip_whitespace();
+        let delim = match self.bump()? {
+            '(' => (Delim::Paren, true),
+            ')' => (Delim::Paren, false),
+            '[' => (Delim::Bracket, true),
+            ']' => (Delim::Bracket, false),
+            '|' => (Delim::Bar, true),
+            '<' => (Delim::Angle, true),
+            '>' => (Delim::Angle, false),
+            '.' => (Delim::None, true),
+            '\\' => match self.command_name().as_str() {
+                "{" | "lbrace" => (Delim::Brace, true),
+                "}" | "rbrace" => (Delim::Brace, false),
+                "|" | "Vert" | "lVert" => (Delim::DoubleBar, true),
+                "rVert" => (Delim::DoubleBar, false),
+                "vert" | "lvert" => (Delim::Bar, true),
+                "rvert" => (Delim::Bar, false),
+                "langle" => (Delim::Angle, true),
+                "rangle" => (Delim::Angle, false),
+                "lfloor" => (Delim::Floor, true),
+                "rfloor" => (Delim::Floor, false),
+                "lceil" => (Delim::Ceil, true),
+                "rceil" => (Delim::Ceil, false),
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(delim)
+    }
+
+    /// An atom with the current [`Variant`] applied.
+    fn symbol(&self, text: impl Into<String>, class: Class) -> Node {
+        let text = text.into();
+        let letter = text
+            .chars()
+            .all(|c| c.is_ascii_alphabetic() || is_greek_lower(c));
+        let (italic, bold) = match self.variant {
+            Variant::Normal => (letter, false),
+            Variant::Italic => (true, false),
+            Variant::Bold => (false, true),
+            Variant::Upright | Variant::DoubleStruck | Variant::Script => (false, false),
+        };
+        let text = match self.variant {
+            Variant::DoubleStruck => text.chars().map(double_struck).collect(),
+            Variant::Script => text.chars().map(script).collect(),
+            _ => text,
+        };
+        Node::Atom(Atom {
+            text,
+            class,
+            italic,
+            bold,
+        })
+    }
+
+    /// A character that isn't part of a command.
+    /// `merge` puts numbers like `3.14` in one atom.
+    fn character(&mut self, c: char, merge: bool) -> Option<Node> {
+        let class = match c {
+            '0'..='9' => {
+                let start = self.pos - 1;
+                if merge {
+                    loop {
+                        let mut chars = self.src[self.pos..].chars();
+                        self.pos += match (chars.next(), chars.next()) {
+                            (Some('0'..='9'), _) => 1,
+                            (Some('.'), Some('0'..='9')) => 2,
+                            _ => break,
+                        };
+                    }
+                }
+                let text = self.src[start..self.pos].to_owned();
+                return Some(self.symbol(text, Class::Ord));
+            }
+            '+' => return Some(self.symbol("+", Class::Bin)),
+            '-' => return Some(self.symbol("−", Class::Bin)),
+            '*' => return Some(self.symbol("∗", Class::Bin)),
+            '\'' => return Some(self.symbol("′", Class::Ord)),
+            '~' => return Some(Node::Space(1.0 / 3.0)),
+            '=' | '<' | '>' | ':' => Class::Rel,
+            ',' | ';' => Class::Punct,
+            '(' | '[' => Class::Open,
+            ')' | ']' | '!' | '?' => Class::Close,
+            '#' | '$' | '%' => return None,
+            _ => Class::Ord,
+        };
+        Some(self.symbol(c, class))
+    }
+
+    fn command(&mut self, name: &str) -> Option<Node> {
+        let node = match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => Node::Frac {
+                num: Box::new(self.argument()?),
+                den: Box::new(self.argument()?),
+                bar: true,
+                display: match name {
+                    "dfrac" | "cfrac" => Some(true),
+                    "tfrac" => Some(false),
+                    _ => None,
+                },
+            },
+            "binom" | "dbinom" | "tbinom" => Node::Fenced {
+                open: Delim::Paren,
+                close: Delim::Paren,
+                body: Box::new(Node::Frac {
+                    num: Box::new(self.argument()?),
+                    den: Box::new(self.argument()?),
+                    bar: false,
+                    display: match name {
+                        "dbinom" => Some(true),
+                        "tbinom" => Some(false),
+                        _ => None,
+                    },
+                }),
+            },
+            "sqrt" => {
+                let index = self.optional()?.map(Box::new);
+                Node::Sqrt {
+                    body: Box::new(self.argument()?),
+                    index,
+                }
+            }
+            "left" => {
+                let (open, _) = self.delimiter()?;
+                let (body, stop) = self.row()?;
+                let Stop::Right(close) = stop else {
+                    return None;
+                };
+                Node::Fenced {
+                    open,
+                    close,
+                    body: Box::new(Node::Group(body)),
+                }
+            }
+            "big" | "bigl" | "bigr" | "bigm" | "Big" | "Bigl" | "Bigr" | "Bigm" | "bigg"
+            | "biggl" | "biggr" | "biggm" | "Bigg" | "Biggl" | "Biggr" | "Biggm" => {
+                let (delim, left) = self.delimiter()?;
+                let class = match name.chars().last() {
+                    Some('l') => Class::Open,
+                    Some('r') => Class::Close,
+                    Some('m') => Class::Rel,
+                    _ => Class::Ord,
+                };
+                let height = match name.trim_end_matches(['l', 'r', 'm']) {
+                    "big" => 1.2,
+                    "Big" => 1.8,
+                    "bigg" => 2.4,
+                    _ => 3.0,
+                };
+                Node::Big {
+                    delim,
+                    left: left || class == Class::Open,
+                    height,
+                    class,
+                }
+            }
+            "begin" => {
+                let name = self.text_argument()?;
+                self.environment(&name)?
+            }
+
+            "text" | "textrm" | "textnormal" | "mbox" | "textbf" | "textit" => Node::Atom(Atom {
+                text: self.text_argument()?,
+                class: Class::Ord,
+                italic: name == "textit",
+                bold: name == "textbf",
+            }),
+            "operatorname" => {
+                let limits = self.peek() == Some('*');
+                if limits {
+                    self.bump();
+                }
+                Node::Atom(Atom {
+                    text: self.text_argument()?,
+                    class: Class::Op {
+                        large: false,
+                        limits,
+                    },
+                    italic: false,
+                    bold: false,
+                })
+            }
+            "mathrm" | "mathsf" | "mathtt" | "mathup" => self.argument_with(Variant::Upright)?,
+            "mathit" => self.argument_with(Variant::Italic)?,
+            "mathbf" | "boldsymbol" | "bm" => self.argument_with(Variant::Bold)?,
+            "mathbb" => self.argument_with(Variant::DoubleStruck)?,
+            "mathcal" | "mathscr" => self.argument_with(Variant::Script)?,
+
+            "hat" | "widehat" | "tilde" | "widetilde" | "dot" | "ddot" | "vec"
+            | "overrightarrow" | "bar" | "overline" | "underline" => Node::Accent {
+                body: Box::new(self.argument()?),
+                accent: match name {
+                    "hat" | "widehat" => Accent::Glyph("ˆ", 1.0, 0.63, 0.8),
+                    "tilde" | "widetilde" => Accent::Glyph("˜", 1.0, 0.65, 0.78),
+                    "dot" => Accent::Glyph("˙", 1.0, 0.67, 0.76),
+                    "ddot" => Accent::Glyph("¨", 1.0, 0.67, 0.76),
+                    "vec" | "overrightarrow" => Accent::Glyph("→", 0.8, 0.11, 0.52),
+                    "underline" => Accent::Under,
+                    _ => Accent::Over,
+                },
+            },
+
+            "," | "thinspace" => Node::Space(3.0 / 18.0),
+            ":" | ">" | "medspace" => Node::Space(4.0 / 18.0),
+            ";" | "thickspace" => Node::Space(5.0 / 18.0),
+            "!" => Node::Space(-3.0 / 18.0),
+            " " => Node::Space(1.0 / 3.0),
+            "quad" => Node::Space(1.0),
+            "qquad" => Node::Space(2.0),
+
+            "{" | "lbrace" => self.symbol("{", Class::Open),
+            "}" | "rbrace" => self.symbol("}", Class::Close),
+            "|" | "Vert" => self.symbol("‖", Class::Ord),
+            "%" | "$" | "#" | "&" | "_" => self.symbol(name, Class::Ord),
+            "not" => {
+                self.skip_whitespace();
+                let negated = match self.bump()? {
+                    '=' => "≠",
+                    '<' => "≮",
+                    '>' => "≯",
+                    '\\' => match self.command_name().as_str() {
+                        "in" => "∉",
+                        "subset" => "⊄",
+                        "supset" => "⊅",
+                        "equiv" => "≢",
+                        "sim" => "≁",
+                        "approx" => "≉",
+                        "leq" | "le" => "≰",
+                        "geq" | "ge" => "≱",
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                self.symbol(negated, Class::Rel)
+            }
+
+            _ => {
+                if let Some((text, limits)) = operator_name(name) {
+                    Node::Atom(Atom {
+                        text: text.to_owned(),
+                        class: Class::Op {
+                            large: false,
+                            limits,
+                        },
+                        italic: false,
+                        bold: false,
+                    })
+                } else {
+                    let (text, class) = symbol(name)?;
+                    self.symbol(text, class)
+                }
+            }
+        };
+        Some(node)
+    }
+
+    /// After `\begin{name}`, up to and including the `\end{name}`.
+    fn environment(&mut self, name: &str) -> Option<Node> {
+        let (open, close) = match name {
+            "matrix" | "smallmatrix" | "array" | "aligned" | "align" | "align*" | "split"
+            | "gathered" | "gather" | "gather*" => (Delim::None, Delim::None),
+            "pmatrix" => (Delim::Paren, Delim::Paren),
+            "bmatrix" => (Delim::Bracket, Delim::Bracket),
+            "Bmatrix" => (Delim::Brace, Delim::Brace),
+            "vmatrix" => (Delim::Bar, Delim::Bar),
+            "Vmatrix" => (Delim::DoubleBar, Delim::DoubleBar),
+            "cases" => (Delim::Brace, Delim::None),
+            _ => return None,
+        };
+        let aligned = matches!(name, "aligned" | "align" | "align*" | "split");
+        let display = aligned || matches!(name, "gathered" | "gather" | "gather*");
+        let mut columns: Vec<ColumnAlign> = if name == "array" {
+            self.text_argument()?
+                .chars()
+                .filter_map(|c| match c {
+                    'l' => Some(ColumnAlign::Left),
+                    'c' => Some(ColumnAlign::Center),
+                    'r' => Some(ColumnAlign::Right),
+                    _ => None,
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let mut rows = vec![Vec::new()];
+        loop {
+            let (mut cell, stop) = self.row()?;
+            let row = rows.last_mut()?;
+            // So that `&=` is spaced like a relation
+            if aligned && row.len() % 2 == 1 {
+                cell.insert(0, Node::Group(Vec::new()));
+            }
+            let cell = Node::Group(cell);
+            row.push(if display {
+                Node::Style {
+                    display: true,
+                    body: Box::new(cell),
+                }
+            } else {
+                cell
+            });
+            match stop {
+                Stop::Align => {}
+                Stop::Row => rows.push(Vec::new()),
+                Stop::End(end) if end == name => break,
+                _ => return None,
+            }
+        }
+        // From a `\\` at the end
+        if rows.len() > 1 && rows.last().is_some_and(|row| is_empty_row(row)) {
+            rows.pop();
+        }
+
+        let count = rows.iter().map(Vec::len).max().unwrap_or(0);
+        columns.resize(
+            count.max(columns.len()),
+            if name == "cases" {
+                ColumnAlign::Left
+            } else {
+                ColumnAlign::Center
+            },
+        );
+        if aligned {
+            for (i, column) in columns.iter_mut().enumerate() {
+                *column = if i % 2 == 0 {
+                    ColumnAlign::Right
+                } else {
+                    ColumnAlign::Left
+                };
+            }
+        }
+
+        let matrix = Node::Matrix {
+            rows,
+            columns,
+            spacing: if aligned { 0.0 } else { 1.0 },
+        };
+        Some(if open == Delim::None && close == Delim::None {
+            matrix
+        } else {
+            Node::Fenced {
+                open,
+                close,
+                body: Box::new(matrix),
+            }
+        })
+    }
+}
+
+fn is_empty_row(row: &[Node]) -> bool {
+    match row {
+        [] => true,
+        [Node::Group(nodes)] => nodes.is_empty(),
+        [Node::Style { body, .. }] => matches!(&**body, Node::Group(nodes) if nodes.is_empty()),
+        _ => false,
+    }
+}
+
+fn is_greek_lower(c: char) -> bool {
+    ('α'..='ω').contains(&c) || "ϵϑϕϖϱ".contains(c)
+}
+
+/// For `\mathbb`.
+fn double_struck(c: char) -> char {
+    let offset = |from: char| u32::from(c) - u32::from(from);
+    let mapped = match c {
+        'C' => Some('ℂ'),
+        'H' => Some('ℍ'),
+        'N' => Some('ℕ'),
+        'P' => Some('ℙ'),
+        'Q' => Some('ℚ'),
+        'R' => Some('ℝ'),
+        'Z' => Some('ℤ'),
+        'A'..='Z' => char::from_u32(0x1D538 + offset('A')),
+        'a'..='z' => char::from_u32(0x1D552 + offset('a')),
+        '0'..='9' => char::from_u32(0x1D7D8 + offset('0')),
+        _ => None,
+    };
+    mapped.unwrap_or(c)
+}
+
+/// For `\mathcal`.
+fn script(c: char) -> char {
+    let mapped = match c {
+        'B' => Some('ℬ'),
+        'E' => Some('ℰ'),
+        'F' => Some('ℱ'),
+        'H' => Some('ℋ'),
+        'I' => Some('ℐ'),
+        'L' => Some('ℒ'),
+        'M' => Some('ℳ'),
+        'R' => Some('ℛ'),
+        'A'..='Z' => char::from_u32(0x1D49C + u32::from(c) - u32::from('A')),
+        _ => None,
+    };
+    mapped.unwrap_or(c)
+}
+
+/// Operators written as words, and whether they have limits.
+fn operator_name(name: &str) -> Option<(&'static str, bool)> {
+    let op = match name {
+        "lim" => ("lim", true),
+        "liminf" => ("lim inf", true),
+        "limsup" => ("lim sup", true),
+        "max" => ("max", true),
+        "min" => ("min", true),
+        "sup" => ("sup", true),
+        "inf" => ("inf", true),
+        "det" => ("det", true),
+        "gcd" => ("gcd", true),
+        "Pr" => ("Pr", true),
+        "sin" => ("sin", false),
+        "cos" => ("cos", false),
+        "tan" => ("tan", false),
+        "cot" => ("cot", false),
+        "sec" => ("sec", false),
+        "csc" => ("csc", false),
+        "arcsin" => ("arcsin", false),
+        "arccos" => ("arccos", false),
+        "arctan" => ("arctan", false),
+        "sinh" => ("sinh", false),
+        "cosh" => ("cosh", false),
+        "tanh" => ("tanh", false),
+        "coth" => ("coth", false),
+        "log" => ("log", false),
+        "ln" => ("ln", false),
+        "lg" => ("lg", false),
+        "exp" => ("exp", false),
+        "deg" => ("deg", false),
+        "dim" => ("dim", false),
+        "ker" => ("ker", false),
+        "hom" => ("hom", false),
+        "arg" => ("arg", false),
+        _ => return None,
+    };
+    Some(op)
+}
+
+fn symbol(name: &str) -> Option<(&'static str, Class)> {
+    let large = |limits| Class::Op {
+        large: true,
+        limits,
+    };
+    let symbol = match name {
+        "alpha" => ("α", Class::Ord),
+        "beta" => ("β", Class::Ord),
+        "gamma" => ("γ", Class::Ord),
+        "delta" => ("δ", Class::Ord),
+        "epsilon" => ("ϵ", Class::Ord),
+        "varepsilon" => ("ε", Class::Ord),
+        "zeta" => ("ζ", Class::Ord),
+        "eta" => ("η", Class::Ord),
+        "theta" => ("θ", Class::Ord),
+        "vartheta" => ("ϑ", Class::Ord),
+        "iota" => ("ι", Class::Ord),
+        "kappa" => ("κ", Class::Ord),
+        "lambda" => ("λ", Class::Ord),
+        "mu" => ("μ", Class::Ord),
+        "nu" => ("ν", Class::Ord),
+        "xi" => ("ξ", Class::Ord),
+        "omicron" => ("ο", Class::Ord),
+        "pi" => ("π", Class::Ord),
+        "varpi" => ("ϖ", Class::Ord),
+        "rho" => ("ρ", Class::Ord),
+        "varrho" => ("ϱ", Class::Ord),
+        "sigma" => ("σ", Class::Ord),
+        "varsigma" => ("ς", Class::Ord),
+        "tau" => ("τ", Class::Ord),
+        "upsilon" => ("υ", Class::Ord),
+        "phi" => ("ϕ", Class::Ord),
+        "varphi" => ("φ", Class::Ord),
+        "chi" => ("χ", Class::Ord),
+        "psi" => ("ψ", Class::Ord),
+        "omega" => ("ω", Class::Ord),
+        "Gamma" => ("Γ", Class::Ord),
+        "Delta" => ("Δ", Class::Ord),
+        "Theta" => ("Θ", Class::Ord),
+        "Lambda" => ("Λ", Class::Ord),
+        "Xi" => ("Ξ", Class::Ord),
+        "Pi" => ("Π", Class::Ord),
+        "Sigma" => ("Σ", Class::Ord),
+        "Upsilon" => ("Υ", Class::Ord),
+        "Phi" => ("Φ", Class::Ord),
+        "Psi" => ("Ψ", Class::Ord),
+        "Omega" => ("Ω", Class::Ord),
+
+        "infty" => ("∞", Class::Ord),
+        "partial" => ("∂", Class::Ord),
+        "nabla" => ("∇", Class::Ord),
+        "forall" => ("∀", Class::Ord),
+        "exists" => ("∃", Class::Ord),
+        "nexists" => ("∄", Class::Ord),
+        "emptyset" | "varnothing" => ("∅", Class::Ord),
+        "ell" => ("ℓ", Class::Ord),
+        "hbar" => ("ℏ", Class::Ord),
+        "Re" => ("ℜ", Class::Ord),
+        "Im" => ("ℑ", Class::Ord),
+        "aleph" => ("ℵ", Class::Ord),
+        "prime" => ("′", Class::Ord),
+        "angle" => ("∠", Class::Ord),
+        "triangle" => ("△", Class::Ord),
+        "neg" | "lnot" => ("¬", Class::Ord),
+        "top" => ("⊤", Class::Ord),
+        "bot" => ("⊥", Class::Ord),
+        "ldots" | "dots" => ("…", Class::Inner),
+        "cdots" => ("⋯", Class::Inner),
+        "vdots" => ("⋮", Class::Ord),
+        "ddots" => ("⋱", Class::Inner),
+        "vert" => ("|", Class::Ord),
+
+        "pm" => ("±", Class::Bin),
+        "mp" => ("∓", Class::Bin),
+        "times" => ("×", Class::Bin),
+        "div" => ("÷", Class::Bin),
+        "cdot" => ("⋅", Class::Bin),
+        "ast" => ("∗", Class::Bin),
+        "star" => ("⋆", Class::Bin),
+        "circ" => ("∘", Class::Bin),
+        "bullet" => ("∙", Class::Bin),
+        "oplus" => ("⊕", Class::Bin),
+        "ominus" => ("⊖", Class::Bin),
+        "otimes" => ("⊗", Class::Bin),
+        "odot" => ("⊙", Class::Bin),
+        "cap" => ("∩", Class::Bin),
+        "cup" => ("∪", Class::Bin),
+        "wedge" | "land" => ("∧", Class::Bin),
+        "vee" | "lor" => ("∨", Class::Bin),
+        "setminus" => ("∖", Class::Bin),
+
+        "leq" | "le" => ("≤", Class::Rel),
+        "geq" | "ge" => ("≥", Class::Rel),
+        "neq" | "ne" => ("≠", Class::Rel),
+        "approx" => ("≈", Class::Rel),
+        "equiv" => ("≡", Class::Rel),
+        "sim" => ("∼", Class::Rel),
+        "simeq" => ("≃", Class::Rel),
+        "cong" => ("≅", Class::Rel),
+        "propto" => ("∝", Class::Rel),
+        "ll" => ("≪", Class::Rel),
+        "gg" => ("≫", Class::Rel),
+        "in" => ("∈", Class::Rel),
+        "notin" => ("∉", Class::Rel),
+        "ni" => ("∋", Class::Rel),
+        "subset" => ("⊂", Class::Rel),
+        "supset" => ("⊃", Class::Rel),
+        "subseteq" => ("⊆", Class::Rel),
+        "supseteq" => ("⊇", Class::Rel),
+        "to" | "rightarrow" => ("→", Class::Rel),
+        "gets" | "leftarrow" => ("←", Class::Rel),
+        "leftrightarrow" => ("↔", Class::Rel),
+        "Rightarrow" => ("⇒", Class::Rel),
+        "Leftarrow" => ("⇐", Class::Rel),
+        "Leftrightarrow" => ("⇔", Class::Rel),
+        "longrightarrow" => ("⟶", Class::Rel),
+        "longleftarrow" => ("⟵", Class::Rel),
+        "implies" => ("⟹", Class::Rel),
+        "impliedby" => ("⟸", Class::Rel),
+        "iff" => ("⟺", Class::Rel),
+        "mapsto" => ("↦", Class::Rel),
+        "uparrow" => ("↑", Class::Rel),
+        "downarrow" => ("↓", Class::Rel),
+        "perp" => ("⊥", Class::Rel),
+        "parallel" => ("∥", Class::Rel),
+        "mid" => ("∣", Class::Rel),
+        "vdash" => ("⊢", Class::Rel),
+        "models" => ("⊨", Class::Rel),
+        "coloneqq" => ("≔", Class::Rel),
+
+        "langle" => ("⟨", Class::Open),
+        "rangle" => ("⟩", Class::Close),
+        "lfloor" => ("⌊", Class::Open),
+        "rfloor" => ("⌋", Class::Close),
+        "lceil" => ("⌈", Class::Open),
+        "rceil" => ("⌉", Class::Close),
+        "colon" => (":", Class::Punct),
+
+        "sum" => ("∑", large(true)),
+        "prod" => ("∏", large(true)),
+        "coprod" => ("∐", large(true)),
+        "bigcup" => ("⋃", large(true)),
+        "bigcap" => ("⋂", large(true)),
+        "bigvee" => ("⋁", large(true)),
+        "bigwedge" => ("⋀", large(true)),
+        "bigoplus" => ("⨁", large(true)),
+        "bigotimes" => ("⨂", large(true)),
+        "bigodot" => ("⨀", large(true)),
+        "int" => ("∫", large(false)),
+        "iint" => ("∬", large(false)),
+        "iiint" => ("∭", large(false)),
+        "oint" => ("∮", large(false)),
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+/// A laid out piece of a formula. Everything is
+/// positioned relative to the start of its baseline.
+#[derive(Debug, Default)]
+struct MathBox {
+    width: f32,
+    ascent: f32,
+    descent: f32,
+    items: Vec<Item>,
+}
+
+#[derive(Debug)]
+enum Item {
+    /// `y` is the baseline.
+    Glyph {
+        x: f32,
+        y: f32,
+        text: String,
+        size: f32,
+        font: Font,
+        width: f32,
+    },
+    /// `y` is the top.
+    Rule {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    /// `y` is the top.
+    Shape {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        shape: Shape,
+        thickness: f32,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Shape {
+    Delim(Delim, bool),
+    Radical,
+}
+
+impl Item {
+    fn moved(mut self, dx: f32, dy: f32) -> Self {
+        let (Item::Glyph { x, y, .. } | Item::Rule { x, y, .. } | Item::Shape { x, y, .. }) =
+            &mut self;
+        *x += dx;
+        *y += dy;
+        self
+    }
+}
+
+impl MathBox {
+    /// Puts `other` in this box, with its baseline starting at `(x, y)`.
+    fn place(&mut self, other: MathBox, x: f32, y: f32) {
+        self.width = self.width.max(x + other.width);
+        self.ascent = self.ascent.max(other.ascent - y);
+        self.descent = self.descent.max(other.descent + y);
+        self.items
+            .extend(other.items.into_iter().map(|n| n.moved(x, y)));
+    }
+}
+
+/// How much of the em box a piece of text covers
+/// above and below the baseline, roughly.
+fn ink(text: &str) -> (f32, f32) {
+    const SHORT: &str = "acemnorsuvwxzαεικνοπστυωϵϖ+−=×÷±∓<>≤≥≈≠∼≃≡⋅∘∗∙:";
+    const DESCENDING: &str = "gjpqyβγζημξρςφχψϕϱ,;()[]{}|‖/⌊⌋⌈⌉⟨⟩∫∬∭∮";
+    if text.chars().any(|c| "∫∬∭∮".contains(c)) {
+        return (0.76, 0.2);
+    }
+    if text.chars().any(|c| "∑∏∐⋃⋂⋁⋀⨁⨂⨀".contains(c)) {
+        return (0.72, 0.18);
+    }
+    let ascent = if text.chars().all(|c| SHORT.contains(c)) {
+        0.55
+    } else {
+        0.72
+    };
+    let descent = if text.chars().any(|c| DESCENDING.contains(c)) {
+        0.22
+    } else {
+        0.0
+    };
+    (ascent, descent)
+}
+
+/// How far apart atoms are, in eighteenths of an em (like TeX).
+fn spacing(prev: Class, next: Class, script: bool) -> f32 {
+    use Class::{Bin, Close, Inner, Open, Ord, Punct, Rel};
+    let op = |n: Class| matches!(n, Class::Op { .. });
+    // Only thin spaces around operators are kept in scripts
+    if (op(next) && matches!(prev, Ord | Close | Inner) || op(prev) && matches!(next, Ord))
+        || op(prev) && op(next)
+    {
+        return 3.0;
+    }
+    if script {
+        return 0.0;
+    }
+    match (prev, next) {
+        (Ord | Close | Inner, Bin) => 4.0,
+        (Bin, Ord | Open | Inner | Class::Op { .. }) => 4.0,
+        (_, Rel) if !matches!(prev, Rel | Open | Bin) => 5.0,
+        (Rel, Ord | Open | Inner | Class::Op { .. }) => 5.0,
+        (Ord | Close | Class::Op { .. }, Inner) => 3.0,
+        (Inner, Ord | Open | Punct | Inner) => 3.0,
+        (Punct, _) => 3.0,
+        _ => 0.0,
+    }
+}
+
+/// How things are laid out at the current point in the formula.
+#[derive(Clone, Copy)]
+struct Ctx<'m> {
+    size: f32,
+    /// The smallest scripts can get.
+    min_size: f32,
+    font: Font,
+    display: bool,
+    script: bool,
+    /// Width of some text.
+    measure: &'m dyn Fn(&str, f32, Font) -> f32,
+}
+
+impl Ctx<'_> {
+    fn em(&self, n: f32) -> f32 {
+        self.size * n
+    }
+
+    fn thickness(&self) -> f32 {
+        self.em(0.05).max(1.0)
+    }
+
+    fn script(self) -> Self {
+        Self {
+            size: (self.size * 0.7).max(self.min_size),
+            display: false,
+            script: true,
+            ..self
+        }
+    }
+
+    fn text(self) -> Self {
+        Self {
+            display: false,
+            ..self
+        }
+    }
+
+    fn layout(&self, node: &Node) -> MathBox {
+        match node {
+            Node::Atom(atom) => self.atom(atom),
+            Node::Group(nodes) => self.row(nodes),
+            Node::Space(width) => MathBox {
+                width: self.em(*width),
+                ..MathBox::default()
+            },
+            Node::Frac {
+                num,
+                den,
+                bar,
+                display,
+            } => self.frac(num, den, *bar, *display),
+            Node::Sqrt { body, index } => self.sqrt(body, index.as_deref()),
+            Node::Scripts { base, sup, sub } => self.scripts(base, sup.as_deref(), sub.as_deref()),
+            Node::Fenced { open, close, body } => {
+                let body = self.layout(body);
+                let axis = self.em(AXIS);
+                let half = (body.ascent - axis)
+                    .max(body.descent + axis)
+                    .max(self.em(0.5));
+                let height = half * 2.0 + self.em(0.1);
+
+                let mut out = self.delimiter(*open, true, height);
+                let x = out.width;
+                let width = body.width;
+                out.place(body, x, 0.0);
+                out.place(self.delimiter(*close, false, height), x + width, 0.0);
+                out
+            }
+            Node::Big {
+                delim,
+                left,
+                height,
+                ..
+            } => self.delimiter(*delim, *left, self.em(*height)),
+            Node::Matrix {
+                rows,
+                columns,
+                spacing,
+            } => self.matrix(rows, columns, *spacing),
+            Node::Accent { body, accent } => self.accent(body, *accent),
+            Node::Style { display, body } => Self {
+                display: *display,
+                ..*self
+            }
+            .layout(body),
+        }
+    }
+
+    fn row(&self, nodes: &[Node]) -> MathBox {
+        let mut classes: Vec<Option<Class>> = nodes.iter().map(Node::class).collect();
+
+        // Like in TeX, a `-` that has nothing to
+        // its left is a sign, not an operator
+        let mut prev = None;
+        for class in classes.iter_mut().flatten() {
+            if *class == Class::Bin
+                && prev.is_none_or(|n| {
+                    matches!(
+                        n,
+                        Class::Bin | Class::Op { .. } | Class::Rel | Class::Open | Class::Punct
+                    )
+                })
+            {
+                *class = Class::Ord;
+            }
+            prev = Some(*class);
+        }
+        let mut next = None;
+        for class in classes.iter_mut().flatten().rev() {
+            if *class == Class::Bin
+                && next.is_none_or(|n| matches!(n, Class::Rel | Class::Close | Class::Punct))
+            {
+                *class = Class::Ord;
+            }
+            next = Some(*class);
+        }
+
+        let mut out = MathBox::default();
+        let mut x = 0.0;
+        let mut prev = None;
+        for (node, class) in nodes.iter().zip(classes) {
+            if let Some(class) = class {
+                if let Some(prev) = prev {
+                    x += self.em(spacing(prev, class, self.script) / 18.0);
+                }
+                prev = Some(class);
+            }
+            let child = self.layout(node);
+            let width = child.width;
+            out.place(child, x, 0.0);
+            x += width;
+        }
+        out.width = x.max(0.0);
+        out
+    }
+
+    fn atom(&self, atom: &Atom) -> MathBox {
+        let large = matches!(atom.class, Class::Op { large: true, .. });
+        let size = match (large, self.display) {
+            (true, true) => self.em(1.4),
+            (true, false) => self.em(1.1),
+            _ => self.size,
+        };
+        let font = Font {
+            style: if atom.italic {
+                iced::font::Style::Italic
+            } else {
+                iced::font::Style::Normal
+            },
+            weight: if atom.bold {
+                iced::font::Weight::Bold
+            } else {
+                self.font.weight
+            },
+            ..self.font
+        };
+        let width = (self.measure)(&atom.text, size, font);
+        let (ascent, descent) = ink(&atom.text);
+        let mut out = MathBox {
+            width,
+            ascent: ascent * size,
+            descent: descent * size,
+            items: vec![Item::Glyph {
+                x: 0.0,
+                y: 0.0,
+                text: atom.text.clone(),
+                size,
+                font,
+                width,
+            }],
+        };
+        if large {
+            // Centered on the axis
+            let dy = (ascent - descent) / 2.0 * size - self.em(AXIS);
+            out.items = out.items.into_iter().map(|n| n.moved(0.0, dy)).collect();
+            out.ascent -= dy;
+            out.descent += dy;
+        }
+        out
+    }
+
+    fn frac(&self, num: &Node, den: &Node, bar: bool, display: Option<bool>) -> MathBox {
+        let display = display.unwrap_or(self.display);
+        let parts = if display { self.text() } else { self.script() };
+        let (num, den) = (parts.layout(num), parts.layout(den));
+
+        let thickness = self.thickness();
+        let gap = self.em(if display { 0.2 } else { 0.12 });
+        let pad = self.em(0.1);
+        let axis = self.em(AXIS);
+        let width = num.width.max(den.width) + pad * 2.0;
+
+        let mut out = MathBox::default();
+        let (num_width, den_width) = (num.width, den.width);
+        let num_y = -(axis + thickness / 2.0 + gap + num.descent);
+        let den_y = -axis + thickness / 2.0 + gap + den.ascent;
+        out.place(num, (width - num_width) / 2.0, num_y);
+        out.place(den, (width - den_width) / 2.0, den_y);
+        if bar {
+            out.items.push(Item::Rule {
+                x: pad / 2.0,
+                y: -axis - thickness / 2.0,
+                width: width - pad,
+                height: thickness,
+            });
+        }
+        out.width = width;
+        out
+    }
+
+    fn sqrt(&self, body: &Node, index: Option<&Node>) -> MathBox {
+        let body = self.layout(body);
+        let thickness = self.thickness();
+        let top = body.ascent + self.em(0.12) + thickness;
+        let bottom = body.descent.max(self.em(0.05));
+        let height = top + bottom;
+        let sign = self.em(0.55);
+
+        let index = index.map(|n| self.script().script().layout(n));
+        let offset = index
+            .as_ref()
+            .map_or(0.0, |n| (n.width - sign * 0.35).max(0.0));
+
+        let mut out = MathBox::default();
+        let body_width = body.width;
+        if let Some(index) = index {
+            let x = offset + sign * 0.35 - index.width;
+            let y = bottom - height * 0.6 - index.descent;
+            out.place(index, x, y);
+        }
+        out.items.push(Item::Shape {
+            x: offset,
+            y: -top,
+            width: sign,
+            height,
+            shape: Shape::Radical,
+            thickness,
+        });
+        out.items.push(Item::Rule {
+            x: offset + sign,
+            y: -top,
+            width: body_width + self.em(0.1),
+            height: thickness,
+        });
+        out.place(body, offset + sign + self.em(0.05), 0.0);
+        out.width = offset + sign + body_width + self.em(0.1);
+        out.ascent = out.ascent.max(top);
+        out.descent = out.descent.max(bottom);
+        out
+    }
+
+    fn scripts(&self, base: &Node, sup: Option<&Node>, sub: Option<&Node>) -> MathBox {
+        let limits = self.display && matches!(base.class(), Some(Class::Op { limits: true, .. }));
+        let base = self.layout(base);
+        let script = self.script();
+        let sup = sup.map(|n| script.layout(n));
+        let sub = sub.map(|n| script.layout(n));
+
+        let mut out = MathBox::default();
+        let (base_width, base_ascent, base_descent) = (base.width, base.ascent, base.descent);
+
+        if limits {
+            let gap = self.em(0.15);
+            let width = [Some(&base), sup.as_ref(), sub.as_ref()]
+                .into_iter()
+                .flatten()
+                .map(|n| n.width)
+                .fold(0.0, f32::max);
+            out.place(base, (width - base_width) / 2.0, 0.0);
+            if let Some(sup) = sup {
+                let (x, y) = (
+                    (width - sup.width) / 2.0,
+                    -(base_ascent + gap + sup.descent),
+                );
+                out.place(sup, x, y);
+            }
+            if let Some(sub) = sub {
+                let (x, y) = ((width - sub.width) / 2.0, base_descent + gap + sub.ascent);
+                out.place(sub, x, y);
+            }
+            return out;
+        }
+
+        out.place(base, 0.0, 0.0);
+        let mut sup_y = -(self.em(0.4).max(base_ascent - self.em(0.3)));
+        let mut sub_y = self.em(0.2).max(base_descent + self.em(0.05));
+        if let (Some(sup), Some(sub)) = (&sup, &sub) {
+            let gap = (sub_y - sub.ascent) - (sup_y + sup.descent);
+            let min = self.em(0.2);
+            if gap < min {
+                sup_y -= (min - gap) / 2.0;
+                sub_y += (min - gap) / 2.0;
+            }
+        }
+        if let Some(sup) = sup {
+            out.place(sup, base_width, sup_y);
+        }
+        if let Some(sub) = sub {
+            out.place(sub, base_width, sub_y);
+        }
+        out.width += self.em(0.05);
+        out
+    }
+
+    /// A delimiter stretched to `height`, centered on the axis.
+    fn delimiter(&self, delim: Delim, left: bool, height: f32) -> MathBox {
+        let pad = self.em(0.08);
+        let width = match delim {
+            Delim::None => {
+                return MathBox {
+                    width: self.em(0.12),
+                    ..MathBox::default()
+                }
+            }
+            Delim::Paren => self.em(0.25) + height * 0.04,
+            Delim::Bracket | Delim::Floor | Delim::Ceil => self.em(0.22),
+            Delim::Brace => self.em(0.35),
+            Delim::Bar => self.em(0.05),
+            Delim::DoubleBar => self.em(0.2),
+            Delim::Angle => self.em(0.25) + height * 0.04,
+        };
+        let axis = self.em(AXIS);
+        MathBox {
+            width: width + pad * 2.0,
+            ascent: axis + height / 2.0,
+            descent: height / 2.0 - axis,
+            items: vec![Item::Shape {
+                x: pad,
+                y: -axis - height / 2.0,
+                width,
+                height,
+                shape: Shape::Delim(delim, left),
+                thickness: self.em(0.07).max(1.0),
+            }],
+        }
+    }
+
+    fn matrix(&self, rows: &[Vec<Node>], columns: &[ColumnAlign], spacing: f32) -> MathBox {
+        let cells: Vec<Vec<MathBox>> = rows
+            .iter()
+            .map(|row| row.iter().map(|n| self.text().layout(n)).collect())
+            .collect();
+
+        let mut widths = vec![0.0_f32; columns.len()];
+        let mut heights = Vec::with_capacity(cells.len());
+        for row in &cells {
+            let mut ascent = self.em(0.72);
+            let mut descent = self.em(0.22);
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(cell.width);
+                ascent = ascent.max(cell.ascent);
+                descent = descent.max(cell.descent);
+            }
+            heights.push((ascent, descent));
+        }
+
+        let gap = self.em(0.3);
+        let spacing = self.em(spacing);
+        let total: f32 = heights.iter().map(|(a, d)| a + d).sum::<f32>()
+            + gap * heights.len().saturating_sub(1) as f32;
+
+        let mut out = MathBox::default();
+        let mut y = -self.em(AXIS) - total / 2.0;
+        for (row, (ascent, descent)) in cells.into_iter().zip(heights) {
+            let mut x = 0.0;
+            for ((cell, width), align) in row.into_iter().zip(&widths).zip(columns) {
+                let offset = match align {
+                    ColumnAlign::Left => 0.0,
+                    ColumnAlign::Center => (width - cell.width) / 2.0,
+                    ColumnAlign::Right => width - cell.width,
+                };
+                out.place(cell, x + offset, y + ascent);
+                x += width + spacing;
+            }
+            y += ascent + descent + gap;
+        }
+        out.width = widths.iter().sum::<f32>() + spacing * widths.len().saturating_sub(1) as f32;
+        out.ascent = out.ascent.max(self.em(AXIS) + total / 2.0);
+        out.descent = out.descent.max(total / 2.0 - self.em(AXIS));
+        out
+    }
+
+    fn accent(&self, body: &Node, accent: Accent) -> MathBox {
+        let body = self.layout(body);
+        let (width, ascent, descent) = (body.width, body.ascent, body.descent);
+        let thickness = self.thickness();
+        let gap = self.em(0.1);
+
+        let mut out = MathBox::default();
+        out.place(body, 0.0, 0.0);
+        match accent {
+            Accent::Over => {
+                let y = -(ascent + gap + thickness);
+                out.items.push(Item::Rule {
+                    x: 0.0,
+                    y,
+                    width,
+                    height: thickness,
+                });
+                out.ascent = out.ascent.max(-y);
+            }
+            Accent::Under => {
+                out.items.push(Item::Rule {
+                    x: 0.0,
+                    y: descent + gap,
+                    width,
+                    height: thickness,
+                });
+                out.descent = out.descent.max(descent + gap + thickness);
+            }
+            Accent::Glyph(text, scale, low, high) => {
+                let size = self.em(scale);
+                let glyph_width = (self.measure)(text, size, self.font);
+                let y = -(ascent + self.em(0.05)) + low * size;
+                out.items.push(Item::Glyph {
+                    x: (width - glyph_width) / 2.0,
+                    y,
+                    text: text.to_owned(),
+                    size,
+                    font: self.font,
+                    width: glyph_width,
+                });
+                out.ascent = out.ascent.max(high * size - y);
+                out.width = out.width.max(glyph_width);
+            }
+        }
+        out
+    }
+}
+
+/// The outline of a stretchy delimiter or radical sign,
+/// filling `rect`.
+fn shape_path(shape: Shape, rect: Rectangle, thickness: f32) -> Path {
+    let top = rect.y + thickness / 2.0;
+    let bottom = rect.y + rect.height - thickness / 2.0;
+    let middle = rect.center_y();
+
+    Path::new(|p| match shape {
+        Shape::Radical => {
+            let x = |f: f32| rect.x + rect.width * f;
+            let y = |f: f32| rect.y + rect.height * f;
+            p.move_to(Point::new(x(0.0), y(0.6)));
+            p.line_to(Point::new(x(0.2), y(0.52)));
+            p.line_to(Point::new(x(0.5), bottom));
+            p.line_to(Point::new(x(1.0), top));
+        }
+        Shape::Delim(delim, left) => {
+            // Drawn as an opening delimiter,
+            // and flipped if it's a closing one
+            let x = |f: f32| {
+                if left {
+                    rect.x + rect.width * f
+                } else {
+                    rect.x + rect.width * (1.0 - f)
+                }
+            };
+            let height = bottom - top;
+            match delim {
+                Delim::None => {}
+                Delim::Paren => {
+                    p.move_to(Point::new(x(1.0), top));
+                    p.bezier_curve_to(
+                        Point::new(x(-0.3), top + height * 0.15),
+                        Point::new(x(-0.3), bottom - height * 0.15),
+                        Point::new(x(1.0), bottom),
+                    );
+                }
+                Delim::Bracket => {
+                    p.move_to(Point::new(x(1.0), top));
+                    p.line_to(Point::new(x(0.0), top));
+                    p.line_to(Point::new(x(0.0), bottom));
+                    p.line_to(Point::new(x(1.0), bottom));
+                }
+                Delim::Floor => {
+                    p.move_to(Point::new(x(0.0), top));
+                    p.line_to(Point::new(x(0.0), bottom));
+                    p.line_to(Point::new(x(1.0), bottom));
+                }
+                Delim::Ceil => {
+                    p.move_to(Point::new(x(1.0), top));
+                    p.line_to(Point::new(x(0.0), top));
+                    p.line_to(Point::new(x(0.0), bottom));
+                }
+                Delim::Bar => {
+                    p.move_to(Point::new(x(0.5), top));
+                    p.line_to(Point::new(x(0.5), bottom));
+                }
+                Delim::DoubleBar => {
+                    for f in [0.1, 0.9] {
+                        p.move_to(Point::new(x(f), top));
+                        p.line_to(Point::new(x(f), bottom));
+                    }
+                }
+                Delim::Angle => {
+                    p.move_to(Point::new(x(1.0), top));
+                    p.line_to(Point::new(x(0.0), middle));
+                    p.line_to(Point::new(x(1.0), bottom));
+                }
+                Delim::Brace => {
+                    let curl = (rect.width * 0.5).min(height * 0.1);
+                    p.move_to(Point::new(x(1.0), top));
+                    p.quadratic_curve_to(Point::new(x(0.5), top), Point::new(x(0.5), top + curl));
+                    p.line_to(Point::new(x(0.5), middle - curl));
+                    p.quadratic_curve_to(Point::new(x(0.5), middle), Point::new(x(0.0), middle));
+                    p.quadratic_curve_to(
+                        Point::new(x(0.5), middle),
+                        Point::new(x(0.5), middle + curl),
+                    );
+                    p.line_to(Point::new(x(0.5), bottom - curl));
+                    p.quadratic_curve_to(Point::new(x(0.5), bottom), Point::new(x(1.0), bottom));
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Math;
+
+    fn parses(source: &str) -> bool {
+        Math::parse(source, false).is_some()
+    }
+
+    #[test]
+    fn supported() {
+        for source in [
+            "",
+            r"x^2_i + \alpha",
+            r"\frac{a}{b} \sqrt[3]{x}",
+            r"\left( \frac12 \right]",
+            r"\sum\limits_{i=0}^n i",
+            r"\begin{pmatrix} a & b \\ c & d \end{pmatrix}",
+            r"\mathbb{R} \text{ if } x",
+        ] {
+            assert!(parses(source), "{source}");
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        for source in [
+            "{a",
+            "a}",
+            "x^",
+            "x^^2",
+            "x^2^3",
+            "x_1_2",
+            r"\frac{a}",
+            r"\sqrt[3{x}",
+            r"\left( a",
+            r"\left( a \right",
+            r"a \right)",
+            r"\begin{matrix} a",
+            r"\begin{matrix} a \end{pmatrix}",
+            r"\end{matrix}",
+            "a & b",
+            r"a \\ b",
+            r"x\limits",
+            r"\text{a",
+        ] {
+            assert!(!parses(source), "{source}");
+        }
+    }
+
+    #[test]
+    fn unsupported() {
+        for source in [r"\notacommand", r"\begin{tikzpicture} \end{tikzpicture}"] {
+            assert!(!parses(source), "{source}");
+        }
+    }
+}
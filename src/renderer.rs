@@ -1,11 +1,23 @@
+use std::rc::Rc;
+
 use iced::{widget, Element, Font, Padding};
-use markup5ever_rcdom::{Node, NodeData};
+use markup5ever_rcdom::Handle;
 
 use crate::{
+    anchor::anchor_id,
+    css::{parse_list_style, CssStyle},
+    find::node_key,
+    form::{Field, FieldKind, FieldValue, SelectOption},
+    ir::{Ir, IrBlock, IrKind},
+    selection::{plain_spans, Hidden, SelectableBlock, SelectionArea},
+    state::get_text,
     structs::{
-        ChildAlignment, ChildDataFlags, ImageInfo, MarkWidget, RenderedSpan, UpdateMsg,
-        UpdateMsgKind,
+        ChildAlignment, ChildDataFlags, CodeBlockInfo, ElementInfo, FClickLink, ImageInfo,
+        ListData, ListStyle, MarkWidget, RenderedSpan, ResolvedWikilink, StableId, TaskToggle,
+        UpdateMsg, UpdateMsgKind,
     },
+    table::{CellInfo, Table, TableGrid},
+    virtualized::VirtualColumn,
     widgets::{link, link_text, underline},
 };
 
@@ -19,123 +31,266 @@ impl<
             + widget::rule::Catalog
             + widget::text_editor::Catalog
             + widget::checkbox::Catalog
+            + widget::radio::Catalog
+            + widget::text_input::Catalog
+            + widget::pick_list::Catalog
+            + widget::overlay::menu::Catalog
+            + widget::container::Catalog
+            + crate::Catalog
             + 'a,
     > MarkWidget<'a, M, T>
 where
     <T as widget::button::Catalog>::Class<'a>: From<widget::button::StyleFn<'a, T>>,
+    <T as widget::text::Catalog>::Class<'a>: From<widget::text::StyleFn<'a, T>>,
+    <T as widget::rule::Catalog>::Class<'a>: From<widget::rule::StyleFn<'a, T>>,
+    <T as widget::text_editor::Catalog>::Class<'a>: From<widget::text_editor::StyleFn<'a, T>>,
+    <T as widget::container::Catalog>::Class<'a>: From<widget::container::StyleFn<'a, T>>,
 {
-    pub(crate) fn traverse_node(&mut self, node: &Node, data: ChildData) -> RenderedSpan<'a, M, T> {
-        match &node.data {
-            markup5ever_rcdom::NodeData::Document => self.render_children(node, data),
-
-            markup5ever_rcdom::NodeData::Text { contents } => {
-                fn calc_size(text_size: f32, scaling: f32, factor: f32) -> f32 {
-                    text_size * (1.0 + ((scaling - 1.0) * factor))
-                }
+    pub(crate) fn traverse_node(&self, index: usize, data: ChildData) -> RenderedSpan<'a, M, T> {
+        let state: &'a crate::MarkState = self.state;
+        let node = &state.ir.nodes[index];
+        match &node.kind {
+            IrKind::Document => self.render_children(index, data),
 
-                let text = contents.borrow();
-                let weight = data.heading_weight;
-                let scaling = match weight {
-                    1 => 1.8,
-                    2 => 1.5,
-                    3 => 1.25,
-                    4 => 1.15,
-                    5 => 0.875,
-                    6 => 0.75,
-                    7 => 0.625,
-                    _ => 1.0,
+            IrKind::Text(text) => {
+                // The `[!KIND]` of an alert isn't shown
+                let skip = match state.ir.alert_texts.get(&index) {
+                    Some(&(quote, skip)) if self.alert(quote).is_some() => skip,
+                    _ => 0,
                 };
-                let size = calc_size(self.text_size, scaling, self.heading_scale);
+                if skip == text.len() {
+                    return RenderedSpan::None;
+                }
+                let size = self.font_size(data);
 
                 if data.flags.contains(ChildDataFlags::MONOSPACE) {
-                    self.codeblock(
-                        text.to_string(),
-                        size,
-                        !data.flags.contains(ChildDataFlags::KEEP_WHITESPACE),
-                    )
+                    self.codeblock(&node.handle, text, size, data)
                 } else {
-                    let mut t =
-                        widget::span(if data.flags.contains(ChildDataFlags::KEEP_WHITESPACE) {
-                            text.to_string()
-                        } else {
-                            clean_whitespace(&text)
-                        })
-                        .size(size);
+                    let font = {
+                        let mut f = self.font;
+                        if data.flags.contains(ChildDataFlags::BOLD) {
+                            f.weight = iced::font::Weight::Bold;
+                        }
+                        if data.flags.contains(ChildDataFlags::ITALIC) {
+                            f.style = iced::font::Style::Italic;
+                        }
+                        f
+                    };
 
-                    RenderedSpan::Spans(vec![{
-                        t = t.font({
-                            let mut f = self.font;
-                            if data.flags.contains(ChildDataFlags::BOLD) {
-                                f.weight = iced::font::Weight::Bold;
-                            }
-                            if data.flags.contains(ChildDataFlags::ITALIC) {
-                                f.style = iced::font::Style::Italic;
-                            }
-                            f
-                        });
+                    let span = |text: &'a str, find_color: Option<iced::Color>| {
+                        let mut t = widget::span(text)
+                            .size(size)
+                            .font(font)
+                            .color_maybe(data.color);
                         if data.flags.contains(ChildDataFlags::STRIKETHROUGH) {
                             t = t.strikethrough(true);
                         }
                         if data.flags.contains(ChildDataFlags::UNDERLINE) {
                             t = t.underline(true);
                         }
-                        if data.flags.contains(ChildDataFlags::HIGHLIGHT) {
-                            let highlight_color = self
-                                .style
-                                .and_then(|n| n.highlight_color)
+                        if let Some(color) = find_color {
+                            t = self.on_background(t, color, None);
+                        } else if data.flags.contains(ChildDataFlags::HIGHLIGHT) {
+                            let colors = self.colors();
+                            let highlight_color = colors
+                                .highlight_color
                                 .unwrap_or_else(|| iced::Color::from_rgb8(0xF7, 0xD8, 0x4B));
-                            t = t.background(highlight_color);
+                            t = self.on_background(
+                                t,
+                                highlight_color,
+                                data.color.or(colors.highlight_text_color),
+                            );
+                        } else if let Some(color) = data.background {
+                            t = self.on_background(t, color, data.color);
                         }
                         t
-                    }])
+                    };
+
+                    let mut rendered = RenderedSpan::None;
+                    let mut spans = Vec::new();
+                    // Only the first run of text is selectable,
+                    // so that the node isn't copied twice
+                    let mut handle = self.tracks_text().then(|| node.handle.clone());
+                    let segments = self.find_segments(&node.handle, text);
+                    for (text, find_color) in skip_start(segments, skip) {
+                        if self.fn_drawing_emoji.is_none() {
+                            spans.push(span(text, find_color));
+                            continue;
+                        }
+                        for (piece, is_emoji) in crate::emoji::split_emoji(text) {
+                            let image = if is_emoji {
+                                self.draw_emoji(piece, size)
+                            } else {
+                                None
+                            };
+                            let Some(image) = image else {
+                                spans.push(span(piece, find_color));
+                                continue;
+                            };
+                            if !spans.is_empty() {
+                                let nodes = handle.take().into_iter().collect();
+                                rendered = rendered
+                                    + RenderedSpan::Spans(std::mem::take(&mut spans), nodes);
+                            }
+                            rendered = rendered + image;
+                        }
+                    }
+                    if spans.is_empty() && !rendered.is_empty() {
+                        rendered
+                    } else {
+                        rendered + RenderedSpan::Spans(spans, handle.into_iter().collect())
+                    }
                 }
             }
-            markup5ever_rcdom::NodeData::Element { name, attrs, .. } => {
-                self.render_html_inner(name, attrs, node, data)
+            IrKind::Element { name, attrs } => {
+                self.render_html_inner(name, attrs, index, node.block, data)
             }
-            _ => RenderedSpan::None,
         }
     }
 
     fn render_html_inner(
-        &mut self,
-        name: &html5ever::QualName,
-        attrs: &std::cell::RefCell<Vec<html5ever::Attribute>>,
-        node: &Node,
+        &self,
+        name: &'a str,
+        attrs: &'a [html5ever::Attribute],
+        node: usize,
+        block_element: bool,
         mut data: ChildData,
     ) -> RenderedSpan<'a, M, T> {
-        let name = name.local.to_string();
-        let attrs = attrs.borrow();
-
-        let block_element = is_block_element(node);
         if block_element {
-            alignment_read(&mut data, &attrs);
+            alignment_read(&mut data, attrs);
         }
 
-        let e = match name.as_str() {
-            "summary" | "kbd" | "span" | "html" | "body" | "p" | "div" => {
-                self.render_children(node, data)
+        // Elements with `display: none` aren't in the IR at all
+        let css = self.state.ir.nodes[node].css;
+        let size = self.font_size(data);
+        css.apply(&mut data, size, self.text_size, block_element);
+
+        let custom = self.fn_rendering_element.as_deref().and_then(|func| {
+            // Only rendered if the closure asks for them,
+            // so that elements it doesn't handle aren't rendered twice
+            let children = || self.render_children(node, data).render();
+            func(ElementInfo {
+                tag: name,
+                attrs: attrs
+                    .iter()
+                    .map(|attr| (&*attr.name.local, &*attr.value))
+                    .collect(),
+                children: &children,
+            })
+        });
+
+        let e = if let Some(custom) = custom {
+            custom.into()
+        } else {
+            self.render_builtin(name, attrs, node, data, &css)
+        };
+
+        let mut e = if let (true, Some(align)) = (block_element, data.alignment) {
+            let align: iced::Alignment = align.into();
+            widget::column![e.render()]
+                .width(iced::Length::Fill)
+                .align_x(align)
+                .into()
+        } else {
+            e
+        };
+
+        // For `scroll_to_anchor` to find
+        for anchor in &self.state.ir.nodes[node].anchors {
+            e = widget::container(e.render()).id(anchor_id(anchor)).into();
+        }
+        e
+    }
+
+    fn render_builtin(
+        &self,
+        name: &str,
+        attrs: &'a [html5ever::Attribute],
+        node: usize,
+        mut data: ChildData,
+        css: &CssStyle,
+    ) -> RenderedSpan<'a, M, T> {
+        match name {
+            "div" if has_class(attrs, "markdown-alert") => {
+                self.draw_github_alert(node, attrs, data)
+            }
+            #[cfg(feature = "math")]
+            "span" | "code" if get_attr(attrs, "data-math-style").is_some() => {
+                self.draw_math(node, attrs, data)
             }
+            "summary" | "kbd" | "span" | "html" | "body" | "p" | "div" | "form" | "fieldset"
+            | "legend" | "label" => self.render_children(node, data),
             "center" => {
                 data.alignment = Some(ChildAlignment::Center);
                 self.render_children(node, data)
             }
-            "pre" => self.render_children(node, data.insert(ChildDataFlags::KEEP_WHITESPACE)),
+            "pre" => match self.draw_custom_code_block(node, attrs) {
+                Some(e) => e.into(),
+                None => self.render_children(node, data.insert(ChildDataFlags::KEEP_WHITESPACE)),
+            },
+
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name.as_bytes()[1] - b'0';
+                let mut data = data.heading(level.into());
+                // Headings are sized relative to the document,
+                // unless they have their own size
+                if css.font_size.is_none() {
+                    data.font_size = None;
+                }
+                let heading = match self.render_children(node, data) {
+                    RenderedSpan::Spans(spans, nodes) => {
+                        let style = self.class_fn();
+                        let plain = (!nodes.is_empty()).then(|| plain_spans(&spans));
+                        let text = widget::rich_text(spans)
+                            .on_link_click(std::convert::identity)
+                            .style(move |theme| widget::text::Style {
+                                color: style(theme).heading_color,
+                            });
+                        match plain {
+                            Some(plain) => {
+                                SelectableBlock::new(text, nodes).with_spans(plain).into()
+                            }
+                            None => text.into(),
+                        }
+                    }
+                    heading => heading,
+                };
+                heading
+            }
+            "sub" | "sup" => {
+                let mut data = data.heading(7);
+                if css.font_size.is_none() {
+                    data.font_size = None;
+                }
+                self.render_children(node, data)
+            }
 
-            "h1" => self.render_children(node, data.heading(1)),
-            "h2" => self.render_children(node, data.heading(2)),
-            "h3" => self.render_children(node, data.heading(3)),
-            "h4" => self.render_children(node, data.heading(4)),
-            "h5" => self.render_children(node, data.heading(5)),
-            "h6" => self.render_children(node, data.heading(6)),
-            "sub" => self.render_children(node, data.heading(7)),
+            "section" if has_class(attrs, "footnotes") => {
+                data.font_size = Some(self.font_size(data) * 0.875);
+                widget::column![
+                    self.rule(widget::rule::horizontal(1), |n| n.rule_color),
+                    self.render_children(node, data).render()
+                ]
+                .spacing(10)
+                .into()
+            }
+            "section" => self.render_children(node, data),
+
+            "dl" => self.render_children(node, data),
+            "dt" => self.render_children(node, data.insert(ChildDataFlags::BOLD)),
+            "dd" => widget::container(self.render_children(node, data).render())
+                .padding(Padding::default().left(self.font_size(data) * 2.0))
+                .into(),
 
+            "blockquote" if self.alert(node).is_some() => {
+                self.draw_alert(node, self.state.ir.children(node), None, data)
+            }
             "blockquote" => widget::stack!(
                 widget::row![
                     widget::space().width(10),
                     self.render_children(node, data).render()
                 ],
-                widget::rule::vertical(2)
+                self.rule(widget::rule::vertical(2), |n| n.quote_bar_color)
             )
             .into(),
 
@@ -145,82 +300,313 @@ where
             "del" | "s" | "strike" => {
                 self.render_children(node, data.insert(ChildDataFlags::STRIKETHROUGH))
             }
-            "code" => self.render_children(node, data.insert(ChildDataFlags::MONOSPACE)),
-            "mark" => self.render_children(node, data.insert(ChildDataFlags::HIGHLIGHT)),
+            "code" => {
+                #[cfg(feature = "highlighting")]
+                {
+                    data.syntax = get_attr(attrs, "class")
+                        .and_then(code_language)
+                        .and_then(crate::highlight::Syntax::find);
+                }
+                self.render_children(node, data.insert(ChildDataFlags::MONOSPACE))
+            }
+            // `<mark style="background: ..">` keeps its own color
+            "mark" if css.background.is_some() => self.render_children(node, data),
+            "mark" => {
+                data.background = None;
+                self.render_children(node, data.insert(ChildDataFlags::HIGHLIGHT))
+            }
 
             "details" => self.draw_details(node, data),
-            "a" => self.draw_link(node, &attrs, data),
-            "img" => self.draw_image(&attrs),
+            "table" => self.draw_table(node, data),
+            "a" => self.draw_link(node, attrs, data, css.color),
+            "img" => self.draw_image(attrs),
 
             "br" => widget::Column::new().into(),
-            "hr" => widget::rule::horizontal(1.0).into(),
-            "head" | "title" | "meta" => RenderedSpan::None,
+            "hr" => self
+                .rule(widget::rule::horizontal(1.0), |n| n.rule_color)
+                .into(),
+            "input" => self.draw_input(node, attrs, data),
+            "button" => self.draw_button(node, data),
+            "select" => self.draw_select(node, data),
+            "textarea" => self.draw_textarea(node, attrs, data),
 
-            "input" => match get_attr(&attrs, "type").unwrap_or("text") {
-                "checkbox" => {
-                    let checked = attrs.iter().any(|attr| &*attr.name.local == "checked");
-                    widget::checkbox(checked).into()
-                }
-                kind => {
-                    RenderedSpan::Spans(vec![widget::span(format!("<input type={kind} (TODO)>"))
-                        .font(Font {
-                            weight: iced::font::Weight::Bold,
-                            ..self.font
-                        })])
-                }
-            },
-
-            "ul" => {
-                data.li_ordered_number = None;
+            "ul" | "ol" => {
+                data.list = Some(self.list_data(name, attrs, node, data, css));
+                data.list_depth = data.list_depth.saturating_add(1);
                 self.render_children(node, data)
             }
-            "ol" => self.render_children(node, data.ordered()),
-            "li" => {
-                let bullet = if let Some(num) = data.li_ordered_number {
-                    widget::text!("{num}. ")
-                } else {
-                    widget::text("- ")
-                };
-                widget::row![bullet, self.render_children(node, data).render()].into()
+            "li" => self.draw_list_item(node, data, css),
+            _ => RenderedSpan::Spans(
+                vec![widget::span(format!("<{name} (TODO)>")).font(Font {
+                    weight: iced::font::Weight::Bold,
+                    ..self.font
+                })],
+                Vec::new(),
+            ),
+        }
+    }
+
+    fn list_data(
+        &self,
+        name: &str,
+        attrs: &[html5ever::Attribute],
+        node: usize,
+        data: ChildData,
+        css: &CssStyle,
+    ) -> ListData {
+        let ir = &self.state.ir;
+        let ordered = name == "ol";
+
+        let style = css.list_style.unwrap_or_else(|| {
+            match get_attr(attrs, "type") {
+                Some("1") => Some(ListStyle::Decimal),
+                Some("a") => Some(ListStyle::LowerAlpha),
+                Some("A") => Some(ListStyle::UpperAlpha),
+                Some("i") => Some(ListStyle::LowerRoman),
+                Some("I") => Some(ListStyle::UpperRoman),
+                Some(n) => parse_list_style(&n.to_ascii_lowercase()),
+                None => None,
+            }
+            .unwrap_or(if ordered {
+                ListStyle::Decimal
+            } else {
+                match data.list_depth {
+                    0 => ListStyle::Disc,
+                    1 => ListStyle::Circle,
+                    _ => ListStyle::Square,
+                }
+            })
+        });
+
+        let items = ir
+            .children(node)
+            .filter(|&n| ir.name(n) == Some("li"))
+            .count();
+        let items = i64::try_from(items).unwrap_or(i64::MAX);
+        let reversed = ordered && attrs.iter().any(|n| &*n.name.local == "reversed");
+        let step: i64 = if reversed { -1 } else { 1 };
+        let start = get_attr(attrs, "start")
+            .and_then(|n| n.trim().parse::<i64>().ok())
+            .unwrap_or(if reversed { items } else { 1 });
+        let end = start.saturating_add(step.saturating_mul((items - 1).max(0)));
+
+        let chars = list_marker(style, start)
+            .chars()
+            .count()
+            .max(list_marker(style, end).chars().count());
+        #[allow(clippy::cast_precision_loss)]
+        let marker_width = (chars as f32 * 0.6 + 0.4) * self.font_size(data);
+
+        ListData {
+            style,
+            number: start,
+            step,
+            marker_width,
+        }
+    }
+
+    fn draw_list_item(
+        &self,
+        node: usize,
+        mut data: ChildData,
+        css: &CssStyle,
+    ) -> RenderedSpan<'a, M, T> {
+        let size = self.font_size(data);
+        let list = data.list.take().unwrap_or(ListData {
+            style: ListStyle::Disc,
+            number: 1,
+            step: 1,
+            marker_width: size,
+        });
+        let style = css.list_style.unwrap_or(list.style);
+
+        let class = self.class_fn();
+        let marker = widget::text(list_marker(style, list.number))
+            .size(size)
+            .width(list.marker_width)
+            .align_x(iced::alignment::Horizontal::Right)
+            .style(move |theme| widget::text::Style {
+                color: class(theme).list_marker_color,
+            });
+
+        // The marker gets its own column, so that wrapped
+        // lines line up with the text instead of the marker
+        widget::row![marker, self.render_children(node, data).render()]
+            .spacing(size * 0.4)
+            .into()
+    }
+
+    /// The kind of alert a quote (`> [!NOTE]`) is, if it's a known one.
+    fn alert(&self, node: usize) -> Option<AlertStyle<'a, T>> {
+        let kind = self.state.ir.alerts.get(&node)?;
+        self.alert_style(kind)
+    }
+
+    /// The `[!KIND]`s of alerts that aren't shown,
+    /// for leaving them out of copied text.
+    fn hidden_text(&self) -> Hidden {
+        let ir = &self.state.ir;
+        ir.alert_texts
+            .iter()
+            .filter(|(_, (quote, _))| self.alert(*quote).is_some())
+            .map(|(&text, &(_, skip))| (node_key(&ir.nodes[text].handle), skip))
+            .collect()
+    }
+
+    fn alert_style(&self, kind: &str) -> Option<AlertStyle<'a, T>> {
+        let kind = kind.to_lowercase();
+        if let Some(n) = self.alert_kinds.get(&kind) {
+            let color = n.color;
+            return Some(AlertStyle {
+                title: n.title.clone(),
+                icon: n.icon.clone(),
+                color: Rc::new(move |_| color),
+            });
+        }
+
+        let (title, icon, default, color): (_, _, _, fn(crate::Style) -> Option<iced::Color>) =
+            match kind.as_str() {
+                "note" => ("Note", "ⓘ", [0x09, 0x69, 0xDA], |n| n.note_color),
+                "tip" => ("Tip", "💡", [0x1A, 0x7F, 0x37], |n| n.tip_color),
+                "important" => ("Important", "❢", [0x82, 0x50, 0xDF], |n| {
+                    n.important_color
+                }),
+                "warning" => ("Warning", "⚠", [0x9A, 0x67, 0x00], |n| n.warning_color),
+                "caution" => ("Caution", "⛔", [0xCF, 0x22, 0x2E], |n| n.caution_color),
+                _ => return None,
+            };
+        let style = self.class_fn();
+        Some(AlertStyle {
+            title: title.to_owned(),
+            icon: icon.to_owned(),
+            color: Rc::new(move |theme| {
+                color(style(theme))
+                    .unwrap_or_else(|| iced::Color::from_rgb8(default[0], default[1], default[2]))
+            }),
+        })
+    }
+
+    /// Alerts as GitHub (and comrak) write them in HTML:
+    /// `<div class="markdown-alert markdown-alert-note">`
+    /// with a `<p class="markdown-alert-title">`.
+    fn draw_github_alert(
+        &self,
+        node: usize,
+        attrs: &[html5ever::Attribute],
+        data: ChildData,
+    ) -> RenderedSpan<'a, M, T> {
+        let ir = &self.state.ir;
+        let title = ir.children(node).find(|&n| match &ir.nodes[n].kind {
+            IrKind::Element { attrs, .. } => has_class(attrs, "markdown-alert-title"),
+            _ => false,
+        });
+        let kind = get_attr(attrs, "class")
+            .unwrap_or_default()
+            .split_whitespace()
+            .find_map(|n| n.strip_prefix("markdown-alert-"))
+            .unwrap_or_default();
+
+        // Just the text, GitHub puts an `<svg>` icon in there too
+        let mut text = String::new();
+        if let Some(title) = title {
+            get_text(&ir.nodes[title].handle, &mut text);
+        }
+        let text = Some(text.trim().to_owned()).filter(|n| !n.is_empty());
+
+        self.draw_alert(
+            node,
+            ir.children(node).filter(|&n| Some(n) != title),
+            Some((kind, text)),
+            data,
+        )
+    }
+
+    /// Draws a callout with a title, and a colored bar and background.
+    ///
+    /// `title` is the kind of alert and its own title (if any),
+    /// otherwise the kind comes from [`Ir::alerts`].
+    fn draw_alert(
+        &self,
+        node: usize,
+        items: impl Iterator<Item = usize>,
+        title: Option<(&str, Option<String>)>,
+        data: ChildData,
+    ) -> RenderedSpan<'a, M, T> {
+        let (style, title) = match title {
+            Some((kind, title)) => (self.alert_style(kind), title),
+            None => (self.alert(node), None),
+        };
+        // Unknown kinds of `markdown-alert`s
+        let style = style.unwrap_or_else(|| {
+            let class = self.class_fn();
+            AlertStyle {
+                title: String::new(),
+                icon: String::new(),
+                color: Rc::new(move |theme| {
+                    class(theme)
+                        .quote_bar_color
+                        .unwrap_or(iced::Color::from_rgb8(0x80, 0x80, 0x80))
+                }),
             }
-            _ => RenderedSpan::Spans(vec![widget::span(format!("<{name} (TODO)>")).font(Font {
+        });
+        let title = title.unwrap_or(style.title);
+
+        let size = self.font_size(data);
+        let (bar, background, text) = (style.color.clone(), style.color.clone(), style.color);
+        let heading = widget::text(format!("{}  {title}", style.icon).trim().to_owned())
+            .size(size)
+            .font(Font {
                 weight: iced::font::Weight::Bold,
                 ..self.font
-            })]),
-        };
+            })
+            .style(move |theme| widget::text::Style {
+                color: Some(text(theme)),
+            });
 
-        if let (true, Some(align)) = (block_element, data.alignment) {
-            let align: iced::Alignment = align.into();
-            widget::column![e.render()]
-                .width(iced::Length::Fill)
-                .align_x(align)
-                .into()
-        } else {
-            e
-        }
+        widget::stack!(
+            widget::container(
+                widget::column![heading, self.render_items(items, data).render()].spacing(8)
+            )
+            .width(iced::Length::Fill)
+            .padding(Padding::default().left(14).right(8).top(8).bottom(8))
+            .style(move |theme| widget::container::Style {
+                background: Some(background(theme).scale_alpha(0.1).into()),
+                ..Default::default()
+            }),
+            widget::rule::vertical(3).style(move |theme| {
+                let mut rule = <T as widget::rule::Catalog>::style(
+                    theme,
+                    &<T as widget::rule::Catalog>::default(),
+                );
+                rule.color = bar(theme);
+                rule
+            })
+        )
+        .into()
     }
 
-    fn draw_details(&mut self, node: &Node, data: ChildData) -> RenderedSpan<'a, M, T> {
-        let e = if let (Some(update), Some(state)) = (
+    fn draw_details(&self, node: usize, data: ChildData) -> RenderedSpan<'a, M, T> {
+        let handle = &self.state.ir.nodes[node].handle;
+        let id = self.state.stable_ids.get(&node_key(handle)).copied();
+        if let (Some(update), Some(id), Some(state)) = (
             self.fn_update.clone(),
-            self.state
-                .dropdown_state
-                .get(&self.current_dropdown_id)
-                .copied(),
+            id,
+            id.and_then(|id| self.state.dropdown_state.get(&id).copied()),
         ) {
             let summary = self.get_summary_elements(node, data);
             let regular_children =
                 self.render_children(node, data.insert(ChildDataFlags::SKIP_SUMMARY));
 
             let umsg = UpdateMsg {
-                kind: UpdateMsgKind::DetailsToggle(self.current_dropdown_id, !state),
+                kind: UpdateMsgKind::DetailsToggle(id, !state),
             };
 
-            let link = if let RenderedSpan::Spans(n) = summary {
+            let link = if let RenderedSpan::Spans(n, nodes) = summary {
                 RenderedSpan::Spans(
                     n.into_iter()
                         .map(|n| n.link(update(umsg.clone())).underline(true))
                         .collect(),
+                    nodes,
                 )
                 .render()
             } else {
@@ -229,50 +615,205 @@ where
                     .into()
             };
 
+            let style = self.class_fn();
+            let chevron = if state {
+                widget::text("V").size(12)
+            } else {
+                widget::text(">").size(14)
+            }
+            .style(move |theme| widget::text::Style {
+                color: style(theme).details_marker_color,
+            });
+
             widget::stack![
                 widget::column![link]
                     .push(state.then_some(regular_children.render()))
                     .padding(Padding::default().left(20).bottom(5)),
-                widget::column![if state {
-                    widget::text("V").size(12)
-                } else {
-                    widget::text(">").size(14)
-                }]
-                .push(state.then_some(widget::rule::vertical(1)))
-                .spacing(5)
-                .padding(Padding::default().left(5).top(if state { 5 } else { 0 })),
+                widget::column![chevron]
+                    .push(state.then(|| {
+                        self.rule(widget::rule::vertical(1), |n| n.details_marker_color)
+                    }))
+                    .spacing(5)
+                    .padding(Padding::default().left(5).top(if state { 5 } else { 0 })),
             ]
             .into()
         } else {
             widget::column![
-                widget::rule::vertical(1),
+                self.rule(widget::rule::vertical(1), |n| n.rule_color),
                 self.render_children(node, data).render(),
-                widget::rule::horizontal(1),
+                self.rule(widget::rule::horizontal(1), |n| n.rule_color),
             ]
             .padding(10)
             .spacing(10)
             .into()
-        };
-        self.current_dropdown_id += 1;
-        e
+        }
     }
 
-    fn get_summary_elements(&mut self, node: &Node, data: ChildData) -> RenderedSpan<'a, M, T> {
-        node.children
-            .borrow()
-            .iter()
-            .find(|elem| {
-                if let NodeData::Element { name, .. } = &elem.data {
-                    &*name.local == "summary"
-                } else {
-                    false
+    fn draw_table(&self, node: usize, data: ChildData) -> RenderedSpan<'a, M, T> {
+        let ir: &'a Ir = &self.state.ir;
+        let mut rows = Vec::new();
+        let mut captions = Vec::new();
+        find_table_rows(ir, node, &mut rows, &mut captions);
+
+        let groups: Vec<_> = rows.iter().map(|(_, group)| *group).collect();
+        let mut grid = TableGrid::new(&groups);
+        let mut cells = Vec::new();
+
+        for (row, &(tr, _)) in rows.iter().enumerate() {
+            let IrKind::Element { attrs, .. } = &ir.nodes[tr].kind else {
+                continue;
+            };
+            let mut row_data = ChildData::default();
+            alignment_read(&mut row_data, attrs);
+
+            let mut column = 0;
+            for td in ir.children(tr) {
+                let IrKind::Element { name, attrs } = &ir.nodes[td].kind else {
+                    continue;
+                };
+                let is_header = match &**name {
+                    "th" => true,
+                    "td" => false,
+                    _ => continue,
+                };
+
+                let colspan = get_attr_span(attrs, "colspan", MAX_COLSPAN);
+                let rowspan = get_attr_span(attrs, "rowspan", grid.rows_left(row).min(MAX_ROWSPAN));
+                column = grid.place(row, column, colspan, rowspan);
+
+                let mut cell_data = data;
+                cell_data.alignment = row_data.alignment;
+                alignment_read(&mut cell_data, attrs);
+                let align_x = cell_data
+                    .alignment
+                    .take()
+                    .map_or(iced::Alignment::Start, Into::into);
+                if is_header {
+                    cell_data.flags.insert(ChildDataFlags::BOLD);
                 }
-            })
+
+                cells.push((
+                    CellInfo {
+                        row,
+                        column,
+                        rowspan,
+                        colspan,
+                        align_x,
+                    },
+                    self.render_children(td, cell_data).render(),
+                ));
+                column = column.saturating_add(colspan);
+            }
+        }
+
+        let style = self.class_fn();
+        let table = Table::new(cells).border_color(move |theme| style(theme).table_border_color);
+        if captions.is_empty() {
+            table.into()
+        } else {
+            let mut data = data;
+            data.alignment = None;
+            widget::column(
+                captions
+                    .iter()
+                    .map(|&n| self.render_children(n, data).render())
+                    .chain(std::iter::once(table.into())),
+            )
+            .spacing(self.paragraph_spacing.unwrap_or(5.0))
+            .into()
+        }
+    }
+
+    fn get_summary_elements(&self, node: usize, data: ChildData) -> RenderedSpan<'a, M, T> {
+        let ir: &'a Ir = &self.state.ir;
+        ir.children(node)
+            .find(|&n| ir.name(n) == Some("summary"))
             .map(|n| self.traverse_node(n, data))
             .unwrap_or_default()
     }
 
-    fn draw_image(&self, attrs: &[html5ever::Attribute]) -> RenderedSpan<'a, M, T> {
+    /// `$math$` and `$$math$$` (see the `math` feature),
+    /// shown as its source if it can't be laid out.
+    #[cfg(feature = "math")]
+    fn draw_math(
+        &self,
+        node: usize,
+        attrs: &[html5ever::Attribute],
+        data: ChildData,
+    ) -> RenderedSpan<'a, M, T> {
+        let display = get_attr(attrs, "data-math-style") == Some("display");
+        let mut source = String::new();
+        get_text(&self.state.ir.nodes[node].handle, &mut source);
+
+        let Some(math) = crate::math::Math::parse(source.trim(), display) else {
+            return self.render_children(node, data.insert(ChildDataFlags::MONOSPACE));
+        };
+        let math = math
+            .size(self.font_size(data))
+            .font(self.font)
+            .color(data.color);
+        if display {
+            widget::container(math)
+                .center_x(iced::Length::Fill)
+                .padding(Padding::default().top(5).bottom(5))
+                .into()
+        } else {
+            math.into()
+        }
+    }
+
+    /// Lets [`MarkWidget::on_drawing_code_block`] draw a
+    /// `<pre><code>` block, if it's just plain text.
+    fn draw_custom_code_block(
+        &self,
+        node: usize,
+        attrs: &'a [html5ever::Attribute],
+    ) -> Option<Element<'a, M, T>> {
+        let func = self.fn_drawing_code_block.as_deref()?;
+        let ir: &'a Ir = &self.state.ir;
+
+        let mut children = ir.children(node);
+        let (Some(code_node), None) = (children.next(), children.next()) else {
+            return None;
+        };
+        let IrKind::Element {
+            name,
+            attrs: code_attrs,
+        } = &ir.nodes[code_node].kind
+        else {
+            return None;
+        };
+        if &**name != "code" {
+            return None;
+        }
+
+        let mut texts = ir.children(code_node);
+        let code = match (texts.next(), texts.next()) {
+            (None, _) => "",
+            (Some(text), None) => match &ir.nodes[text].kind {
+                IrKind::Text(text) => text.strip_suffix('\n').unwrap_or(text),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        let language = get_attr(code_attrs, "class")
+            .and_then(code_language)
+            .or_else(|| get_attr(attrs, "lang"));
+        let info_string = match (language, get_attr(code_attrs, "data-meta")) {
+            (Some(language), Some(meta)) => format!("{language} {meta}"),
+            (None, Some(meta)) => meta.to_owned(),
+            (language, None) => language.unwrap_or_default().to_owned(),
+        };
+
+        func(CodeBlockInfo {
+            language,
+            info_string,
+            code,
+        })
+    }
+
+    fn draw_image(&self, attrs: &'a [html5ever::Attribute]) -> RenderedSpan<'a, M, T> {
         if let Some(attr) = attrs.iter().find(|attr| &*attr.name.local == "src") {
             let url = &*attr.value;
 
@@ -287,62 +828,440 @@ where
         RenderedSpan::None
     }
 
-    fn draw_link(
-        &mut self,
-        node: &Node,
-        attrs: &std::cell::Ref<'_, Vec<html5ever::Attribute>>,
+    /// Draws an emoji as an image, through [`MarkWidget::on_drawing_emoji`]
+    /// and [`MarkWidget::on_drawing_image`].
+    fn draw_emoji(&self, emoji: &str, size: f32) -> Option<RenderedSpan<'a, M, T>> {
+        let draw_image = self.fn_drawing_image.as_deref()?;
+        let url = self.fn_drawing_emoji.as_deref()?(emoji)?;
+        Some(
+            draw_image(ImageInfo {
+                url: &url,
+                width: Some(size),
+                height: Some(size),
+            })
+            .into(),
+        )
+    }
+
+    fn draw_task(&self, index: usize, attrs: &[html5ever::Attribute]) -> RenderedSpan<'a, M, T> {
+        let checked = attrs.iter().any(|attr| &*attr.name.local == "checked");
+        let source_range = self.state.tasks[index].source_range.clone();
+        widget::checkbox(checked)
+            .on_toggle_maybe(self.fn_toggling_task.clone().map(|func| {
+                move |checked| {
+                    func(TaskToggle {
+                        index,
+                        source_range: source_range.clone(),
+                        checked,
+                    })
+                }
+            }))
+            .into()
+    }
+
+    /// The form field made from this node, if any.
+    fn field(&self, node: usize) -> Option<(StableId, &'a Field)> {
+        let state: &'a crate::MarkState = self.state;
+        let id = *state
+            .stable_ids
+            .get(&node_key(&state.ir.nodes[node].handle))?;
+        Some((id, state.forms.fields.get(&id)?))
+    }
+
+    fn field_checked(&self, id: StableId) -> bool {
+        matches!(
+            self.state.form_state.get(&id),
+            Some(FieldValue::Checked(true))
+        )
+    }
+
+    fn draw_input(
+        &self,
+        node: usize,
+        attrs: &[html5ever::Attribute],
         data: ChildData,
     ) -> RenderedSpan<'a, M, T> {
-        let link_col = self
-            .style
-            .and_then(|n| n.link_color)
-            .unwrap_or_else(|| iced::Color::from_rgb8(0x5A, 0x6B, 0x9E));
+        let key = node_key(&self.state.ir.nodes[node].handle);
+        if let Some(&index) = self.state.task_ids.get(&key) {
+            return self.draw_task(index, attrs);
+        }
+        let Some((id, field)) = self.field(node) else {
+            return RenderedSpan::None;
+        };
+        let size = self.font_size(data);
+        let update = self.fn_update.clone().filter(|_| !field.disabled);
 
-        let children = self.render_children(node, data);
+        match &field.kind {
+            FieldKind::Text {
+                secure,
+                placeholder,
+            } => {
+                let value = match self.state.form_state.get(&id) {
+                    Some(FieldValue::Text(n)) => n.as_str(),
+                    _ => "",
+                };
+                let chars = get_attr(attrs, "size")
+                    .and_then(|n| n.trim().parse::<u16>().ok())
+                    .unwrap_or(20);
+                widget::text_input(placeholder, value)
+                    .secure(*secure)
+                    .size(size)
+                    .padding(4)
+                    .width(f32::from(chars) * size * 0.6 + 8.0)
+                    .on_input_maybe(update.map(|update| {
+                        move |text| {
+                            update(UpdateMsg {
+                                kind: UpdateMsgKind::FieldInput(id, text),
+                            })
+                        }
+                    }))
+                    .on_submit_maybe(self.submit_message(field.form, None))
+                    .into()
+            }
+            FieldKind::Checkbox => widget::checkbox(self.field_checked(id))
+                .size(size)
+                .on_toggle_maybe(update.map(|update| {
+                    move |checked| {
+                        update(UpdateMsg {
+                            kind: UpdateMsgKind::FieldChecked(id, checked),
+                        })
+                    }
+                }))
+                .into(),
+            FieldKind::Radio => {
+                let checked = self.field_checked(id);
+                if let Some(update) = update {
+                    widget::radio("", (), checked.then_some(()), |()| {
+                        update(UpdateMsg {
+                            kind: UpdateMsgKind::FieldChecked(id, true),
+                        })
+                    })
+                    .size(size)
+                    .spacing(0)
+                    .into()
+                } else {
+                    RenderedSpan::Spans(
+                        vec![widget::span(if checked { "◉" } else { "○" }).font(self.font)],
+                        Vec::new(),
+                    )
+                }
+            }
+            FieldKind::Submit | FieldKind::Reset | FieldKind::Button => {
+                let label = match (&field.kind, field.value.as_str()) {
+                    (FieldKind::Submit, "") => "Submit",
+                    (FieldKind::Reset, "") => "Reset",
+                    (_, value) => value,
+                };
+                self.form_button(id, field, widget::text(label.to_owned()).size(size).into())
+            }
+            FieldKind::Hidden | FieldKind::Select { .. } | FieldKind::TextArea => {
+                RenderedSpan::None
+            }
+        }
+    }
 
-        if let Some(attr) = attrs
-            .iter()
-            .find(|attr| attr.name.local.to_string().as_str() == "href")
-        {
-            let url = attr.value.to_string();
-            let children_empty = { node.children.borrow().is_empty() };
+    fn draw_button(&self, node: usize, data: ChildData) -> RenderedSpan<'a, M, T> {
+        let children = self.render_children(node, data).render();
+        match self.field(node) {
+            Some((id, field)) => self.form_button(id, field, children),
+            None => widget::button(children).into(),
+        }
+    }
+
+    /// A submit, reset or plain button.
+    fn form_button(
+        &self,
+        id: StableId,
+        field: &Field,
+        content: Element<'a, M, T>,
+    ) -> RenderedSpan<'a, M, T> {
+        let message = match (&field.kind, field.disabled) {
+            (FieldKind::Submit, false) => self.submit_message(field.form, Some(id)),
+            (FieldKind::Reset, false) => {
+                field
+                    .form
+                    .zip(self.fn_update.as_deref())
+                    .map(|(form, update)| {
+                        update(UpdateMsg {
+                            kind: UpdateMsgKind::FormReset(form),
+                        })
+                    })
+            }
+            _ => None,
+        };
+        widget::button(content)
+            .padding([2, 8])
+            .on_press_maybe(message)
+            .into()
+    }
 
-            let msg = self.fn_clicking_link.as_ref();
+    /// The message for submitting a form, if it can be.
+    fn submit_message(&self, form: Option<usize>, submitter: Option<StableId>) -> Option<M> {
+        let func = self.fn_submitting_form.as_deref()?;
+        let state = self.state;
+        Some(func(state.forms.submit(
+            form?,
+            &state.form_state,
+            submitter,
+        )))
+    }
 
-            if children_empty {
-                RenderedSpan::Spans(vec![
-                    link_text(widget::span(url.clone()), url, msg).color(link_col)
-                ])
-            } else if let RenderedSpan::Spans(n) = children {
-                RenderedSpan::Spans(
+    fn draw_select(&self, node: usize, data: ChildData) -> RenderedSpan<'a, M, T> {
+        let Some((id, field)) = self.field(node) else {
+            return RenderedSpan::None;
+        };
+        let FieldKind::Select { options, .. } = &field.kind else {
+            return RenderedSpan::None;
+        };
+        let size = self.font_size(data);
+        let selected = match self.state.form_state.get(&id) {
+            Some(FieldValue::Selected(n)) => n.and_then(|n| options.get(n)),
+            _ => None,
+        };
+
+        match self.fn_update.clone().filter(|_| !field.disabled) {
+            Some(update) => {
+                let options: &'a [SelectOption] = options;
+                widget::pick_list(options, selected, move |option| {
+                    let index = options.iter().position(|n| *n == option);
+                    update(UpdateMsg {
+                        kind: UpdateMsgKind::FieldSelect(id, index.unwrap_or_default()),
+                    })
+                })
+                .text_size(size)
+                .padding([2, 8])
+                .into()
+            }
+            None => widget::button(
+                widget::text(selected.map(|n| n.label.clone()).unwrap_or_default()).size(size),
+            )
+            .padding([2, 8])
+            .into(),
+        }
+    }
+
+    fn draw_textarea(
+        &self,
+        node: usize,
+        attrs: &[html5ever::Attribute],
+        data: ChildData,
+    ) -> RenderedSpan<'a, M, T> {
+        let state: &'a crate::MarkState = self.state;
+        let Some((id, field)) = self.field(node) else {
+            return RenderedSpan::None;
+        };
+        let Some(FieldValue::TextArea(content)) = state.form_state.get(&id) else {
+            return RenderedSpan::None;
+        };
+        let size = self.font_size(data);
+        let rows = get_attr(attrs, "rows")
+            .and_then(|n| n.trim().parse::<u16>().ok())
+            .unwrap_or(2);
+
+        let mut editor = widget::text_editor(content)
+            .size(size)
+            .padding(4)
+            .height(f32::from(rows) * size * 1.3 + 8.0);
+        if let Some(update) = self.fn_update.clone().filter(|_| !field.disabled) {
+            editor = editor.on_action(move |action| {
+                update(UpdateMsg {
+                    kind: UpdateMsgKind::FieldEdit(id, action),
+                })
+            });
+        }
+        if let Some(cols) = get_attr(attrs, "cols").and_then(|n| n.trim().parse::<u16>().ok()) {
+            editor = editor.width(f32::from(cols) * size * 0.6 + 8.0);
+        }
+        editor.into()
+    }
+
+    fn draw_link(
+        &self,
+        node: usize,
+        attrs: &[html5ever::Attribute],
+        data: ChildData,
+        color: Option<iced::Color>,
+    ) -> RenderedSpan<'a, M, T> {
+        let link_col = color
+            .or(self.colors().link_color)
+            .unwrap_or_else(|| iced::Color::from_rgb8(0x5A, 0x6B, 0x9E));
+
+        if get_attr(attrs, "data-wikilink").is_some() {
+            return self.draw_wikilink(node, attrs, data, link_col);
+        }
+
+        if let Some(href) = get_attr(attrs, "href") {
+            let (url, msg) = self.link_message(href.to_owned());
+            self.linked(node, data, href.to_owned(), url, msg, link_col)
+        } else {
+            match self.render_children(node, data) {
+                RenderedSpan::Spans(n, nodes) => RenderedSpan::Spans(
                     n.into_iter()
-                        .map(|n| link_text(n, url.clone(), msg).color(link_col))
+                        .map(|n| n.underline(true).color(link_col))
                         .collect(),
-                )
-            } else {
-                link(
+                    nodes,
+                ),
+                children => link(
                     children.render(),
-                    &url,
-                    msg,
+                    "",
+                    Some(&Self::e).filter(|_| false),
                     self.fn_style_link_button.clone(),
                 )
-                .into()
+                .into(),
+            }
+        }
+    }
+
+    /// Whether text has to remember the nodes it came from,
+    /// for selecting it or scrolling to a match of a find.
+    fn tracks_text(&self) -> bool {
+        self.selectable || !self.state.find.matches.is_empty()
+    }
+
+    /// Draws a `[[Page Name]]` link, going wherever
+    /// [`MarkWidget::on_resolving_wikilink`] says.
+    fn draw_wikilink(
+        &self,
+        node: usize,
+        attrs: &[html5ever::Attribute],
+        data: ChildData,
+        link_col: iced::Color,
+    ) -> RenderedSpan<'a, M, T> {
+        let page = percent_decode(get_attr(attrs, "href").unwrap_or_default());
+        let Some(resolve) = self.fn_resolving_wikilink.as_deref() else {
+            let (url, msg) = self.link_message(page.clone());
+            return self.linked(node, data, page, url, msg, link_col);
+        };
+
+        match resolve(&page) {
+            Some(ResolvedWikilink::Url(url)) => {
+                let (url, msg) = self.link_message(url);
+                self.linked(node, data, page, url, msg, link_col)
+            }
+            Some(ResolvedWikilink::Message(message)) => {
+                let msg = move |_| message.clone();
+                self.linked(node, data, page, String::new(), Some(&msg), link_col)
+            }
+            None => {
+                let color = self
+                    .colors()
+                    .unresolved_link_color
+                    .unwrap_or_else(|| iced::Color::from_rgb8(0xCF, 0x22, 0x2E));
+                self.linked(
+                    node,
+                    data,
+                    page.clone(),
+                    page,
+                    self.fn_clicking_link.as_ref(),
+                    color,
+                )
             }
-        } else if let RenderedSpan::Spans(n) = children {
-            RenderedSpan::Spans(
+        }
+    }
+
+    /// Picks where clicking a link to `url` goes: links to `#anchors`
+    /// go to [`MarkWidget::on_jumping_to_anchor`] (without the `#`) if it's set.
+    fn link_message(&self, url: String) -> (String, Option<&FClickLink<M>>) {
+        if let (Some(anchor), Some(f)) = (url.strip_prefix('#'), self.fn_jumping_to_anchor.as_ref())
+        {
+            (anchor.to_owned(), Some(f))
+        } else {
+            (url, self.fn_clicking_link.as_ref())
+        }
+    }
+
+    /// Makes the contents of a link clickable,
+    /// showing `text` if the link is empty.
+    fn linked<F: Fn(String) -> M>(
+        &self,
+        node: usize,
+        data: ChildData,
+        text: String,
+        url: String,
+        msg: Option<&F>,
+        link_col: iced::Color,
+    ) -> RenderedSpan<'a, M, T> {
+        if self.state.ir.children(node).next().is_none() {
+            return RenderedSpan::Spans(
+                vec![link_text(widget::span(text), url, msg).color(link_col)],
+                Vec::new(),
+            );
+        }
+
+        match self.render_children(node, data) {
+            RenderedSpan::Spans(n, nodes) => RenderedSpan::Spans(
                 n.into_iter()
-                    .map(|n| n.underline(true).color(link_col))
+                    .map(|n| link_text(n, url.clone(), msg).color(link_col))
                     .collect(),
-            )
-        } else {
-            link(
+                nodes,
+            ),
+            children => link(
                 children.render(),
-                "",
-                Some(&Self::e).filter(|_| false),
+                &url,
+                msg,
                 self.fn_style_link_button.clone(),
             )
-            .into()
+            .into(),
+        }
+    }
+
+    /// The size of text, based on the heading level
+    /// (or the CSS `font-size`).
+    fn font_size(&self, data: ChildData) -> f32 {
+        if let Some(size) = data.font_size {
+            return size;
+        }
+        let scaling = match data.heading_weight {
+            1 => 1.8,
+            2 => 1.5,
+            3 => 1.25,
+            4 => 1.15,
+            5 => 0.875,
+            6 => 0.75,
+            7 => 0.625,
+            _ => 1.0,
+        };
+        self.text_size * (1.0 + ((scaling - 1.0) * self.heading_scale))
+    }
+
+    /// Puts a background behind text, making sure
+    /// the text stays readable on top of it.
+    fn on_background(
+        &self,
+        span: widget::text::Span<'a, M, Font>,
+        background: iced::Color,
+        text_color: Option<iced::Color>,
+    ) -> widget::text::Span<'a, M, Font> {
+        let span = span.background(background);
+        if let Some(color) = text_color {
+            return span.color(color);
+        }
+        // Mostly see-through backgrounds don't change
+        // the contrast much, so leave those alone
+        if background.a <= 0.5 {
+            return span;
         }
+        let text = self.colors().text_color.unwrap_or(iced::Color::BLACK);
+        span.color(iced::theme::palette::readable(background, text))
+    }
+
+    /// Colors a rule with one of the colors from the theme (if set).
+    fn rule(
+        &self,
+        rule: widget::Rule<'a, T>,
+        color: impl Fn(crate::Style) -> Option<iced::Color> + 'a,
+    ) -> widget::Rule<'a, T> {
+        let style = self.class_fn();
+        rule.style(move |theme| {
+            let mut rule = <T as widget::rule::Catalog>::style(
+                theme,
+                &<T as widget::rule::Catalog>::default(),
+            );
+            if let Some(color) = color(style(theme)) {
+                rule.color = color;
+            }
+            rule
+        })
     }
 
     fn e(_: String) -> M {
@@ -350,36 +1269,49 @@ where
         panic!()
     }
 
-    fn render_children(&mut self, node: &Node, data: ChildData) -> RenderedSpan<'a, M, T> {
-        let children = node.children.borrow();
+    fn render_children(&self, node: usize, data: ChildData) -> RenderedSpan<'a, M, T> {
+        self.render_items(self.state.ir.children(node), data)
+    }
+
+    /// Renders a run of sibling nodes, putting
+    /// inline ones in rows and block ones in a column.
+    pub(crate) fn render_items(
+        &self,
+        items: impl Iterator<Item = usize>,
+        data: ChildData,
+    ) -> RenderedSpan<'a, M, T> {
+        let ir: &'a Ir = &self.state.ir;
 
         let mut column = Vec::new();
         let mut row = RenderedSpan::None;
 
         let mut skipped_summary = false;
 
-        let mut i = 0;
-        for item in children.iter() {
-            if is_node_useless(item) {
+        // Numbers of `<li>` elements, if this is a list
+        let mut list = data.list;
+
+        for item in items {
+            if !skipped_summary
+                && data.flags.contains(ChildDataFlags::SKIP_SUMMARY)
+                && ir.name(item) == Some("summary")
+            {
+                skipped_summary = true;
                 continue;
             }
-            if let NodeData::Element { name, .. } = &item.data {
-                if !skipped_summary
-                    && data.flags.contains(ChildDataFlags::SKIP_SUMMARY)
-                    && &*name.local == "summary"
-                {
-                    skipped_summary = true;
-                    continue;
-                }
-            }
 
             let mut data = data;
-            if data.li_ordered_number.is_some() {
-                data.li_ordered_number = Some(i + 1);
+            if let (Some(list), Some("li")) = (&mut list, ir.name(item)) {
+                if let IrKind::Element { attrs, .. } = &ir.nodes[item].kind {
+                    if let Some(value) = get_attr(attrs, "value").and_then(|n| n.parse().ok()) {
+                        list.number = value;
+                    }
+                }
+                data.list = Some(*list);
+                list.number = list.number.saturating_add(list.step);
             }
             let element = self.traverse_node(item, data);
 
-            if is_block_element(item) {
+            if ir.nodes[item].block {
                 if !row.is_empty() {
                     let mut old_row = RenderedSpan::None;
                     std::mem::swap(&mut row, &mut old_row);
@@ -390,8 +1322,6 @@ where
             } else {
                 row = row + element;
             }
-
-            i += 1;
         }
 
         if !row.is_empty() {
@@ -417,24 +1347,112 @@ where
         }
     }
 
-    fn codeblock(&self, code: String, size: f32, inline: bool) -> RenderedSpan<'a, M, T> {
-        if let (false, Some(state), Some(select)) = (
+    /// Splits the text of a node into pieces, with the background
+    /// color of the [`crate::MarkState::find`] match they're in (if any).
+    fn find_segments(&self, node: &Handle, text: &'a str) -> Vec<(&'a str, Option<iced::Color>)> {
+        let find = &self.state.find;
+        find.segments(node, text)
+            .into_iter()
+            .map(|(text, i)| {
+                let color = i.map(|i| {
+                    let colors = self.colors();
+                    if find.current == Some(i) {
+                        colors
+                            .find_current_color
+                            .unwrap_or_else(|| iced::Color::from_rgb8(0xFF, 0x96, 0x32))
+                    } else {
+                        colors.find_match_color.unwrap_or_else(|| {
+                            iced::Color::from_rgb8(0xF7, 0xD8, 0x4B).scale_alpha(0.5)
+                        })
+                    }
+                });
+                (text, color)
+            })
+            .collect()
+    }
+
+    fn codeblock(
+        &self,
+        node: &Handle,
+        code: &'a str,
+        size: f32,
+        data: ChildData,
+    ) -> RenderedSpan<'a, M, T> {
+        let inline = !data.flags.contains(ChildDataFlags::KEEP_WHITESPACE);
+        let id = self.state.stable_ids.get(&node_key(node)).copied();
+        if let (false, Some(id), Some(state), Some(select)) = (
             inline,
-            self.state.selection_state.get(&code),
+            id,
+            id.and_then(|id| self.state.selection_state.get(&id)),
             self.fn_update.clone(),
         ) {
-            widget::text_editor(state)
+            let style = self.class_fn();
+            let editor = widget::text_editor(state)
                 .size(size)
                 .padding(5)
                 .font(self.font_mono)
+                .style(move |theme, status| {
+                    let colors = style(theme);
+                    let mut style = <T as widget::text_editor::Catalog>::style(
+                        theme,
+                        &<T as widget::text_editor::Catalog>::default(),
+                        status,
+                    );
+                    if let Some(color) = colors.code_block_background {
+                        style.background = color.into();
+                    }
+                    if let Some(color) = colors.code_block_border_color {
+                        style.border.color = color;
+                    }
+                    style
+                })
                 .on_action(move |action| {
                     select(UpdateMsg {
-                        kind: UpdateMsgKind::TextEditor(code.clone(), action),
+                        kind: UpdateMsgKind::TextEditor(id, action),
                     })
-                })
-                .into()
+                });
+
+            #[cfg(feature = "highlighting")]
+            if let Some(syntax) = data.syntax {
+                return SelectableBlock::new(
+                    editor.highlight_with::<crate::highlight::Highlighter>(
+                        crate::highlight::Settings {
+                            syntax,
+                            theme: self
+                                .highlight_theme
+                                .or(self.colors().highlight_theme)
+                                .unwrap_or_default(),
+                        },
+                        crate::highlight::to_format,
+                    ),
+                    vec![node.clone()],
+                )
+                .into();
+            }
+
+            SelectableBlock::new(editor, vec![node.clone()]).into()
         } else {
-            RenderedSpan::Spans(vec![widget::span(code).size(size).font(self.font_mono)])
+            let colors = self.colors();
+            let spans = self
+                .find_segments(node, code)
+                .into_iter()
+                .map(|(code, find_color)| {
+                    let mut t = widget::span(code).size(size).font(self.font_mono);
+                    if inline {
+                        t = t.color_maybe(data.color.or(colors.inline_code_color));
+                    }
+                    if let Some(color) = find_color {
+                        self.on_background(t, color, None)
+                    } else if let (true, Some(color)) = (inline, colors.inline_code_background) {
+                        t.background(color)
+                            .padding(Padding::default().left(2).right(2))
+                    } else {
+                        t
+                    }
+                })
+                .collect();
+            let nodes = self.tracks_text().then(|| node.clone());
+            RenderedSpan::Spans(spans, nodes.into_iter().collect())
         }
     }
 }
@@ -455,10 +1473,160 @@ fn alignment_read(data: &mut ChildData, attrs: &[html5ever::Attribute]) {
     }
 }
 
+/// Takes the first `skip` bytes off some text split into pieces.
+fn skip_start<C>(segments: Vec<(&str, C)>, mut skip: usize) -> Vec<(&str, C)> {
+    segments
+        .into_iter()
+        .filter_map(|(text, color)| {
+            let cut = skip.min(text.len());
+            skip -= cut;
+            (cut < text.len()).then(|| (&text[cut..], color))
+        })
+        .collect()
+}
+
+/// The marker in front of a list item, like `•`, `3.` or `iv.`
+fn list_marker(style: ListStyle, number: i64) -> String {
+    match style {
+        ListStyle::Disc => "•".to_owned(),
+        ListStyle::Circle => "◦".to_owned(),
+        ListStyle::Square => "▪".to_owned(),
+        ListStyle::None => String::new(),
+        ListStyle::Decimal => format!("{number}."),
+        ListStyle::LowerAlpha | ListStyle::UpperAlpha => {
+            let Some(mut n) = u64::try_from(number).ok().filter(|n| *n > 0) else {
+                return format!("{number}.");
+            };
+            // a, b, .. z, aa, ab, ..
+            let mut letters = Vec::new();
+            while n > 0 {
+                n -= 1;
+                letters.push(b'a' + u8::try_from(n % 26).unwrap_or(0));
+                n /= 26;
+            }
+            let text: String = letters.into_iter().rev().map(char::from).collect();
+            if style == ListStyle::UpperAlpha {
+                format!("{}.", text.to_uppercase())
+            } else {
+                format!("{text}.")
+            }
+        }
+        ListStyle::LowerRoman | ListStyle::UpperRoman => {
+            if !(1..4000).contains(&number) {
+                return format!("{number}.");
+            }
+            const NUMERALS: [(i64, &str); 13] = [
+                (1000, "m"),
+                (900, "cm"),
+                (500, "d"),
+                (400, "cd"),
+                (100, "c"),
+                (90, "xc"),
+                (50, "l"),
+                (40, "xl"),
+                (10, "x"),
+                (9, "ix"),
+                (5, "v"),
+                (4, "iv"),
+                (1, "i"),
+            ];
+            let mut n = number;
+            let mut text = String::new();
+            for (value, numeral) in NUMERALS {
+                while n >= value {
+                    text.push_str(numeral);
+                    n -= value;
+                }
+            }
+            if style == ListStyle::UpperRoman {
+                format!("{}.", text.to_uppercase())
+            } else {
+                format!("{text}.")
+            }
+        }
+    }
+}
+
+/// The largest `colspan` and `rowspan` allowed,
+/// same as in browsers (see the HTML spec).
+const MAX_COLSPAN: usize = 1000;
+const MAX_ROWSPAN: usize = 65534;
+
+/// Reads a `colspan`/`rowspan` attribute, clamped to `1..=max`.
+/// `rowspan="0"` spans all the way to `max`.
+fn get_attr_span(attrs: &[html5ever::Attribute], attr_name: &str, max: usize) -> usize {
+    let max = max.max(1);
+    match get_attr(attrs, attr_name).and_then(|n| n.trim().parse::<usize>().ok()) {
+        Some(0) if attr_name == "rowspan" => max,
+        span => span.unwrap_or(1).clamp(1, max),
+    }
+}
+
+/// Finds the `<tr>`s of a table, along with
+/// the `<thead>`/`<tbody>`/`<tfoot>` they're in.
+fn find_table_rows(
+    ir: &Ir,
+    node: usize,
+    rows: &mut Vec<(usize, usize)>,
+    captions: &mut Vec<usize>,
+) {
+    for child in ir.children(node) {
+        match ir.name(child) {
+            Some("tr") => rows.push((child, node)),
+            Some("caption") => captions.push(child),
+            Some("thead" | "tbody" | "tfoot") => find_table_rows(ir, child, rows, captions),
+            _ => {}
+        }
+    }
+}
+
+/// Gets the language of a code block from the
+/// `class="language-xyz"` that comrak puts on `<code>`.
+fn code_language(class: &str) -> Option<&str> {
+    class.split_whitespace().find_map(|n| {
+        n.strip_prefix("language-")
+            .or_else(|| n.strip_prefix("lang-"))
+    })
+}
+
+/// Undoes the `%20` escaping of URLs
+/// (eg: in the `href` of wikilinks from comrak).
+fn percent_decode(url: &str) -> String {
+    let mut bytes = Vec::with_capacity(url.len());
+    let mut rest = url.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let escaped = (b == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(escaped) = escaped {
+            bytes.push(escaped);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
 fn get_attr_num(attrs: &[html5ever::Attribute], attr_name: &str) -> Option<f32> {
     get_attr(attrs, attr_name).and_then(|n| n.parse::<f32>().ok())
 }
 
+/// How an alert looks, see [`MarkWidget::alert_kind`].
+struct AlertStyle<'a, T> {
+    title: String,
+    icon: String,
+    color: Rc<dyn Fn(&T) -> iced::Color + 'a>,
+}
+
+fn has_class(attrs: &[html5ever::Attribute], class: &str) -> bool {
+    get_attr(attrs, "class").is_some_and(|n| n.split_whitespace().any(|n| n == class))
+}
+
 fn get_attr<'a>(attrs: &'a [html5ever::Attribute], attr_name: &str) -> Option<&'a str> {
     attrs
         .iter()
@@ -469,63 +1637,6 @@ fn get_attr<'a>(attrs: &'a [html5ever::Attribute], attr_name: &str) -> Option<&'
         .map(|n| &*n.value)
 }
 
-fn is_node_useless(node: &Node) -> bool {
-    if let markup5ever_rcdom::NodeData::Text { contents } = &node.data {
-        let contents = contents.borrow();
-        let contents = contents.to_string();
-        contents.trim().is_empty()
-    } else {
-        false
-    }
-}
-
-fn is_block_element(node: &Node) -> bool {
-    let markup5ever_rcdom::NodeData::Element { name, .. } = &node.data else {
-        return false;
-    };
-    let n: &str = &name.local;
-
-    matches!(
-        n,
-        "address"
-            | "article"
-            | "aside"
-            | "blockquote"
-            | "canvas"
-            | "dd"
-            | "div"
-            | "dl"
-            | "dt"
-            | "fieldset"
-            | "figcaption"
-            | "figure"
-            | "footer"
-            | "form"
-            | "h1"
-            | "h2"
-            | "h3"
-            | "h4"
-            | "h5"
-            | "h6"
-            | "header"
-            | "hr"
-            | "li"
-            | "main"
-            | "nav"
-            | "noscript"
-            | "ol"
-            | "p"
-            | "pre"
-            | "section"
-            | "table"
-            | "tfoot"
-            | "ul"
-            | "video"
-            | "br"
-            | "summary" // not really block but acts like it
-    )
-}
-
 impl<
         'a,
         M: Clone + 'static,
@@ -534,28 +1645,409 @@ impl<
             + widget::rule::Catalog
             + widget::text_editor::Catalog
             + widget::checkbox::Catalog
+            + widget::radio::Catalog
+            + widget::text_input::Catalog
+            + widget::pick_list::Catalog
+            + widget::overlay::menu::Catalog
+            + widget::container::Catalog
+            + crate::Catalog
             + 'a,
     > From<MarkWidget<'a, M, T>> for Element<'a, M, T>
 where
     <T as widget::button::Catalog>::Class<'a>: From<widget::button::StyleFn<'a, T>>,
+    <T as widget::text::Catalog>::Class<'a>: From<widget::text::StyleFn<'a, T>>,
+    <T as widget::rule::Catalog>::Class<'a>: From<widget::rule::StyleFn<'a, T>>,
+    <T as widget::text_editor::Catalog>::Class<'a>: From<widget::text_editor::StyleFn<'a, T>>,
+    <T as widget::container::Catalog>::Class<'a>: From<widget::container::StyleFn<'a, T>>,
 {
-    fn from(mut value: MarkWidget<'a, M, T>) -> Self {
-        let node = &value.state.dom.document;
-        value.traverse_node(node, ChildData::default()).render()
+    fn from(value: MarkWidget<'a, M, T>) -> Self {
+        let selection = value.selectable.then(|| {
+            (
+                value.fn_update.clone(),
+                value.colors().selection_color,
+                value.hidden_text(),
+            )
+        });
+        let style = value.class_fn();
+
+        let element = if value.virtualized {
+            let blocks: &'a [IrBlock] = &value.state.ir.blocks;
+            let spacing = value.paragraph_spacing.unwrap_or(5.0);
+            let text_size = value.text_size;
+            VirtualColumn::new(
+                blocks,
+                move |i| {
+                    value
+                        .render_items(blocks[i].items.iter().copied(), ChildData::default())
+                        .render()
+                },
+                spacing,
+                text_size,
+            )
+            .into()
+        } else {
+            value.traverse_node(Ir::ROOT, ChildData::default()).render()
+        };
+
+        let element = if let Some((fn_update, color, hidden)) = selection {
+            SelectionArea::new(element, fn_update, color)
+                .with_hidden(hidden)
+                .into()
+        } else {
+            element
+        };
+
+        // Sets the default text color for everything inside
+        widget::container(element)
+            .style(move |theme| widget::container::Style {
+                text_color: style(theme).text_color,
+                ..Default::default()
+            })
+            .into()
     }
 }
 
-fn clean_whitespace(input: &str) -> String {
-    let mut s = input.split_whitespace().collect::<Vec<&str>>().join(" ");
-    if let Some(last) = input.chars().last() {
-        if last.is_whitespace() && last != '\n' {
-            s.push(last);
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use crate::{MarkState, MarkWidget};
+
+    #[test]
+    #[cfg(all(feature = "markdown", feature = "iced-tiny-skia"))]
+    fn clicking_links() {
+        let state = MarkState::with_html_and_markdown(
+            "[see below](#installation) and [docs](https://example.com)\n\n# Installation",
+        );
+        let clicked = |x: f32| {
+            let mut element: iced::Element<'_, String> = MarkWidget::new(&state)
+                .on_jumping_to_anchor(|n| format!("jump {n}"))
+                .on_clicking_link(|n| format!("open {n}"))
+                .into();
+            crate::testing::click(&mut element, |layout| {
+                layout.bounds().position() + iced::Vector::new(x, 8.0)
+            })
+        };
+        assert_eq!(clicked(5.0), ["jump installation"]);
+        assert_eq!(clicked(130.0), ["open https://example.com"]);
+    }
+
+    #[test]
+    #[cfg(feature = "iced-tiny-skia")]
+    fn text_is_tracked_for_selection_and_find() {
+        let mut state = MarkState::with_html("<p>foo</p><h1>bar</h1><pre>baz</pre>");
+        let blocks = |state: &MarkState, selectable: bool| {
+            let mut element: iced::Element<'_, ()> =
+                MarkWidget::new(state).selectable(selectable).into();
+            let blocks = crate::testing::blocks(&mut element);
+            blocks.into_iter().map(|(text, _)| text).collect::<Vec<_>>()
+        };
+        assert!(blocks(&state, false).is_empty());
+        assert_eq!(blocks(&state, true), ["foo", "bar", "baz"]);
+
+        state.find("bar", crate::FindOptions::default());
+        assert_eq!(blocks(&state, false), ["foo", "bar", "baz"]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "markdown", feature = "iced-tiny-skia"))]
+    fn footnotes() {
+        use iced::advanced::widget::{Id, Operation};
+
+        /// Finds the ids of containers, like `scroll_to_anchor` does.
+        #[derive(Default)]
+        struct Ids(Vec<Id>);
+
+        impl Operation for Ids {
+            fn traverse(&mut self, operate: &mut dyn FnMut(&mut dyn Operation)) {
+                operate(self);
+            }
+
+            fn container(&mut self, id: Option<&Id>, _bounds: iced::Rectangle) {
+                self.0.extend(id.cloned());
+            }
+        }
+
+        let state = MarkState::with_html_and_markdown("Text[^note]\n\n[^note]: The note.");
+        let anchors: Vec<_> = state.ir.blocks.iter().map(|n| n.anchors.clone()).collect();
+        // The reference and the note link to each other
+        assert_eq!(anchors, [["fnref-note"], ["fn-note"]]);
+
+        for virtualized in [false, true] {
+            let mut element: iced::Element<'_, ()> =
+                MarkWidget::new(&state).virtualized(virtualized).into();
+            let renderer = crate::testing::renderer();
+            let mut tree = iced::advanced::widget::Tree::new(&element);
+            let limits = iced::advanced::layout::Limits::new(
+                iced::Size::ZERO,
+                iced::Size::new(800.0, f32::INFINITY),
+            );
+            let node = element
+                .as_widget_mut()
+                .layout(&mut tree, &renderer, &limits);
+
+            let mut ids = Ids::default();
+            element.as_widget_mut().operate(
+                &mut tree,
+                iced::advanced::Layout::new(&node),
+                &renderer,
+                &mut ids,
+            );
+            for anchor in ["fnref-note", "fn-note"] {
+                assert!(ids.0.contains(&super::anchor_id(anchor)), "{anchor}");
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "markdown", feature = "iced-tiny-skia"))]
+    fn definition_lists() {
+        use super::RenderedSpan;
+        use crate::structs::ChildData;
+
+        let state = MarkState::with_html_and_markdown("Term\n\n: Meaning\n\nAfter");
+        let ir = &state.ir;
+        let dt = (0..ir.nodes.len())
+            .find(|&n| ir.name(n) == Some("dt"))
+            .unwrap();
+        let widget: MarkWidget<'_, ()> = MarkWidget::new(&state);
+        let RenderedSpan::Spans(spans, _) = widget.traverse_node(dt, ChildData::default()) else {
+            panic!("not text");
+        };
+        assert!(spans
+            .iter()
+            .all(|n| n.font.is_some_and(|n| n.weight == iced::font::Weight::Bold)));
+
+        let mut element: iced::Element<'_, ()> = MarkWidget::new(&state).selectable(true).into();
+        let blocks = crate::testing::blocks(&mut element);
+        let x: Vec<_> = blocks
+            .iter()
+            .map(|(text, n)| (text.as_str(), n.x))
+            .collect();
+        // The definition is indented under its term
+        assert_eq!(x, [("Term", 0.0), ("Meaning", 32.0), ("After", 0.0)]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "markdown", feature = "iced-tiny-skia"))]
+    fn code_block_hook() {
+        use std::cell::RefCell;
+
+        let state = MarkState::with_html_and_markdown(
+            "```rust title=\"main.rs\"\nfn main() {}\n```\n\n```\nplain\n```\n\n\
+             <pre><code>mixed <b>content</b></code></pre>",
+        );
+        let seen = RefCell::new(Vec::new());
+        let mut element: iced::Element<'_, ()> = MarkWidget::new(&state)
+            .selectable(true)
+            .on_drawing_code_block(|info| {
+                seen.borrow_mut().push((
+                    info.language.map(str::to_owned),
+                    info.info_string.clone(),
+                    info.code.to_owned(),
+                ));
+                (info.language == Some("rust")).then(|| iced::widget::text("custom").into())
+            })
+            .into();
+        let blocks = crate::testing::blocks(&mut element);
+        drop(element);
+
+        assert_eq!(
+            seen.into_inner(),
+            [
+                (
+                    Some("rust".to_owned()),
+                    "rust title=\"main.rs\"".to_owned(),
+                    "fn main() {}".to_owned()
+                ),
+                (None, String::new(), "plain".to_owned()),
+            ]
+        );
+        // The others are drawn as usual
+        let texts: Vec<_> = blocks.iter().map(|(text, _)| text.as_str()).collect();
+        assert_eq!(texts, ["plain\n", "mixed content"]);
+    }
+
+    #[test]
+    fn percent_decoding() {
+        use super::percent_decode;
+
+        assert_eq!(percent_decode("My%20Page"), "My Page");
+        assert_eq!(percent_decode("caf%C3%A9"), "café");
+        // Not escapes, left alone
+        assert_eq!(percent_decode("%zz 100% %+1 %"), "%zz 100% %+1 %");
+        assert_eq!(percent_decode("%FF"), "\u{FFFD}");
+    }
+
+    #[test]
+    fn alert_kinds() {
+        use super::RenderedSpan;
+        use crate::{find::node_key, structs::ChildData, AlertKind};
+
+        let state = MarkState::with_html(
+            "<blockquote><p>[!NOTE]\nnoted</p></blockquote>\
+             <blockquote><p>[!bug]\nbuggy</p></blockquote>\
+             <blockquote><p>[!TODO]\nfix this</p></blockquote>",
+        );
+        let widget: MarkWidget<'_, ()> = MarkWidget::new(&state).alert_kind(
+            "BUG",
+            AlertKind {
+                title: "Bug".to_owned(),
+                icon: String::new(),
+                color: iced::Color::BLACK,
+            },
+        );
+
+        let ir = &state.ir;
+        let mut texts: Vec<_> = ir.alert_texts.iter().collect();
+        texts.sort();
+        let shown: Vec<_> = texts
+            .iter()
+            .map(|(&text, &(quote, _))| {
+                let RenderedSpan::Spans(spans, _) =
+                    widget.traverse_node(text, ChildData::default())
+                else {
+                    panic!("not text");
+                };
+                let text: String = spans.iter().map(|n| &*n.text).collect();
+                (text, widget.alert(quote).is_some())
+            })
+            .collect();
+        assert_eq!(
+            shown,
+            [
+                ("noted".to_owned(), true),
+                ("buggy".to_owned(), true),
+                // Unknown kinds are regular quotes, marker and all
+                ("[!TODO] fix this".to_owned(), false),
+            ]
+        );
+
+        let hidden = widget.hidden_text();
+        assert_eq!(hidden.len(), 2);
+        assert_eq!(
+            hidden.get(&node_key(&ir.nodes[*texts[0].0].handle)),
+            Some(&8)
+        );
+    }
+
+    #[test]
+    fn list_markers() {
+        use super::list_marker;
+        use crate::structs::ListStyle;
+
+        assert_eq!(list_marker(ListStyle::Decimal, -3), "-3.");
+        assert_eq!(list_marker(ListStyle::LowerAlpha, 0), "0.");
+        assert_eq!(list_marker(ListStyle::LowerAlpha, -1), "-1.");
+        assert_eq!(list_marker(ListStyle::LowerAlpha, 1), "a.");
+        assert_eq!(list_marker(ListStyle::LowerAlpha, 26), "z.");
+        assert_eq!(list_marker(ListStyle::UpperAlpha, 27), "AA.");
+        assert_eq!(list_marker(ListStyle::LowerAlpha, 702), "zz.");
+        assert_eq!(list_marker(ListStyle::LowerAlpha, 703), "aaa.");
+        assert_eq!(list_marker(ListStyle::LowerRoman, 0), "0.");
+        assert_eq!(list_marker(ListStyle::LowerRoman, -4), "-4.");
+        assert_eq!(list_marker(ListStyle::LowerRoman, 4), "iv.");
+        assert_eq!(list_marker(ListStyle::UpperRoman, 1994), "MCMXCIV.");
+        assert_eq!(list_marker(ListStyle::UpperRoman, 3999), "MMMCMXCIX.");
+        assert_eq!(list_marker(ListStyle::UpperRoman, 4000), "4000.");
+        let max = list_marker(ListStyle::LowerAlpha, i64::MAX);
+        assert!(max
+            .trim_end_matches('.')
+            .bytes()
+            .all(|c| c.is_ascii_lowercase()));
+    }
+
+    #[test]
+    fn huge_list_numbers_saturate() {
+        for html in [
+            "<ol start=\"9223372036854775807\"><li>a</li><li>b</li><li>c</li></ol>",
+            "<ol start=\"-9223372036854775808\" reversed><li>a</li><li>b</li></ol>",
+            "<ol><li value=\"9223372036854775807\">a</li><li>b</li></ol>",
+        ] {
+            let state = MarkState::with_html(html);
+            let _: iced::Element<'_, ()> = MarkWidget::new(&state).into();
         }
     }
-    if let Some(first) = input.chars().next() {
-        if first.is_whitespace() && first != '\n' {
-            s.insert(0, first);
+
+    #[test]
+    fn huge_table_spans_are_clamped() {
+        let state = MarkState::with_html(
+            "<table><tr>\
+            <td colspan=\"18446744073709551615\">a</td>\
+            <td colspan=\"99999999\" rowspan=\"99999999\">b</td>\
+            </tr><tr><td>c</td></tr></table>",
+        );
+        let _: iced::Element<'_, ()> = MarkWidget::new(&state).into();
+
+        let attrs = |value: &str| {
+            vec![html5ever::Attribute {
+                name: html5ever::QualName::new(
+                    None,
+                    html5ever::Namespace::from(""),
+                    "colspan".into(),
+                ),
+                value: value.into(),
+            }]
+        };
+        assert_eq!(
+            super::get_attr_span(
+                &attrs("18446744073709551615"),
+                "colspan",
+                super::MAX_COLSPAN
+            ),
+            1000
+        );
+        assert_eq!(
+            super::get_attr_span(&attrs("0"), "colspan", super::MAX_COLSPAN),
+            1
+        );
+        assert_eq!(
+            super::get_attr_span(&attrs("-3"), "colspan", super::MAX_COLSPAN),
+            1
+        );
+        assert_eq!(
+            super::get_attr_span(&attrs(" 4 "), "colspan", super::MAX_COLSPAN),
+            4
+        );
+    }
+
+    #[test]
+    fn custom_elements_render_children_once() {
+        let html = format!("{}text{}", "<div>".repeat(20), "</div>".repeat(20));
+        let state = MarkState::with_html(&html);
+
+        let calls = Cell::new(0);
+        let widget: MarkWidget<'_, ()> = MarkWidget::new(&state).on_rendering_element(|_| {
+            calls.set(calls.get() + 1);
+            None
+        });
+        let _: iced::Element<'_, ()> = widget.into();
+
+        // `<html>`, `<body>` and the 20 `<div>`s
+        assert_eq!(calls.get(), 22);
+    }
+
+    #[test]
+    fn class_applies_in_any_order() {
+        let state = MarkState::with_html("");
+        let class = || -> crate::StyleFn<'static, iced::Theme> {
+            Box::new(|theme| crate::Style {
+                link_color: Some(iced::Color::from_rgb8(0xFF, 0, 0)),
+                ..crate::Style::from(theme)
+            })
+        };
+
+        let before: MarkWidget<'_, ()> = MarkWidget::new(&state)
+            .class(class())
+            .theme(&iced::Theme::Dark);
+        let after: MarkWidget<'_, ()> = MarkWidget::new(&state)
+            .theme(&iced::Theme::Dark)
+            .class(class());
+        for widget in [before, after] {
+            assert_eq!(
+                widget.colors().link_color,
+                Some(iced::Color::from_rgb8(0xFF, 0, 0))
+            );
         }
     }
-    s
 }
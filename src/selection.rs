@@ -0,0 +1,1423 @@
+//! Document-wide text selection.
+//!
+//! Every run of text in the document is wrapped in a [`SelectableBlock`],
+//! which remembers the DOM nodes it came from. The [`SelectionArea`] at
+//! the root of the document finds these blocks (through a widget
+//! [`Operation`]) and lets the user select text by click-dragging,
+//! or with Ctrl+A. Ctrl+C copies the selection.
+//!
+//! A selection goes from a character in one block to a character
+//! in another. Blocks of rich text find which character is under
+//! the cursor themselves, and draw the part of them that's selected.
+
+use std::{collections::HashMap, ops::Range, rc::Rc, sync::Arc};
+
+use iced::{
+    advanced::{
+        clipboard, layout, mouse, overlay, renderer,
+        text::{self, Paragraph},
+        widget::{tree, Operation, Tree},
+        Clipboard, Layout, Shell, Widget,
+    },
+    keyboard,
+    widget::text::{LineHeight, Shaping, Span, Wrapping},
+    Color, Element, Event, Font, Length, Point, Rectangle, Size, Vector,
+};
+use markup5ever_rcdom::{Handle, NodeData};
+
+use crate::{
+    find::node_key,
+    ir::{clean_whitespace, is_block_element},
+    structs::{UpdateMsg, UpdateMsgKind},
+};
+
+/// The format to get selected text in,
+/// from [`crate::MarkState::selected_text`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextFormat {
+    /// Text without any formatting.
+    Plain,
+    /// Markdown, keeping things like bold text, links and headings.
+    Markdown,
+    /// HTML, keeping things like bold text, links and headings.
+    Html,
+}
+
+/// The currently selected text, in every format.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelectedText {
+    pub plain: String,
+    pub markdown: String,
+    pub html: String,
+}
+
+impl SelectedText {
+    pub fn get(&self, format: TextFormat) -> &str {
+        match format {
+            TextFormat::Plain => &self.plain,
+            TextFormat::Markdown => &self.markdown,
+            TextFormat::Html => &self.html,
+        }
+    }
+
+    fn push(&mut self, other: SelectedText) {
+        push_block(&mut self.plain, &other.plain, "\n");
+        push_block(&mut self.markdown, &other.markdown, "\n\n");
+        push_block(&mut self.html, &other.html, "\n");
+    }
+}
+
+/// Adds a block of text, with a separator if it's not the first one.
+fn push_block(text: &mut String, block: &str, separator: &str) {
+    if block.is_empty() {
+        return;
+    }
+    if !text.is_empty() {
+        text.push_str(separator);
+    }
+    text.push_str(block);
+}
+
+/// Copies what [`SelectableBlock::with_spans`] needs from spans:
+/// their text, and what changes its size.
+pub(crate) fn plain_spans<'a, L>(spans: &[Span<'a, L, Font>]) -> Vec<Span<'a, (), Font>> {
+    spans
+        .iter()
+        .map(|n| Span {
+            text: n.text.clone(),
+            size: n.size,
+            line_height: n.line_height,
+            font: n.font,
+            ..Span::new("")
+        })
+        .collect()
+}
+
+/// A run of text that can be selected.
+///
+/// Rich text (see [`SelectableBlock::with_spans`]) can be selected
+/// a character at a time, anything else (eg: code blocks) as a whole.
+pub struct SelectableBlock<'a, M, T, R> {
+    content: Element<'a, M, T, R>,
+    nodes: Rc<[Handle]>,
+    /// The spans of `content`, for finding where each character is.
+    spans: Option<Vec<Span<'a, (), Font>>>,
+}
+
+impl<'a, M, T, R> SelectableBlock<'a, M, T, R> {
+    pub fn new(content: impl Into<Element<'a, M, T, R>>, nodes: Vec<Handle>) -> Self {
+        Self {
+            content: content.into(),
+            nodes: nodes.into(),
+            spans: None,
+        }
+    }
+
+    /// Makes the block selectable a character at a time, if `content`
+    /// is a [`iced::widget::text::Rich`] made from these spans
+    /// (see [`plain_spans`]).
+    pub fn with_spans(mut self, spans: Vec<Span<'a, (), Font>>) -> Self {
+        self.spans = Some(spans);
+        self
+    }
+
+    /// Length of the text (in bytes), if it can
+    /// be selected a character at a time.
+    fn len(&self) -> Option<usize> {
+        self.spans
+            .as_ref()
+            .map(|spans| spans.iter().map(|n| n.text.len()).sum())
+    }
+}
+
+/// What a [`SelectableBlock`] passes to [`Operation::custom`].
+pub(crate) struct Block {
+    /// The DOM nodes the text came from.
+    pub nodes: Rc<[Handle]>,
+    /// See [`SelectableBlock::len`].
+    len: Option<usize>,
+    /// A point (relative to the block) to find the offset of,
+    /// set by the operation. The block answers with an [`Offset`].
+    probe: Option<Point>,
+    /// The selected part of the text, set by the operation.
+    selected: Option<Range<usize>>,
+    color: Color,
+}
+
+impl Block {
+    /// A block that [`crate::MarkWidget::virtualized`] hasn't built,
+    /// which can only be found and selected as a whole.
+    pub(crate) fn unbuilt(nodes: Rc<[Handle]>) -> Self {
+        Self {
+            nodes,
+            len: None,
+            probe: None,
+            selected: None,
+            color: Color::TRANSPARENT,
+        }
+    }
+}
+
+/// Where in its text a [`SelectableBlock`] found [`Block::probe`].
+struct Offset(usize);
+
+/// A line of text in a [`SelectableBlock`].
+struct Line<P> {
+    spans: Vec<Span<'static, (), Font>>,
+    paragraph: P,
+    top: f32,
+    height: f32,
+    /// Where the line starts in the text of the block.
+    start: usize,
+    len: usize,
+}
+
+#[derive(Default)]
+struct BlockState<P> {
+    /// The space the text was laid out in.
+    bounds: Size,
+    /// The text laid out a line at a time (`rich_text`
+    /// doesn't tell us which line a point is on),
+    /// only done once it's needed.
+    lines: Option<Vec<Line<P>>>,
+    selected: Option<Range<usize>>,
+    color: Color,
+    /// Where to draw the selection, relative to the block.
+    highlight: Vec<Rectangle>,
+}
+
+impl<M, T, R> SelectableBlock<'_, M, T, R>
+where
+    R: text::Renderer<Font = Font>,
+{
+    fn lines<'s>(
+        &self,
+        state: &'s mut BlockState<R::Paragraph>,
+        renderer: &R,
+    ) -> &'s [Line<R::Paragraph>] {
+        let bounds = state.bounds;
+        state.lines.get_or_insert_with(|| {
+            let mut top = 0.0;
+            split_lines(self.spans.as_deref().unwrap_or_default())
+                .into_iter()
+                .map(|(start, spans)| {
+                    let paragraph: R::Paragraph = paragraph(&spans, bounds, renderer);
+                    let height = paragraph.min_bounds().height;
+                    let line = Line {
+                        len: spans.iter().map(|n| n.text.len()).sum(),
+                        spans,
+                        paragraph,
+                        top,
+                        height,
+                        start,
+                    };
+                    top += height;
+                    line
+                })
+                .collect()
+        })
+    }
+
+    /// Finds the offset in the text closest to a point in the block.
+    fn offset_at(&self, state: &mut BlockState<R::Paragraph>, renderer: &R, point: Point) -> usize {
+        let lines = self.lines(state, renderer);
+        let Some(line) = lines
+            .iter()
+            .rev()
+            .find(|n| n.top <= point.y)
+            .or(lines.first())
+        else {
+            return 0;
+        };
+        if point.y < line.top {
+            return line.start;
+        }
+        if point.y >= line.top + line.height {
+            return line.start + line.len;
+        }
+        match line
+            .paragraph
+            .hit_test(Point::new(point.x, point.y - line.top))
+        {
+            Some(hit) => line.start + hit.cursor().min(line.len),
+            None if point.x <= 0.0 => line.start,
+            None => line.start + line.len,
+        }
+    }
+
+    /// Finds where to draw the selection.
+    fn highlight(
+        &self,
+        state: &mut BlockState<R::Paragraph>,
+        renderer: &R,
+        size: Size,
+    ) -> Vec<Rectangle> {
+        let Some(selected) = state.selected.clone() else {
+            return Vec::new();
+        };
+        let whole = self
+            .len()
+            .is_none_or(|len| selected.start == 0 && selected.end >= len);
+        if whole {
+            return vec![Rectangle::with_size(size)];
+        }
+
+        let bounds = state.bounds;
+        let mut highlight = Vec::new();
+        for line in self.lines(state, renderer) {
+            let end = line.start + line.len;
+            let range = selected.start.clamp(line.start, end) - line.start
+                ..selected.end.clamp(line.start, end) - line.start;
+            if range.is_empty() {
+                continue;
+            }
+            // Splitting the spans at the ends of the selection
+            // lets the paragraph tell us where that part of it is
+            let (spans, picked) = split_spans(&line.spans, range);
+            let paragraph: R::Paragraph = paragraph(&spans, bounds, renderer);
+            for i in picked {
+                highlight.extend(
+                    paragraph
+                        .span_bounds(i)
+                        .into_iter()
+                        .map(|n| n + Vector::new(0.0, line.top)),
+                );
+            }
+        }
+        highlight
+    }
+}
+
+/// Lays out spans like [`iced::widget::text::Rich`] does by default.
+fn paragraph<P: Paragraph<Font = Font>>(
+    spans: &[Span<'_, (), Font>],
+    bounds: Size,
+    renderer: &impl text::Renderer<Font = Font>,
+) -> P {
+    P::with_spans(iced::advanced::Text {
+        content: spans,
+        bounds,
+        size: renderer.default_size(),
+        line_height: LineHeight::default(),
+        font: renderer.default_font(),
+        align_x: text::Alignment::Default,
+        align_y: iced::alignment::Vertical::Top,
+        shaping: Shaping::Advanced,
+        wrapping: Wrapping::default(),
+    })
+}
+
+fn copy_span(span: &Span<'_, (), Font>, text: &str) -> Span<'static, (), Font> {
+    Span {
+        text: text.to_owned().into(),
+        size: span.size,
+        line_height: span.line_height,
+        font: span.font,
+        ..Span::new("")
+    }
+}
+
+/// Splits spans into lines (at `\n`), with where each line starts.
+fn split_lines(spans: &[Span<'_, (), Font>]) -> Vec<(usize, Vec<Span<'static, (), Font>>)> {
+    let mut lines = vec![(0, Vec::new())];
+    let mut offset = 0;
+    for span in spans {
+        for (i, piece) in span.text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push((offset, Vec::new()));
+            }
+            if !piece.is_empty() {
+                lines.last_mut().unwrap().1.push(copy_span(span, piece));
+            }
+            offset += piece.len() + 1;
+        }
+        // No `\n` after the last piece
+        offset -= 1;
+    }
+    lines
+}
+
+/// Splits spans at both ends of `range`,
+/// with which of the new spans are inside it.
+fn split_spans(
+    spans: &[Span<'static, (), Font>],
+    range: Range<usize>,
+) -> (Vec<Span<'static, (), Font>>, Range<usize>) {
+    let mut split = Vec::new();
+    let mut picked = 0..0;
+    let mut offset = 0;
+    for span in spans {
+        let text = &*span.text;
+        let start = range.start.clamp(offset, offset + text.len()) - offset;
+        let end = range.end.clamp(offset, offset + text.len()) - offset;
+        let start = floor_char_boundary(text, start);
+        let end = floor_char_boundary(text, end);
+
+        if start > 0 {
+            split.push(copy_span(span, &text[..start]));
+        }
+        if start < end {
+            if picked.is_empty() {
+                picked.start = split.len();
+            }
+            split.push(copy_span(span, &text[start..end]));
+            picked.end = split.len();
+        }
+        if end < text.len() {
+            split.push(copy_span(span, &text[end.max(start)..]));
+        }
+        offset += text.len();
+    }
+    (split, picked)
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+impl<M, T, R> Widget<M, T, R> for SelectableBlock<'_, M, T, R>
+where
+    R: text::Renderer<Font = Font>,
+    R::Paragraph: 'static,
+{
+    fn size(&self) -> Size<Length> {
+        self.content.as_widget().size()
+    }
+
+    fn size_hint(&self) -> Size<Length> {
+        self.content.as_widget().size_hint()
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<BlockState<R::Paragraph>>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(BlockState::<R::Paragraph>::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        // The text might have changed
+        tree.state.downcast_mut::<BlockState<R::Paragraph>>().lines = None;
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn layout(&mut self, tree: &mut Tree, renderer: &R, limits: &layout::Limits) -> layout::Node {
+        let node = self
+            .content
+            .as_widget_mut()
+            .layout(&mut tree.children[0], renderer, limits);
+
+        let state = tree.state.downcast_mut::<BlockState<R::Paragraph>>();
+        if state.bounds != limits.max() {
+            state.bounds = limits.max();
+            state.lines = None;
+        }
+        if state.selected.is_some() {
+            state.highlight = self.highlight(state, renderer, node.size());
+        }
+        node
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut R,
+        theme: &T,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout,
+            cursor,
+            viewport,
+        );
+
+        let state = tree.state.downcast_ref::<BlockState<R::Paragraph>>();
+        let position = layout.position() - Point::ORIGIN;
+        for bounds in &state.highlight {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: *bounds + position,
+                    ..renderer::Quad::default()
+                },
+                state.color,
+            );
+        }
+    }
+
+    fn operate(
+        &mut self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &R,
+        operation: &mut dyn Operation,
+    ) {
+        let bounds = layout.bounds();
+        let state = tree.state.downcast_mut::<BlockState<R::Paragraph>>();
+        let mut block = Block {
+            nodes: self.nodes.clone(),
+            len: self.len(),
+            probe: None,
+            selected: state.selected.clone(),
+            color: state.color,
+        };
+        operation.custom(None, bounds, &mut block);
+
+        if let (Some(point), Some(_)) = (block.probe, &self.spans) {
+            let offset = self.offset_at(state, renderer, point);
+            operation.custom(None, bounds, &mut Offset(offset));
+        }
+        if block.selected != state.selected || block.color != state.color {
+            state.selected = block.selected;
+            state.color = block.color;
+            state.highlight = self.highlight(state, renderer, bounds.size());
+        }
+
+        self.content
+            .as_widget_mut()
+            .operate(&mut tree.children[0], layout, renderer, operation);
+    }
+
+    fn update(
+        &mut self,
+        tree: &mut Tree,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &R,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, M>,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget_mut().update(
+            &mut tree.children[0],
+            event,
+            layout,
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        );
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &R,
+    ) -> mouse::Interaction {
+        let interaction = self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout,
+            cursor,
+            viewport,
+            renderer,
+        );
+        if interaction == mouse::Interaction::None && cursor.is_over(layout.bounds()) {
+            mouse::Interaction::Text
+        } else {
+            interaction
+        }
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'b>,
+        renderer: &R,
+        viewport: &Rectangle,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, M, T, R>> {
+        self.content.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout,
+            renderer,
+            viewport,
+            translation,
+        )
+    }
+}
+
+impl<'a, M: 'a, T: 'a, R> From<SelectableBlock<'a, M, T, R>> for Element<'a, M, T, R>
+where
+    R: text::Renderer<Font = Font> + 'a,
+    R::Paragraph: 'static,
+{
+    fn from(value: SelectableBlock<'a, M, T, R>) -> Self {
+        Element::new(value)
+    }
+}
+
+type FUpdate<M> = Arc<dyn Fn(UpdateMsg) -> M>;
+
+/// Wraps the whole document, handling selection of the
+/// [`SelectableBlock`]s inside it.
+pub struct SelectionArea<'a, M, T, R> {
+    content: Element<'a, M, T, R>,
+    fn_update: Option<FUpdate<M>>,
+    color: Color,
+    hidden: Arc<Hidden>,
+}
+
+/// How many bytes at the start of text nodes aren't shown
+/// (like the `[!KIND]` of alerts), by their [`node_key`].
+pub(crate) type Hidden = HashMap<usize, usize>;
+
+impl<'a, M, T, R> SelectionArea<'a, M, T, R> {
+    pub fn new(
+        content: impl Into<Element<'a, M, T, R>>,
+        fn_update: Option<FUpdate<M>>,
+        color: Option<Color>,
+    ) -> Self {
+        Self {
+            content: content.into(),
+            fn_update,
+            color: color.unwrap_or_else(|| Color::from_rgba8(0x3D, 0x7E, 0xFF, 0.3)),
+            hidden: Arc::default(),
+        }
+    }
+
+    /// Leaves out the start of text nodes that isn't shown, when copying.
+    pub fn with_hidden(mut self, hidden: Hidden) -> Self {
+        self.hidden = Arc::new(hidden);
+        self
+    }
+}
+
+/// How far the cursor has to move (in pixels)
+/// before a click turns into a selection.
+const DRAG_THRESHOLD: f32 = 4.0;
+
+/// A place in the document: the index of a block,
+/// and an offset in its text (see [`Block::len`]).
+type Position = (usize, usize);
+
+#[derive(Default)]
+struct State {
+    /// Where the drag started, and where it is now.
+    selection: Option<(Position, Position)>,
+    /// Where the drag started, if dragging.
+    origin: Option<Point>,
+    /// Whether the last click was on this document,
+    /// so keyboard shortcuts apply to it.
+    focused: bool,
+}
+
+impl State {
+    fn range(&self) -> Option<(Position, Position)> {
+        self.selection.map(|(a, b)| (a.min(b), a.max(b)))
+    }
+}
+
+/// Finds all [`SelectableBlock`]s, and optionally
+/// shows a selection on them or gets its text.
+#[derive(Default)]
+struct Collect {
+    blocks: Vec<Rectangle>,
+    /// See [`Block::len`].
+    lens: Vec<Option<usize>>,
+    /// A point to find the [`Position`] of.
+    probe: Option<Point>,
+    /// Offsets the blocks found for `probe`, by block.
+    hits: Vec<(usize, usize)>,
+    selection: Option<(Position, Position)>,
+    /// The color to show `selection` in (if it should be shown).
+    show: Option<Color>,
+    /// The text of `selection` (if it's wanted).
+    text: Option<SelectedText>,
+    hidden: Arc<Hidden>,
+}
+
+impl Collect {
+    /// Where `probe` is in the document.
+    fn position(&self, point: Point) -> Option<Position> {
+        let i = self
+            .blocks
+            .iter()
+            .position(|n| n.contains(point))
+            .or_else(|| self.blocks.iter().rposition(|n| n.y <= point.y))
+            .or_else(|| (!self.blocks.is_empty()).then_some(0))?;
+        let offset = match self.hits.iter().find(|n| n.0 == i) {
+            Some(&(_, offset)) => offset,
+            None if point.y < self.blocks[i].y => 0,
+            None => self.lens[i].unwrap_or(0),
+        };
+        Some((i, offset))
+    }
+}
+
+/// The part of a block's text that's in a selection (if any).
+/// Blocks that aren't selected a character at a time are all or nothing.
+fn selected_range(
+    (start, end): (Position, Position),
+    block: usize,
+    len: Option<usize>,
+) -> Option<Range<usize>> {
+    if block < start.0 || block > end.0 {
+        return None;
+    }
+    let Some(len) = len else {
+        return Some(0..0);
+    };
+    let range = if block == start.0 {
+        start.1.min(len)
+    } else {
+        0
+    }..if block == end.0 { end.1.min(len) } else { len };
+    (!range.is_empty()).then_some(range)
+}
+
+impl Operation for Collect {
+    fn traverse(&mut self, operate: &mut dyn FnMut(&mut dyn Operation)) {
+        operate(self);
+    }
+
+    fn custom(
+        &mut self,
+        _id: Option<&iced::advanced::widget::Id>,
+        bounds: Rectangle,
+        state: &mut dyn std::any::Any,
+    ) {
+        if let Some(Offset(offset)) = state.downcast_ref::<Offset>() {
+            self.hits.push((self.blocks.len() - 1, *offset));
+            return;
+        }
+        let Some(block) = state.downcast_mut::<Block>() else {
+            return;
+        };
+        let i = self.blocks.len();
+        self.blocks.push(bounds);
+        self.lens.push(block.len);
+
+        if let Some(point) = self.probe.filter(|n| bounds.contains(*n)) {
+            block.probe = Some(point - (bounds.position() - Point::ORIGIN));
+        }
+        let selected = self
+            .selection
+            .and_then(|selection| selected_range(selection, i, block.len));
+        if let (Some(text), Some(range)) = (&mut self.text, &selected) {
+            if block.len.is_some() {
+                text.push(block_text(&block.nodes, Some(range.clone()), &self.hidden));
+            } else {
+                // Blocks that weren't built can have several paragraphs
+                for nodes in block.nodes.chunk_by(same_block) {
+                    text.push(block_text(nodes, None, &self.hidden));
+                }
+            }
+        }
+        if let Some(color) = self.show {
+            block.selected = selected;
+            block.color = color;
+        }
+    }
+}
+
+impl<M, T, R: iced::advanced::Renderer> SelectionArea<'_, M, T, R> {
+    fn collect(
+        &mut self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &R,
+        mut collect: Collect,
+    ) -> Collect {
+        self.content.as_widget_mut().operate(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            &mut collect,
+        );
+        collect
+    }
+
+    /// Finds where a point is in the document.
+    fn position(
+        &mut self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &R,
+        point: Point,
+    ) -> Option<Position> {
+        let probe = Collect {
+            probe: Some(point),
+            ..Collect::default()
+        };
+        self.collect(tree, layout, renderer, probe).position(point)
+    }
+
+    /// Shows the current selection on the blocks.
+    fn show(&mut self, tree: &mut Tree, layout: Layout<'_>, renderer: &R) {
+        let show = Collect {
+            selection: tree.state.downcast_ref::<State>().range(),
+            show: Some(self.color),
+            ..Collect::default()
+        };
+        self.collect(tree, layout, renderer, show);
+    }
+
+    fn text(&mut self, tree: &mut Tree, layout: Layout<'_>, renderer: &R) -> Option<SelectedText> {
+        let selection = tree.state.downcast_ref::<State>().range()?;
+        let collect = Collect {
+            selection: Some(selection),
+            text: Some(SelectedText::default()),
+            hidden: self.hidden.clone(),
+            ..Collect::default()
+        };
+        self.collect(tree, layout, renderer, collect).text
+    }
+
+    fn publish(
+        &mut self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &R,
+        shell: &mut Shell<'_, M>,
+    ) {
+        let Some(update) = self.fn_update.clone() else {
+            return;
+        };
+        let text = self.text(tree, layout, renderer);
+        shell.publish(update(UpdateMsg {
+            kind: UpdateMsgKind::Selection(text),
+        }));
+    }
+}
+
+impl<M, T, R: iced::advanced::Renderer> Widget<M, T, R> for SelectionArea<'_, M, T, R> {
+    fn size(&self) -> Size<Length> {
+        self.content.as_widget().size()
+    }
+
+    fn size_hint(&self) -> Size<Length> {
+        self.content.as_widget().size_hint()
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        vec![Tree::new(&self.content)]
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(std::slice::from_ref(&self.content));
+    }
+
+    fn layout(&mut self, tree: &mut Tree, renderer: &R, limits: &layout::Limits) -> layout::Node {
+        let node = self
+            .content
+            .as_widget_mut()
+            .layout(&mut tree.children[0], renderer, limits);
+        layout::Node::with_children(node.size(), vec![node])
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut R,
+        theme: &T,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget().draw(
+            &tree.children[0],
+            renderer,
+            theme,
+            style,
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+        );
+    }
+
+    fn operate(
+        &mut self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &R,
+        operation: &mut dyn Operation,
+    ) {
+        self.content.as_widget_mut().operate(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            operation,
+        );
+    }
+
+    fn update(
+        &mut self,
+        tree: &mut Tree,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &R,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, M>,
+        viewport: &Rectangle,
+    ) {
+        self.content.as_widget_mut().update(
+            &mut tree.children[0],
+            event,
+            layout.children().next().unwrap(),
+            cursor,
+            renderer,
+            clipboard,
+            shell,
+            viewport,
+        );
+        let captured = shell.is_event_captured();
+
+        match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => {
+                let state = tree.state.downcast_mut::<State>();
+                let had_selection = state.selection.take().is_some();
+                let Some(position) = cursor.position_over(layout.bounds()) else {
+                    state.focused = false;
+                    state.origin = None;
+                    if had_selection {
+                        self.show(tree, layout, renderer);
+                        shell.request_redraw();
+                        self.publish(tree, layout, renderer, shell);
+                    }
+                    return;
+                };
+                state.focused = true;
+                if had_selection {
+                    self.show(tree, layout, renderer);
+                }
+                if !captured {
+                    let start = self.position(tree, layout, renderer, position);
+                    let state = tree.state.downcast_mut::<State>();
+                    state.origin = Some(position);
+                    state.selection = start.map(|n| (n, n));
+                }
+                shell.request_redraw();
+                if had_selection {
+                    self.publish(tree, layout, renderer, shell);
+                }
+            }
+            Event::Mouse(mouse::Event::CursorMoved { .. }) => {
+                let state = tree.state.downcast_ref::<State>();
+                let (Some(position), Some(_), Some((start, end))) =
+                    (cursor.position(), state.origin, state.selection)
+                else {
+                    return;
+                };
+                let Some(new_end) = self.position(tree, layout, renderer, position) else {
+                    return;
+                };
+                if new_end != end {
+                    tree.state.downcast_mut::<State>().selection = Some((start, new_end));
+                    self.show(tree, layout, renderer);
+                    shell.request_redraw();
+                }
+            }
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                let state = tree.state.downcast_mut::<State>();
+                let Some(origin) = state.origin.take() else {
+                    return;
+                };
+                // A click without dragging just clears the selection
+                let moved = cursor
+                    .position()
+                    .is_some_and(|n| n.distance(origin) > DRAG_THRESHOLD);
+                if !moved {
+                    state.selection = None;
+                    self.show(tree, layout, renderer);
+                    shell.request_redraw();
+                    return;
+                }
+                self.publish(tree, layout, renderer, shell);
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed {
+                key: keyboard::Key::Character(c),
+                modifiers,
+                ..
+            }) if modifiers.command() && !captured => {
+                let state = tree.state.downcast_ref::<State>();
+                if !state.focused {
+                    return;
+                }
+                match c.as_str() {
+                    "a" => {
+                        let lens = self
+                            .collect(tree, layout, renderer, Collect::default())
+                            .lens;
+                        tree.state.downcast_mut::<State>().selection = lens
+                            .last()
+                            .map(|len| ((0, 0), (lens.len() - 1, len.unwrap_or(0))));
+                        self.show(tree, layout, renderer);
+                        shell.capture_event();
+                        shell.request_redraw();
+                        self.publish(tree, layout, renderer, shell);
+                    }
+                    "c" => {
+                        let Some(text) = self.text(tree, layout, renderer) else {
+                            return;
+                        };
+                        clipboard.write(clipboard::Kind::Standard, text.plain);
+                        shell.capture_event();
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &R,
+    ) -> mouse::Interaction {
+        self.content.as_widget().mouse_interaction(
+            &tree.children[0],
+            layout.children().next().unwrap(),
+            cursor,
+            viewport,
+            renderer,
+        )
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'b>,
+        renderer: &R,
+        viewport: &Rectangle,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, M, T, R>> {
+        self.content.as_widget_mut().overlay(
+            &mut tree.children[0],
+            layout.children().next().unwrap(),
+            renderer,
+            viewport,
+            translation,
+        )
+    }
+}
+
+impl<'a, M: 'a, T: 'a, R: iced::advanced::Renderer + 'a> From<SelectionArea<'a, M, T, R>>
+    for Element<'a, M, T, R>
+{
+    fn from(value: SelectionArea<'a, M, T, R>) -> Self {
+        Element::new(value)
+    }
+}
+
+fn parent(node: &Handle) -> Option<Handle> {
+    let weak = node.parent.take();
+    let parent = weak.as_ref().and_then(std::rc::Weak::upgrade);
+    node.parent.set(weak);
+    parent
+}
+
+fn element_name(node: &Handle) -> Option<&str> {
+    if let NodeData::Element { name, .. } = &node.data {
+        Some(&name.local)
+    } else {
+        None
+    }
+}
+
+fn get_attr(node: &Handle, attr: &str) -> Option<String> {
+    let NodeData::Element { attrs, .. } = &node.data else {
+        return None;
+    };
+    attrs
+        .borrow()
+        .iter()
+        .find(|n| &*n.name.local == attr)
+        .map(|n| n.value.to_string())
+}
+
+/// The number of an `<li>` in an `<ol>` (going by `start`,
+/// `reversed` and `value` like the renderer), or `None` in other lists.
+fn list_number(li: &Handle) -> Option<i64> {
+    let list = parent(li)?;
+    if element_name(&list) != Some("ol") {
+        return None;
+    }
+    let items: Vec<Handle> = list
+        .children
+        .borrow()
+        .iter()
+        .filter(|n| element_name(n) == Some("li"))
+        .cloned()
+        .collect();
+    let count = i64::try_from(items.len()).unwrap_or(i64::MAX);
+    let reversed = get_attr(&list, "reversed").is_some();
+    let step = if reversed { -1 } else { 1 };
+
+    let mut number = get_attr(&list, "start")
+        .and_then(|n| n.trim().parse::<i64>().ok())
+        .unwrap_or(if reversed { count } else { 1 });
+    for item in items {
+        if let Some(value) = get_attr(&item, "value").and_then(|n| n.parse().ok()) {
+            number = value;
+        }
+        if std::rc::Rc::ptr_eq(&item, li) {
+            return Some(number);
+        }
+        number = number.saturating_add(step);
+    }
+    None
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The block element a text node is in.
+fn block_of(node: &Handle) -> Option<Handle> {
+    let mut current = parent(node);
+    while let Some(n) = current {
+        if is_block_element(&n) {
+            return Some(n);
+        }
+        current = parent(&n);
+    }
+    None
+}
+
+fn same_block(a: &Handle, b: &Handle) -> bool {
+    match (block_of(a), block_of(b)) {
+        (Some(a), Some(b)) => Rc::ptr_eq(&a, &b),
+        (a, b) => a.is_none() && b.is_none(),
+    }
+}
+
+/// Converts a run of text nodes to every [`TextFormat`],
+/// rebuilding inline formatting from their ancestors.
+fn block_text(nodes: &[Handle], range: Option<Range<usize>>, hidden: &Hidden) -> SelectedText {
+    let mut plain = String::new();
+    let mut markdown = String::new();
+    let mut html = String::new();
+    // Where the text of the current node starts in the block
+    let mut offset = 0;
+
+    let mut block = None;
+    let mut in_pre = false;
+    let mut in_quote = false;
+
+    for node in nodes {
+        let NodeData::Text { contents } = &node.data else {
+            continue;
+        };
+
+        // Inline elements this text is inside, innermost first
+        let mut inline = Vec::new();
+        let mut current = parent(node);
+        while let Some(n) = current {
+            if is_block_element(&n) {
+                if block.is_none() {
+                    block = Some(n.clone());
+                    let mut ancestor = Some(n.clone());
+                    while let Some(a) = ancestor {
+                        match element_name(&a) {
+                            Some("pre") => in_pre = true,
+                            Some("blockquote") => in_quote = true,
+                            _ => {}
+                        }
+                        ancestor = parent(&a);
+                    }
+                }
+                break;
+            }
+            current = parent(&n);
+            inline.push(n);
+        }
+
+        let contents = contents.borrow();
+        let text = if in_pre {
+            contents.to_string()
+        } else {
+            clean_whitespace(&contents)
+        };
+        let text = match hidden.get(&node_key(node)) {
+            Some(&skip) => text.get(skip..).unwrap_or_default().to_owned(),
+            None => text,
+        };
+        let len = text.len();
+        let text = match &range {
+            Some(range) => {
+                let start = range.start.clamp(offset, offset + len) - offset;
+                let end = range.end.clamp(offset, offset + len) - offset;
+                text[floor_char_boundary(&text, start)..floor_char_boundary(&text, end)].to_owned()
+            }
+            None => text,
+        };
+        offset += len;
+        if text.is_empty() {
+            continue;
+        }
+        plain.push_str(&text);
+
+        let mut md = text.clone();
+        let mut ht = escape_html(&text);
+        for n in &inline {
+            let NodeData::Element { name, attrs, .. } = &n.data else {
+                continue;
+            };
+            let name = &*name.local;
+            match name {
+                "b" | "strong" => md = format!("**{md}**"),
+                "i" | "em" => md = format!("*{md}*"),
+                "del" | "s" | "strike" => md = format!("~~{md}~~"),
+                "code" if !in_pre => md = format!("`{md}`"),
+                "a" => {
+                    if let Some(href) = attrs.borrow().iter().find(|n| &*n.name.local == "href") {
+                        md = format!("[{md}]({})", &*href.value);
+                    }
+                }
+                _ => {}
+            }
+            if matches!(
+                name,
+                "b" | "strong"
+                    | "i"
+                    | "em"
+                    | "u"
+                    | "del"
+                    | "s"
+                    | "strike"
+                    | "code"
+                    | "mark"
+                    | "sub"
+                    | "sup"
+                    | "kbd"
+            ) {
+                ht = format!("<{name}>{ht}</{name}>");
+            } else if name == "a" {
+                let href = attrs
+                    .borrow()
+                    .iter()
+                    .find(|n| &*n.name.local == "href")
+                    .map(|n| escape_html(&n.value).replace('"', "&quot;"))
+                    .unwrap_or_default();
+                ht = format!("<a href=\"{href}\">{ht}</a>");
+            }
+        }
+        markdown.push_str(&md);
+        html.push_str(&ht);
+    }
+
+    let plain = if in_pre {
+        plain
+    } else {
+        plain.trim().to_owned()
+    };
+    let markdown = if in_pre {
+        markdown
+    } else {
+        markdown.trim().to_owned()
+    };
+    let html = if in_pre { html } else { html.trim().to_owned() };
+    if markdown.is_empty() {
+        return SelectedText::default();
+    }
+    // Only add heading and list markers if the
+    // selection has the start of the block
+    let from_start = range.is_none_or(|n| n.start == 0);
+
+    let (mut markdown, html) = match block.as_ref().and_then(element_name) {
+        Some(h @ ("h1" | "h2" | "h3" | "h4" | "h5" | "h6")) if from_start => {
+            let level = usize::from(h.as_bytes()[1] - b'0');
+            (
+                format!("{} {markdown}", "#".repeat(level)),
+                format!("<{h}>{html}</{h}>"),
+            )
+        }
+        Some("li") if from_start => {
+            let marker = block
+                .as_ref()
+                .and_then(list_number)
+                .map_or_else(|| "-".to_owned(), |n| format!("{n}."));
+            (format!("{marker} {markdown}"), format!("<li>{html}</li>"))
+        }
+        _ if in_pre => (
+            format!("```\n{}\n```", markdown.trim_end_matches('\n')),
+            format!("<pre><code>{html}</code></pre>"),
+        ),
+        _ => (markdown, format!("<p>{html}</p>")),
+    };
+    if in_quote {
+        markdown = markdown
+            .lines()
+            .map(|n| format!("> {n}"))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+
+    SelectedText {
+        plain,
+        markdown,
+        html,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use html5ever::{tendril::TendrilSink, ParseOpts};
+    use markup5ever_rcdom::{Handle, NodeData, RcDom};
+
+    use iced::widget::text::Span;
+
+    use super::{block_text, selected_range, split_lines, split_spans, Hidden, SelectedText};
+
+    fn text_nodes(node: &Handle, out: &mut Vec<Handle>) {
+        if let NodeData::Text { contents } = &node.data {
+            if !contents.borrow().trim().is_empty() {
+                out.push(node.clone());
+            }
+        }
+        for child in &*node.children.borrow() {
+            text_nodes(child, out);
+        }
+    }
+
+    fn selected(html: &str) -> SelectedText {
+        let dom =
+            html5ever::parse_document(RcDom::default(), ParseOpts::default()).one(html.to_owned());
+        let mut nodes = Vec::new();
+        text_nodes(&dom.document, &mut nodes);
+
+        let mut text = SelectedText::default();
+        for node in nodes {
+            text.push(block_text(&[node], None, &Hidden::new()));
+        }
+        text
+    }
+
+    #[test]
+    fn ordered_list_numbers() {
+        let text = selected(r#"<ol start="3"><li>a</li><li value="10">b</li><li>c</li></ol>"#);
+        assert_eq!(text.markdown, "3. a\n\n10. b\n\n11. c");
+
+        let text = selected("<ol reversed><li>a</li><li>b</li></ol><ul><li>c</li></ul>");
+        assert_eq!(text.markdown, "2. a\n\n1. b\n\n- c");
+        assert_eq!(text.plain, "a\nb\nc");
+    }
+
+    #[test]
+    fn separators_per_format() {
+        let mut text = SelectedText::default();
+        text.push(SelectedText {
+            plain: String::new(),
+            markdown: "![image](a.png)".to_owned(),
+            html: "<p><img src=\"a.png\"></p>".to_owned(),
+        });
+        text.push(SelectedText {
+            plain: "b".to_owned(),
+            markdown: "b".to_owned(),
+            html: "<p>b</p>".to_owned(),
+        });
+        assert_eq!(text.plain, "b");
+        assert_eq!(text.markdown, "![image](a.png)\n\nb");
+        assert_eq!(text.html, "<p><img src=\"a.png\"></p>\n<p>b</p>");
+    }
+
+    fn texts(spans: &[Span<'_, (), iced::Font>]) -> Vec<String> {
+        spans.iter().map(|n| n.text.to_string()).collect()
+    }
+
+    #[test]
+    fn lines_keep_offsets() {
+        let spans = [Span::new("ab\nc"), Span::new("d\n"), Span::new("e")];
+        let lines: Vec<_> = split_lines(&spans)
+            .into_iter()
+            .map(|(start, spans)| (start, texts(&spans)))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                (0, vec!["ab".to_owned()]),
+                (3, vec!["c".to_owned(), "d".to_owned()]),
+                (6, vec!["e".to_owned()])
+            ]
+        );
+    }
+
+    #[test]
+    fn spans_split_at_selection() {
+        let spans = [Span::new("hello "), Span::new("wörld")];
+        let (split, picked) = split_spans(&spans, 3..9);
+        assert_eq!(texts(&split), ["hel", "lo ", "wö", "rld"]);
+        assert_eq!(picked, 1..3);
+
+        // Inside a multi-byte character, rounds down
+        let (split, picked) = split_spans(&spans, 0..8);
+        assert_eq!(texts(&split), ["hello ", "w", "örld"]);
+        assert_eq!(picked, 0..2);
+    }
+
+    #[test]
+    fn selected_parts_of_blocks() {
+        let selection = ((1, 3), (3, 2));
+        assert_eq!(selected_range(selection, 0, Some(10)), None);
+        assert_eq!(selected_range(selection, 1, Some(10)), Some(3..10));
+        assert_eq!(selected_range(selection, 1, Some(3)), None);
+        assert_eq!(selected_range(selection, 2, Some(10)), Some(0..10));
+        assert_eq!(selected_range(selection, 2, None), Some(0..0));
+        assert_eq!(selected_range(selection, 3, Some(10)), Some(0..2));
+        assert_eq!(selected_range(selection, 4, Some(10)), None);
+    }
+
+    #[test]
+    fn hidden_text() {
+        let dom = html5ever::parse_document(RcDom::default(), ParseOpts::default())
+            .one("<blockquote><p>[!NOTE]\nnoted</p></blockquote>".to_owned());
+        let mut nodes = Vec::new();
+        text_nodes(&dom.document, &mut nodes);
+
+        let hidden = Hidden::from([(crate::find::node_key(&nodes[0]), 8)]);
+        let text = block_text(&nodes, None, &hidden);
+        assert_eq!(text.plain, "noted");
+        assert_eq!(block_text(&nodes, Some(0..3), &hidden).plain, "not");
+    }
+
+    #[test]
+    fn partial_block_text() {
+        let dom = html5ever::parse_document(RcDom::default(), ParseOpts::default())
+            .one("<h2>one <b>two</b> three</h2>".to_owned());
+        let mut nodes = Vec::new();
+        text_nodes(&dom.document, &mut nodes);
+
+        let text = block_text(&nodes, Some(4..9), &Hidden::new());
+        assert_eq!(text.plain, "two t");
+        assert_eq!(text.markdown, "**two** t");
+        assert_eq!(text.html, "<p><b>two</b> t</p>");
+
+        let text = block_text(&nodes, Some(0..3), &Hidden::new());
+        assert_eq!(text.markdown, "## one");
+    }
+}
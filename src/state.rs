@@ -1,10 +1,21 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::Range,
+};
 
 use html5ever::{tendril::TendrilSink, ParseOpts};
-use iced::widget;
+use iced::{widget, Task};
 use markup5ever_rcdom::RcDom;
 
-use crate::structs::{UpdateMsg, UpdateMsgKind};
+use crate::{
+    css::Stylesheet,
+    find::{node_key, FindMatch, FindOptions, FindState},
+    form::{find_forms, Field, FieldValue, Forms},
+    ir::Ir,
+    selection::{SelectedText, TextFormat},
+    structs::{Heading, StableId, UpdateMsg, UpdateMsgKind},
+};
 
 /// The state of the document.
 ///
@@ -26,9 +37,29 @@ use crate::structs::{UpdateMsg, UpdateMsgKind};
 /// ```
 pub struct MarkState {
     pub(crate) dom: RcDom,
+    pub(crate) ir: Ir,
 
-    pub(crate) selection_state: HashMap<String, widget::text_editor::Content>,
-    pub(crate) dropdown_state: HashMap<usize, bool>,
+    pub(crate) source: SourceKind,
+    pub(crate) stylesheet: Stylesheet,
+    pub(crate) style_tags: bool,
+    pub(crate) autolinks: bool,
+
+    /// Code blocks and `<details>` elements,
+    /// by their [`node_key`].
+    pub(crate) stable_ids: HashMap<usize, StableId>,
+    pub(crate) selection_state: HashMap<StableId, widget::text_editor::Content>,
+    pub(crate) dropdown_state: HashMap<StableId, bool>,
+    /// Form fields are also in `stable_ids`.
+    pub(crate) forms: Forms,
+    pub(crate) form_state: HashMap<StableId, FieldValue>,
+
+    pub(crate) anchors: Vec<String>,
+    /// Checkboxes in list items, in document order.
+    pub(crate) tasks: Vec<TaskItem>,
+    /// Indices into `tasks`, by the [`node_key`] of the checkbox.
+    pub(crate) task_ids: HashMap<usize, usize>,
+    pub(crate) selection: Option<SelectedText>,
+    pub(crate) find: FindState,
 }
 
 impl MarkState {
@@ -48,21 +79,38 @@ impl MarkState {
             // Will not panic as reading from &[u8] cannot fail
             .unwrap();
 
-        let mut selection_state = HashMap::new();
-        let mut dropdown_state = HashMap::new();
-        let mut dropdown_counter = 0;
-        find_state(
-            &dom.document,
-            &mut selection_state,
-            &mut dropdown_state,
-            &mut dropdown_counter,
-            false,
-        );
+        let mut ids = StableIds::default();
+        find_state(&dom.document, &mut ids, false);
+
+        let mut anchors = Vec::new();
+        find_anchors(&dom.document, &mut anchors, &mut HashMap::new());
+
+        let mut tasks = Vec::new();
+        let mut task_ids = HashMap::new();
+        find_tasks(&dom.document, None, &mut tasks, &mut task_ids);
+
+        let mut forms = Forms::default();
+        find_forms(&dom.document, None, &mut ids, &mut forms, &task_ids);
+
+        let ir = Ir::new(&dom.document, &Stylesheet::default());
 
         Self {
             dom,
-            selection_state,
-            dropdown_state,
+            ir,
+            source: SourceKind::Html,
+            stylesheet: Stylesheet::default(),
+            style_tags: false,
+            autolinks: false,
+            stable_ids: ids.nodes,
+            selection_state: ids.selection_state,
+            dropdown_state: ids.dropdown_state,
+            forms,
+            form_state: ids.form_state,
+            anchors,
+            tasks,
+            task_ids,
+            selection: None,
+            find: FindState::default(),
         }
     }
 
@@ -76,6 +124,36 @@ impl MarkState {
     #[must_use]
     #[cfg(feature = "markdown")]
     pub fn with_html_and_markdown(input: &str) -> Self {
+        let mut state = Self::parse_markdown(input, true);
+        state.locate_tasks(input, None);
+        state
+    }
+
+    /// Processes documents containing **pure Markdown**,
+    /// filtering out any HTML content.
+    ///
+    /// Useful for things like messaging apps.
+    #[must_use]
+    #[cfg(feature = "markdown")]
+    pub fn with_markdown_only(input: &str) -> Self {
+        let mut out = Vec::new();
+        let mut out_cursor = std::io::Cursor::new(&mut out);
+        _ = comrak::html::escape(&mut out_cursor, input.as_bytes());
+        let escaped = String::from_utf8_lossy(&out);
+
+        let mut state = Self {
+            source: SourceKind::MarkdownOnly,
+            // comrak would link the escaped text (`&amp;`),
+            // so the links are found after parsing instead
+            ..Self::parse_markdown(&escaped, false)
+        };
+        state.autolink();
+        state.locate_tasks(&escaped, Some(input));
+        state
+    }
+
+    #[cfg(feature = "markdown")]
+    fn parse_markdown(input: &str, autolink: bool) -> Self {
         let html = comrak::markdown_to_html(
             input,
             &comrak::Options {
@@ -87,6 +165,15 @@ impl MarkState {
                     subscript: true,
                     underline: true,
                     table: true,
+                    footnotes: true,
+                    description_lists: true,
+                    alerts: true,
+                    math_dollars: cfg!(feature = "math"),
+                    math_code: cfg!(feature = "math"),
+                    autolink,
+                    wikilinks_title_after_pipe: cfg!(feature = "wikilinks"),
+                    #[cfg(feature = "shortcodes")]
+                    shortcodes: true,
                     ..Default::default()
                 },
                 parse: comrak::ParseOptions::default(),
@@ -94,39 +181,202 @@ impl MarkState {
                     // Our renderer doesn't have the
                     // vulnerabilities of a browser
                     unsafe_: true,
+                    // For finding task list items in the source
+                    sourcepos: true,
+                    // For `CodeBlockInfo::info_string`
+                    full_info_string: true,
                     ..Default::default()
                 },
             },
         );
 
-        Self::with_html(&html)
+        Self {
+            source: SourceKind::Markdown,
+            ..Self::with_html(&html)
+        }
     }
 
-    /// Processes documents containing **pure Markdown**,
-    /// filtering out any HTML content.
+    /// Finds the `[ ]` of every task list item in the Markdown
+    /// that was parsed, from the `data-sourcepos` of its `<li>`.
     ///
-    /// Useful for things like messaging apps.
-    #[must_use]
+    /// If `original` is given, `markdown` is it with HTML escaped
+    /// (see [`MarkState::with_markdown_only`]), and the ranges
+    /// are mapped back to it.
     #[cfg(feature = "markdown")]
-    pub fn with_markdown_only(input: &str) -> Self {
-        let mut out = Vec::new();
-        let mut out_cursor = std::io::Cursor::new(&mut out);
-        _ = comrak::html::escape(&mut out_cursor, input.as_bytes());
-        Self::with_html_and_markdown(&String::from_utf8_lossy(&out))
+    fn locate_tasks(&mut self, markdown: &str, original: Option<&str>) {
+        let lines: Vec<&str> = markdown.split('\n').collect();
+        let original_lines: Option<Vec<&str>> = original.map(|n| n.split('\n').collect());
+
+        for task in &mut self.tasks {
+            let Some((line, column)) = task.sourcepos else {
+                continue;
+            };
+            let Some(text) = line.checked_sub(1).and_then(|n| lines.get(n)) else {
+                continue;
+            };
+            let Some(open) = find_task_marker(text, column.saturating_sub(1)) else {
+                continue;
+            };
+
+            let (text, open) = match &original_lines {
+                Some(original_lines) => {
+                    let Some(text) = original_lines.get(line - 1) else {
+                        continue;
+                    };
+                    let Some(open) = unescaped_offset(text, open) else {
+                        continue;
+                    };
+                    (*text, open)
+                }
+                None => (*text, open),
+            };
+            let Some(close) = text[open..].find(']') else {
+                continue;
+            };
+
+            let line_start: usize = original_lines
+                .as_ref()
+                .map_or(&lines, |n| n)
+                .iter()
+                .take(line - 1)
+                .map(|n| n.len() + 1)
+                .sum();
+            task.source_range = Some(line_start + open..line_start + open + close + 1);
+        }
+    }
+
+    /// Styles the document with a [`Stylesheet`].
+    ///
+    /// ```no_run
+    /// # use frostmark::{MarkState, Stylesheet};
+    /// # const YOUR_TEXT: &str = "";
+    /// # fn e() { let m =
+    /// MarkState::with_html_and_markdown(YOUR_TEXT)
+    ///     .with_stylesheet(Stylesheet::parse("h1 { color: #3d7eff; } .warning { color: red; }"))
+    /// # ; }
+    /// ```
+    ///
+    /// The stylesheet is kept by [`MarkState::reparse`].
+    #[must_use]
+    pub fn with_stylesheet(mut self, stylesheet: Stylesheet) -> Self {
+        self.stylesheet = stylesheet;
+        self.restyle();
+        self
+    }
+
+    /// Whether to use the `<style>` elements in the document
+    /// (on top of [`MarkState::with_stylesheet`]).
+    ///
+    /// Off by default. Only turn this on for documents you trust,
+    /// since they can use it to hide or restyle any part of themselves
+    /// (eg: to make a link look like something else).
+    #[must_use]
+    pub fn with_style_tags(mut self, enabled: bool) -> Self {
+        self.style_tags = enabled;
+        self.restyle();
+        self
+    }
+
+    /// Turns bare URLs (`https://..`, `www...`) and e-mail addresses
+    /// in the text into links, like comrak's `autolink` extension.
+    ///
+    /// Off by default for [`MarkState::with_html`]. Markdown is
+    /// already autolinked, so there this only adds links
+    /// inside raw HTML.
+    ///
+    /// This is kept by [`MarkState::reparse`].
+    #[must_use]
+    pub fn with_autolinks(mut self) -> Self {
+        self.autolinks = true;
+        self.autolink();
+        self
+    }
+
+    fn autolink(&mut self) {
+        crate::linkify::linkify(&mut self.dom);
+        self.restyle();
+    }
+
+    /// Rebuilds the [`Ir`] after the stylesheet changed.
+    fn restyle(&mut self) {
+        let mut stylesheet = self.stylesheet.clone();
+        if self.style_tags {
+            let mut css = String::new();
+            find_style_tags(&self.dom.document, &mut css);
+            stylesheet.extend(Stylesheet::parse(&css));
+        }
+        self.ir = Ir::new(&self.dom.document, &stylesheet);
+    }
+
+    /// Replaces the document with a new version of it,
+    /// keeping the state of things that didn't change.
+    ///
+    /// Use this instead of creating a new [`MarkState`]
+    /// when the document is being edited (eg: in a live preview),
+    /// so that open dropdowns (`<details>`), text selection in
+    /// code blocks, form fields and search results
+    /// (see [`MarkState::find`]) carry over.
+    ///
+    /// The new source is parsed the same way as the old one
+    /// (eg: with Markdown if this was made with
+    /// [`MarkState::with_html_and_markdown`]).
+    ///
+    /// Code blocks are matched by their contents, dropdowns by
+    /// the text of their `<summary>` and form fields by their name.
+    /// If there are several with the same contents,
+    /// they're matched in order.
+    pub fn reparse(&mut self, source: &str) {
+        let mut new = match self.source {
+            SourceKind::Html => Self::with_html(source),
+            #[cfg(feature = "markdown")]
+            SourceKind::Markdown => Self::with_html_and_markdown(source),
+            #[cfg(feature = "markdown")]
+            SourceKind::MarkdownOnly => Self::with_markdown_only(source),
+        };
+
+        if self.autolinks {
+            new.autolinks = true;
+            new.autolink();
+        }
+        if self.style_tags || !self.stylesheet.is_empty() {
+            new.stylesheet = std::mem::take(&mut self.stylesheet);
+            new.style_tags = self.style_tags;
+            new.restyle();
+        }
+
+        for (id, content) in self.selection_state.drain() {
+            if let Some(n) = new.selection_state.get_mut(&id) {
+                *n = content;
+            }
+        }
+        for (id, open) in self.dropdown_state.drain() {
+            if let Some(n) = new.dropdown_state.get_mut(&id) {
+                *n = open;
+            }
+        }
+        for (id, value) in self.form_state.drain() {
+            if let Some(n) = new.form_state.get_mut(&id) {
+                *n = value;
+            }
+        }
+        new.find = self.find.refresh(&new.ir);
+        new.selection = self.selection.take();
+
+        *self = new;
     }
 
     /// Updates the internal state of the document.
     ///
     /// Call this method after receiving an update message
     /// from [`crate::MarkWidget::on_updating_state`].
-    /// It currently handles the update of text selection
-    /// within code blocks, but additional use cases may be
-    /// supported in the future.
+    /// It currently handles text selection (both in the document
+    /// and within code blocks) and toggling of dropdowns,
+    /// but additional use cases may be supported in the future.
     pub fn update(&mut self, action: UpdateMsg) {
         match action.kind {
-            UpdateMsgKind::TextEditor(code, action) => {
+            UpdateMsgKind::TextEditor(id, action) => {
                 if !action.is_edit() {
-                    if let Some(n) = self.selection_state.get_mut(&code) {
+                    if let Some(n) = self.selection_state.get_mut(&id) {
                         n.perform(action);
                     }
                 }
@@ -134,9 +384,140 @@ impl MarkState {
             UpdateMsgKind::DetailsToggle(id, action) => {
                 self.dropdown_state.insert(id, action);
             }
+            UpdateMsgKind::Selection(selection) => {
+                self.selection = selection;
+            }
+            UpdateMsgKind::FieldInput(id, text) => {
+                self.form_state.insert(id, FieldValue::Text(text));
+            }
+            UpdateMsgKind::FieldChecked(id, checked) => {
+                if checked {
+                    for other in self.forms.radio_group(id) {
+                        self.form_state.insert(other, FieldValue::Checked(false));
+                    }
+                }
+                self.form_state.insert(id, FieldValue::Checked(checked));
+            }
+            UpdateMsgKind::FieldSelect(id, option) => {
+                self.form_state
+                    .insert(id, FieldValue::Selected(Some(option)));
+            }
+            UpdateMsgKind::FieldEdit(id, action) => {
+                if let Some(FieldValue::TextArea(n)) = self.form_state.get_mut(&id) {
+                    n.perform(action);
+                }
+            }
+            UpdateMsgKind::FormReset(form) => {
+                let Some(form) = self.forms.forms.get(form) else {
+                    return;
+                };
+                for id in &form.fields {
+                    if let Some(value) = self.forms.fields.get(id).and_then(Field::initial) {
+                        self.form_state.insert(*id, value);
+                    }
+                }
+            }
         }
     }
 
+    /// Returns the text currently selected in the document
+    /// (see [`crate::MarkWidget::selectable`]), if any.
+    ///
+    /// [`TextFormat::Markdown`] and [`TextFormat::Html`] keep
+    /// basic formatting like bold text, links, headings,
+    /// list items and code blocks.
+    ///
+    /// This is only kept up to date if you handle
+    /// [`crate::MarkWidget::on_updating_state`].
+    #[must_use]
+    pub fn selected_text(&self, format: TextFormat) -> Option<&str> {
+        self.selection.as_ref().map(|n| n.get(format))
+    }
+
+    /// Searches the text of the document, returning all matches.
+    ///
+    /// The matches are highlighted in [`crate::MarkWidget`]
+    /// (see [`crate::Style::find_match_color`]) until you call
+    /// this again or [`MarkState::clear_find`]. The first match
+    /// becomes the current one. Use [`MarkState::next_match`] and
+    /// [`MarkState::previous_match`] to move between them.
+    ///
+    /// Matches can span across formatting (eg: `foo bar` in `foo **bar**`),
+    /// but not across paragraphs.
+    ///
+    /// > **Note**: Matches inside code blocks can't be highlighted
+    /// > when [`crate::MarkWidget::on_updating_state`] is set,
+    /// > but can still be scrolled to.
+    pub fn find(&mut self, query: &str, options: FindOptions) -> &[FindMatch] {
+        self.find = FindState::new(&self.ir, query, options);
+        &self.find.matches
+    }
+
+    /// Stops highlighting the matches from [`MarkState::find`].
+    pub fn clear_find(&mut self) {
+        self.find = FindState::default();
+    }
+
+    /// Returns the index of the current match
+    /// from [`MarkState::find`], if any.
+    #[must_use]
+    pub fn current_match(&self) -> Option<usize> {
+        self.find.current
+    }
+
+    /// Moves to the next match from [`MarkState::find`]
+    /// (wrapping around at the end), returning its index.
+    pub fn next_match(&mut self) -> Option<usize> {
+        let len = self.find.matches.len();
+        self.find.current = self.find.current.map(|n| (n + 1) % len);
+        self.find.current
+    }
+
+    /// Moves to the previous match from [`MarkState::find`]
+    /// (wrapping around at the start), returning its index.
+    pub fn previous_match(&mut self) -> Option<usize> {
+        let len = self.find.matches.len();
+        self.find.current = self.find.current.map(|n| (n + len - 1) % len);
+        self.find.current
+    }
+
+    /// Scrolls the [`iced::widget::Scrollable`] with the given id
+    /// so that the current match from [`MarkState::find`] is visible.
+    ///
+    /// ```no_run
+    /// # use frostmark::{FindOptions, MarkState};
+    /// # use iced::Task;
+    /// # enum Message { Search(String), Next }
+    /// # struct App { state: MarkState }
+    /// const SCROLL_ID: &str = "docs";
+    ///
+    /// impl App {
+    ///     fn update(&mut self, msg: Message) -> Task<Message> {
+    ///         match msg {
+    ///             Message::Search(query) => {
+    ///                 self.state.find(&query, FindOptions::default());
+    ///             }
+    ///             Message::Next => {
+    ///                 self.state.next_match();
+    ///             }
+    ///         }
+    ///         self.state.scroll_to_match(SCROLL_ID)
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// Does nothing if the match is already visible,
+    /// or if there is no current match.
+    pub fn scroll_to_match<M: Send + 'static>(&self, scrollable: impl Into<widget::Id>) -> Task<M> {
+        self.find
+            .current
+            .and_then(|n| self.find.matches.get(n))
+            .and_then(|n| n.pieces.first())
+            .map_or_else(Task::none, |(node, _)| {
+                crate::find::scroll_to_node(scrollable, *node)
+            })
+    }
+
     /// Retrieves all image URLs that need to be loaded, returned as a [`HashSet<String>`].
     ///
     /// This method gathers all image URLs in the document, which you can:
@@ -150,6 +531,68 @@ impl MarkState {
         find_image_links(&self.dom.document, &mut storage);
         storage
     }
+
+    /// Retrieves all emoji in the document (eg: `🎉`),
+    /// for loading the images you return in [`crate::MarkWidget::on_drawing_emoji`].
+    ///
+    /// Emoji in code are left out, as they aren't drawn as images.
+    #[must_use]
+    pub fn find_emojis(&self) -> HashSet<String> {
+        let mut storage = HashSet::new();
+        find_emojis(&self.dom.document, &mut storage);
+        storage
+    }
+
+    /// Returns the anchors of all headings in the document, in order.
+    ///
+    /// Headings keep their `id` attribute if they have one,
+    /// otherwise they get a GitHub-style slug of their text
+    /// (eg: `## Getting Started!` becomes `getting-started`).
+    /// Duplicates get a numbered suffix (`-1`, `-2`, ...).
+    ///
+    /// Links to these (eg: `[see below](#getting-started)`) are sent to
+    /// [`crate::MarkWidget::on_jumping_to_anchor`].
+    #[must_use]
+    pub fn anchors(&self) -> &[String] {
+        &self.anchors
+    }
+
+    /// Returns how many task list items (`- [x] item` in Markdown)
+    /// are checked, and how many there are in total,
+    /// as `(done, total)`.
+    ///
+    /// See [`crate::MarkWidget::on_toggling_task`]
+    /// for making them clickable.
+    #[must_use]
+    pub fn task_progress(&self) -> (usize, usize) {
+        let done = self.tasks.iter().filter(|n| n.checked).count();
+        (done, self.tasks.len())
+    }
+
+    /// Returns a tree of all headings in the document,
+    /// for building a table of contents.
+    ///
+    /// Headings are nested under the closest previous
+    /// heading of a higher level (eg: `<h3>` under `<h2>`).
+    /// See [`crate::TocWidget`] for a ready-made widget to render this.
+    #[must_use]
+    pub fn outline(&self) -> Vec<Heading> {
+        let mut headings = Vec::new();
+        find_headings(&self.dom.document, &mut headings);
+
+        // Build the tree from the back, so every heading
+        // can take the ones that follow it as children.
+        let mut roots: Vec<Heading> = Vec::new();
+        for mut heading in headings.into_iter().rev() {
+            let split = roots
+                .iter()
+                .position(|n| n.level <= heading.level)
+                .unwrap_or(roots.len());
+            heading.children = roots.drain(..split).collect();
+            roots.insert(0, heading);
+        }
+        roots
+    }
 }
 
 impl Default for MarkState {
@@ -158,58 +601,91 @@ impl Default for MarkState {
     }
 }
 
-fn find_state(
-    node: &markup5ever_rcdom::Node,
-    selection_state: &mut HashMap<String, widget::text_editor::Content>,
-    dropdown_state: &mut HashMap<usize, bool>,
-    dropdown_counter: &mut usize,
-    scan_text: bool,
-) {
+/// How the document was parsed, for [`MarkState::reparse`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum SourceKind {
+    Html,
+    #[cfg(feature = "markdown")]
+    Markdown,
+    #[cfg(feature = "markdown")]
+    MarkdownOnly,
+}
+
+/// A checkbox in a list item, see [`MarkState::task_progress`].
+pub(crate) struct TaskItem {
+    pub checked: bool,
+    /// Line and column of the `<li>` it's in,
+    /// from the `data-sourcepos` that comrak adds.
+    #[cfg_attr(not(feature = "markdown"), allow(dead_code))]
+    pub sourcepos: Option<(usize, usize)>,
+    /// See [`crate::TaskToggle::source_range`].
+    pub source_range: Option<Range<usize>>,
+}
+
+#[derive(Default)]
+pub(crate) struct StableIds {
+    nodes: HashMap<usize, StableId>,
+    selection_state: HashMap<StableId, widget::text_editor::Content>,
+    dropdown_state: HashMap<StableId, bool>,
+    pub form_state: HashMap<StableId, FieldValue>,
+    /// How many times every (kind, content) pair was seen so far
+    seen: HashMap<u64, usize>,
+}
+
+impl StableIds {
+    pub fn make(&mut self, node: &markup5ever_rcdom::Node, kind: &str, content: &str) -> StableId {
+        let mut hasher = DefaultHasher::new();
+        (kind, content).hash(&mut hasher);
+        let base = hasher.finish();
+
+        let occurrence = self.seen.entry(base).or_default();
+        let mut hasher = DefaultHasher::new();
+        (base, *occurrence).hash(&mut hasher);
+        *occurrence += 1;
+
+        let id = StableId(hasher.finish());
+        self.nodes.insert(node_key(node), id);
+        id
+    }
+}
+
+fn find_state(node: &markup5ever_rcdom::Node, ids: &mut StableIds, scan_text: bool) {
     let borrow = node.children.borrow();
     match &node.data {
         markup5ever_rcdom::NodeData::Element { name, .. } if &name.local == "code" => {
             for child in &*borrow {
-                find_state(
-                    child,
-                    selection_state,
-                    dropdown_state,
-                    dropdown_counter,
-                    true,
-                );
+                find_state(child, ids, true);
             }
         }
         markup5ever_rcdom::NodeData::Element { name, .. } if &name.local == "details" => {
-            dropdown_state.insert(*dropdown_counter, false);
-            *dropdown_counter += 1;
+            let mut summary = String::new();
+            if let Some(n) = borrow.iter().find(|n| is_summary(n)) {
+                get_text(n, &mut summary);
+            }
+            let id = ids.make(node, "details", &summary);
+            ids.dropdown_state.insert(id, false);
             for child in &*borrow {
-                find_state(
-                    child,
-                    selection_state,
-                    dropdown_state,
-                    dropdown_counter,
-                    false,
-                );
+                find_state(child, ids, false);
             }
         }
         markup5ever_rcdom::NodeData::Text { contents } if scan_text => {
-            let contents = contents.borrow().to_string();
+            let contents = contents.borrow();
+            let id = ids.make(node, "code", &contents);
             let v = widget::text_editor::Content::with_text(&contents);
-            selection_state.insert(contents.clone(), v);
+            ids.selection_state.insert(id, v);
         }
         _ => {
             for child in &*borrow {
-                find_state(
-                    child,
-                    selection_state,
-                    dropdown_state,
-                    dropdown_counter,
-                    scan_text,
-                );
+                find_state(child, ids, scan_text);
             }
         }
     }
 }
 
+fn is_summary(node: &markup5ever_rcdom::Node) -> bool {
+    matches!(&node.data, markup5ever_rcdom::NodeData::Element { name, .. } if &name.local == "summary")
+}
+
 fn find_image_links(node: &markup5ever_rcdom::Node, storage: &mut HashSet<String>) {
     let borrow = node.children.borrow();
     match &node.data {
@@ -229,3 +705,331 @@ fn find_image_links(node: &markup5ever_rcdom::Node, storage: &mut HashSet<String
         }
     }
 }
+
+fn find_emojis(node: &markup5ever_rcdom::Node, storage: &mut HashSet<String>) {
+    match &node.data {
+        markup5ever_rcdom::NodeData::Element { name, .. }
+            if matches!(&*name.local, "code" | "pre" | "script" | "style") => {}
+        markup5ever_rcdom::NodeData::Text { contents } => {
+            for (piece, is_emoji) in crate::emoji::split_emoji(&contents.borrow()) {
+                if is_emoji {
+                    storage.insert(piece.to_owned());
+                }
+            }
+        }
+        _ => {
+            for child in &*node.children.borrow() {
+                find_emojis(child, storage);
+            }
+        }
+    }
+}
+
+/// `li` is `Some` inside list items, with the
+/// `data-sourcepos` of the closest one (if any).
+fn find_tasks(
+    node: &markup5ever_rcdom::Node,
+    li: Option<Option<(usize, usize)>>,
+    tasks: &mut Vec<TaskItem>,
+    ids: &mut HashMap<usize, usize>,
+) {
+    let mut li = li;
+    if let markup5ever_rcdom::NodeData::Element { name, attrs, .. } = &node.data {
+        let attrs = attrs.borrow();
+        let attr = |n: &str| attrs.iter().find(|a| &*a.name.local == n);
+        match &*name.local {
+            // Checkboxes in forms are fields, not tasks
+            "form" => return,
+            "li" => li = Some(attr("data-sourcepos").and_then(|n| parse_sourcepos(&n.value))),
+            "input" => {
+                let is_checkbox =
+                    attr("type").is_some_and(|n| n.value.eq_ignore_ascii_case("checkbox"));
+                if let (true, Some(sourcepos)) = (is_checkbox, li) {
+                    ids.insert(node_key(node), tasks.len());
+                    tasks.push(TaskItem {
+                        checked: attr("checked").is_some(),
+                        sourcepos,
+                        source_range: None,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    for child in &*node.children.borrow() {
+        find_tasks(child, li, tasks, ids);
+    }
+}
+
+/// Parses the start of a `data-sourcepos`
+/// (eg: `3:1-3:9` is line 3, column 1).
+fn parse_sourcepos(value: &str) -> Option<(usize, usize)> {
+    let (start, _) = value.split_once('-')?;
+    let (line, column) = start.split_once(':')?;
+    Some((line.parse().ok()?, column.parse().ok()?))
+}
+
+/// Finds the `[` of a task list item on its line,
+/// starting from the list marker (`-`, `*`, `+`, `1.` or `1)`).
+#[cfg(feature = "markdown")]
+fn find_task_marker(line: &str, from: usize) -> Option<usize> {
+    let rest = line.get(from..)?;
+    let open = rest.find('[')?;
+    // comrak also takes `\[x]` as a task
+    let before = rest[..open].strip_suffix('\\').unwrap_or(&rest[..open]);
+    let is_marker = before
+        .chars()
+        .all(|c| c.is_ascii_digit() || c.is_whitespace() || "-*+.)".contains(c));
+    let is_task = rest[open..].chars().nth(2) == Some(']');
+    (is_marker && is_task).then_some(from + open)
+}
+
+/// Turns an offset in `line` with HTML escaped
+/// into one in `line` itself.
+#[cfg(feature = "markdown")]
+fn unescaped_offset(line: &str, escaped: usize) -> Option<usize> {
+    let mut position = 0;
+    for (i, c) in line.char_indices() {
+        if position == escaped {
+            return Some(i);
+        }
+        let mut out = Vec::new();
+        _ = comrak::html::escape(&mut out, c.encode_utf8(&mut [0; 4]).as_bytes());
+        position += out.len();
+    }
+    (position == escaped).then_some(line.len())
+}
+
+fn find_style_tags(node: &markup5ever_rcdom::Node, css: &mut String) {
+    match &node.data {
+        markup5ever_rcdom::NodeData::Element { name, .. } if &name.local == "style" => {
+            get_text(node, css);
+            css.push('\n');
+        }
+        _ => {
+            for child in &*node.children.borrow() {
+                find_style_tags(child, css);
+            }
+        }
+    }
+}
+
+/// `taken` has every anchor so far, with the
+/// number to try next when it's taken again.
+fn find_anchors(
+    node: &markup5ever_rcdom::Node,
+    anchors: &mut Vec<String>,
+    taken: &mut HashMap<String, usize>,
+) {
+    let markup5ever_rcdom::NodeData::Element { name, attrs, .. } = &node.data else {
+        for child in &*node.children.borrow() {
+            find_anchors(child, anchors, taken);
+        }
+        return;
+    };
+    if heading_level(&name.local).is_none() {
+        for child in &*node.children.borrow() {
+            find_anchors(child, anchors, taken);
+        }
+        return;
+    }
+
+    let mut attrs = attrs.borrow_mut();
+    if let Some(id) = attrs.iter().find(|n| &*n.name.local == "id") {
+        let id = id.value.to_string();
+        taken.entry(id.clone()).or_insert(1);
+        anchors.push(id);
+        return;
+    }
+
+    let mut text = String::new();
+    get_text(node, &mut text);
+    let slug = slugify(&text);
+
+    let mut anchor = slug.clone();
+    if let Some(next) = taken.get_mut(&slug) {
+        let mut i = *next;
+        loop {
+            anchor = format!("{slug}-{i}");
+            i += 1;
+            if !taken.contains_key(&anchor) {
+                break;
+            }
+        }
+        taken.insert(slug, i);
+    }
+    taken.insert(anchor.clone(), 1);
+
+    attrs.push(html5ever::Attribute {
+        name: html5ever::QualName::new(None, html5ever::Namespace::from(""), "id".into()),
+        value: anchor.as_str().into(),
+    });
+    anchors.push(anchor);
+}
+
+pub(crate) fn get_text(node: &markup5ever_rcdom::Node, out: &mut String) {
+    if let markup5ever_rcdom::NodeData::Text { contents } = &node.data {
+        out.push_str(&contents.borrow());
+    }
+    for child in &*node.children.borrow() {
+        get_text(child, out);
+    }
+}
+
+/// Turns heading text into an anchor the way GitHub does:
+/// lowercase, punctuation removed, spaces replaced with `-`.
+fn slugify(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter_map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' {
+                Some(c.to_lowercase().next().unwrap_or(c))
+            } else if c.is_whitespace() {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+fn find_headings(node: &markup5ever_rcdom::Node, headings: &mut Vec<Heading>) {
+    if let markup5ever_rcdom::NodeData::Element { name, attrs, .. } = &node.data {
+        if let Some(level) = heading_level(&name.local) {
+            let attrs = attrs.borrow();
+            let anchor = attrs
+                .iter()
+                .find(|n| &*n.name.local == "id")
+                .map(|n| n.value.to_string())
+                .unwrap_or_default();
+
+            let mut text = String::new();
+            get_text(node, &mut text);
+
+            headings.push(Heading {
+                level,
+                text: text.split_whitespace().collect::<Vec<_>>().join(" "),
+                anchor,
+                children: Vec::new(),
+            });
+            return;
+        }
+    }
+    for child in &*node.children.borrow() {
+        find_headings(child, headings);
+    }
+}
+
+fn heading_level(name: &str) -> Option<u8> {
+    match name {
+        "h1" => Some(1),
+        "h2" => Some(2),
+        "h3" => Some(3),
+        "h4" => Some(4),
+        "h5" => Some(5),
+        "h6" => Some(6),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{slugify, MarkState};
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify(" Hello, World! "), "hello-world");
+        assert_eq!(slugify("C++ & Rust_2024"), "c--rust_2024");
+        assert_eq!(slugify("Über-Straße"), "über-straße");
+    }
+
+    #[test]
+    fn duplicate_slugs() {
+        let state = MarkState::with_html(
+            "<h1>Intro</h1><h2>Intro</h2><h2 id=\"intro-2\">Custom</h2><h3>Intro!</h3>",
+        );
+        assert_eq!(state.anchors(), ["intro", "intro-1", "intro-2", "intro-3"]);
+
+        let state = MarkState::with_html("<h1>A</h1><h1>A 1</h1><h1>A</h1><h1>A</h1>");
+        assert_eq!(state.anchors(), ["a", "a-1", "a-2", "a-3"]);
+    }
+
+    /// Ids of code blocks, in document order.
+    fn code_ids(state: &MarkState) -> Vec<super::StableId> {
+        fn walk(state: &MarkState, node: &markup5ever_rcdom::Node, ids: &mut Vec<super::StableId>) {
+            if let Some(id) = state.stable_ids.get(&super::node_key(node)) {
+                if state.selection_state.contains_key(id) {
+                    ids.push(*id);
+                }
+            }
+            for child in &*node.children.borrow() {
+                walk(state, child, ids);
+            }
+        }
+        let mut ids = Vec::new();
+        walk(state, &state.dom.document, &mut ids);
+        ids
+    }
+
+    #[test]
+    fn stable_ids_across_reparse() {
+        let html = "<pre><code>same</code></pre><pre><code>same</code></pre>\
+                    <details><summary>More</summary>hidden</details>";
+        let mut state = MarkState::with_html(html);
+        let ids = code_ids(&state);
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+
+        // Something only the second block has, like a selection
+        state.selection_state.insert(
+            ids[1],
+            iced::widget::text_editor::Content::with_text("second"),
+        );
+        for open in state.dropdown_state.values_mut() {
+            *open = true;
+        }
+
+        state.reparse(&format!(
+            "<p>Added before</p>{html}<pre><code>same</code></pre>"
+        ));
+        let new_ids = code_ids(&state);
+        assert_eq!(new_ids[..2], ids);
+        assert_eq!(new_ids.len(), 3);
+        assert_eq!(state.selection_state[&ids[0]].text(), "same");
+        assert_eq!(state.selection_state[&ids[1]].text(), "second");
+        assert_eq!(state.selection_state[&new_ids[2]].text(), "same");
+        assert!(state.dropdown_state.values().all(|open| *open));
+    }
+
+    #[cfg(feature = "markdown")]
+    fn task_sources(source: &str, state: &MarkState) -> Vec<String> {
+        state
+            .tasks
+            .iter()
+            .map(|n| source[n.source_range.clone().unwrap()].to_owned())
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "markdown")]
+    fn task_ranges() {
+        let source = "# <Tasks> & more\n\n- [ ] one\n- [x] two\n  1. [X] nested\n- \\[x] escaped";
+        let state = MarkState::with_html_and_markdown(source);
+        assert_eq!(task_sources(source, &state), ["[ ]", "[x]", "[X]", "[x]"]);
+        assert_eq!(state.tasks[1].source_range, Some(30..33));
+        assert_eq!(state.tasks[3].source_range, Some(57..60));
+    }
+
+    #[test]
+    #[cfg(feature = "markdown")]
+    fn task_ranges_with_escaped_html() {
+        // `<` and `&` are longer once escaped, so the
+        // offsets have to be mapped back to the source
+        let source =
+            "<b>\"a\" & b</b>\n\n- [x] <i>one</i> & [two]\n- [ ] \\[x] three\n- \\[x] <four>";
+        let state = MarkState::with_markdown_only(source);
+        assert_eq!(task_sources(source, &state), ["[x]", "[ ]", "[x]"]);
+        assert_eq!(state.tasks[0].source_range, Some(18..21));
+        assert_eq!(state.tasks[1].source_range, Some(43..46));
+    }
+}
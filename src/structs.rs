@@ -1,12 +1,23 @@
-use std::{ops::Add, sync::Arc};
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    ops::{Add, Range},
+    rc::Rc,
+    sync::Arc,
+};
 
 use bitflags::bitflags;
 use iced::{
     widget::{self, text_editor},
     Element, Font,
 };
+use markup5ever_rcdom::Handle;
 
-use crate::state::MarkState;
+use crate::{
+    form::FormSubmit,
+    selection::{plain_spans, SelectableBlock, SelectedText},
+    state::MarkState,
+};
 
 #[derive(Debug, Default, Clone, Copy)]
 pub struct ChildData {
@@ -14,7 +25,18 @@ pub struct ChildData {
     pub flags: ChildDataFlags,
     pub alignment: Option<ChildAlignment>,
 
-    pub li_ordered_number: Option<usize>,
+    /// The list that `<li>` elements are in.
+    pub list: Option<ListData>,
+    /// How many lists this is inside of.
+    pub list_depth: u8,
+
+    /// From CSS (see [`crate::css::CssStyle`]).
+    pub color: Option<iced::Color>,
+    pub background: Option<iced::Color>,
+    pub font_size: Option<f32>,
+
+    #[cfg(feature = "highlighting")]
+    pub syntax: Option<crate::highlight::Syntax>,
 }
 
 impl ChildData {
@@ -27,11 +49,33 @@ impl ChildData {
         self.flags.insert(flags);
         self
     }
+}
 
-    pub fn ordered(mut self) -> Self {
-        self.li_ordered_number = Some(1);
-        self
-    }
+#[derive(Debug, Clone, Copy)]
+pub struct ListData {
+    pub style: ListStyle,
+    /// The number of the next `<li>`
+    /// (or the current one, for its children).
+    pub number: i64,
+    /// -1 for `<ol reversed>`, otherwise 1.
+    pub step: i64,
+    /// Width of the widest marker in the list,
+    /// so that the items line up.
+    pub marker_width: f32,
+}
+
+/// The marker of list items (`list-style-type` in CSS).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListStyle {
+    Disc,
+    Circle,
+    Square,
+    Decimal,
+    LowerAlpha,
+    UpperAlpha,
+    LowerRoman,
+    UpperRoman,
+    None,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -73,13 +117,35 @@ pub struct UpdateMsg {
 
 #[derive(Debug, Clone)]
 pub enum UpdateMsgKind {
-    TextEditor(String, text_editor::Action),
-    DetailsToggle(usize, bool),
+    TextEditor(StableId, text_editor::Action),
+    DetailsToggle(StableId, bool),
+    Selection(Option<SelectedText>),
+    FieldInput(StableId, String),
+    FieldChecked(StableId, bool),
+    FieldSelect(StableId, usize),
+    FieldEdit(StableId, text_editor::Action),
+    FormReset(usize),
 }
 
-type FClickLink<M> = Box<dyn Fn(String) -> M>;
+/// Identifies a code block, `<details>` element or form field
+/// in a way that survives [`MarkState::reparse`].
+///
+/// Made from its contents (the code, the summary text or the field name)
+/// and how many elements with the same contents came before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StableId(pub(crate) u64);
+
+pub(crate) type FClickLink<M> = Box<dyn Fn(String) -> M>;
+type FResolveWikilink<'a, M> = Box<dyn Fn(&str) -> Option<ResolvedWikilink<M>> + 'a>;
 type FDrawImage<'a, M, T> = Box<dyn Fn(ImageInfo) -> Element<'static, M, T> + 'a>;
+type FDrawEmoji<'a> = Box<dyn Fn(&str) -> Option<String> + 'a>;
+type FDrawCodeBlock<'a, M, T> = Box<dyn Fn(CodeBlockInfo<'a>) -> Option<Element<'a, M, T>> + 'a>;
+type FRenderElement<'a, M, T> =
+    Box<dyn Fn(ElementInfo<'a, '_, M, T>) -> Option<Element<'a, M, T>> + 'a>;
 type FUpdate<M> = Arc<dyn Fn(UpdateMsg) -> M>;
+pub(crate) type FToggleTask<M> = Arc<dyn Fn(TaskToggle) -> M>;
+type FSubmitForm<M> = Box<dyn Fn(FormSubmit) -> M>;
+pub(crate) type FStyle<'a, T> = Rc<dyn Fn(&T) -> crate::Style + 'a>;
 pub(crate) type FStyleLinkButton<T> =
     Arc<dyn Fn(&T, widget::button::Status) -> widget::button::Style + 'static>;
 
@@ -108,17 +174,32 @@ pub struct MarkWidget<'a, Message, Theme = iced::Theme> {
     pub(crate) font: Font,
     pub(crate) font_mono: Font,
     pub(crate) style: Option<crate::Style>,
+    pub(crate) class: Option<FStyle<'a, Theme>>,
+    /// The theme from [`MarkWidget::theme`].
+    pub(crate) theme: Option<Theme>,
+    /// The colors from [`MarkWidget::colors`], once they're needed.
+    pub(crate) colors: Cell<Option<crate::Style>>,
     pub(crate) text_size: f32,
     pub(crate) heading_scale: f32,
 
     pub(crate) fn_clicking_link: Option<FClickLink<Message>>,
+    pub(crate) fn_jumping_to_anchor: Option<FClickLink<Message>>,
+    pub(crate) fn_resolving_wikilink: Option<FResolveWikilink<'a, Message>>,
     pub(crate) fn_drawing_image: Option<FDrawImage<'a, Message, Theme>>,
+    pub(crate) fn_drawing_emoji: Option<FDrawEmoji<'a>>,
+    pub(crate) fn_drawing_code_block: Option<FDrawCodeBlock<'a, Message, Theme>>,
+    pub(crate) fn_rendering_element: Option<FRenderElement<'a, Message, Theme>>,
     pub(crate) fn_update: Option<FUpdate<Message>>,
+    pub(crate) fn_toggling_task: Option<FToggleTask<Message>>,
+    pub(crate) fn_submitting_form: Option<FSubmitForm<Message>>,
     pub(crate) fn_style_link_button: Option<FStyleLinkButton<Theme>>,
 
+    pub(crate) alert_kinds: HashMap<String, AlertKind>,
     pub(crate) paragraph_spacing: Option<f32>,
-
-    pub(crate) current_dropdown_id: usize,
+    pub(crate) selectable: bool,
+    pub(crate) virtualized: bool,
+    #[cfg(feature = "highlighting")]
+    pub(crate) highlight_theme: Option<crate::HighlightTheme>,
 }
 
 impl<'a, M: 'a, T: 'a> MarkWidget<'a, M, T> {
@@ -132,14 +213,28 @@ impl<'a, M: 'a, T: 'a> MarkWidget<'a, M, T> {
             font: Font::DEFAULT,
             font_mono: Font::MONOSPACE,
             fn_clicking_link: None,
+            fn_jumping_to_anchor: None,
+            fn_resolving_wikilink: None,
             fn_drawing_image: None,
+            fn_drawing_emoji: None,
+            fn_drawing_code_block: None,
+            fn_rendering_element: None,
             fn_update: None,
+            fn_toggling_task: None,
+            fn_submitting_form: None,
             fn_style_link_button: None,
             style: None,
-            current_dropdown_id: 0,
+            class: None,
+            theme: None,
+            colors: Cell::new(None),
             text_size: 16.0,
             heading_scale: 1.0,
+            alert_kinds: HashMap::new(),
             paragraph_spacing: None,
+            selectable: false,
+            virtualized: false,
+            #[cfg(feature = "highlighting")]
+            highlight_theme: None,
         }
     }
 
@@ -192,6 +287,36 @@ impl<'a, M: 'a, T: 'a> MarkWidget<'a, M, T> {
         self
     }
 
+    /// Adds a kind of alert (like `> [!NOTE]` in Markdown),
+    /// or changes one of the built-in ones
+    /// (`note`, `tip`, `important`, `warning` and `caution`).
+    ///
+    /// The name isn't case-sensitive.
+    ///
+    /// ```no_run
+    /// # use frostmark::{AlertKind, MarkWidget, MarkState};
+    /// # #[derive(Clone)]
+    /// # enum Message {}
+    /// # struct E {mark_state: MarkState} impl E { fn e(&self) {
+    /// # let m: MarkWidget<'_, Message> =
+    /// // For `> [!BUG]`
+    /// MarkWidget::new(&self.mark_state)
+    ///     .alert_kind("bug", AlertKind {
+    ///         title: "Known Bug".to_owned(),
+    ///         icon: "🐞".to_owned(),
+    ///         color: iced::Color::from_rgb8(0xBF, 0x3A, 0x8C),
+    ///     })
+    /// # ; } }
+    /// ```
+    ///
+    /// Quotes starting with an alert name that
+    /// isn't known are shown as regular quotes.
+    #[must_use]
+    pub fn alert_kind(mut self, name: &str, kind: AlertKind) -> Self {
+        self.alert_kinds.insert(name.to_lowercase(), kind);
+        self
+    }
+
     /// When clicking a link, send a message to handle it.
     ///
     /// ```no_run
@@ -210,6 +335,121 @@ impl<'a, M: 'a, T: 'a> MarkWidget<'a, M, T> {
         self
     }
 
+    /// When clicking a link to a heading in this document
+    /// (eg: `[see below](#installation)`), send a message to handle it.
+    /// Footnote references and their `↩` back-links are handled here too.
+    ///
+    /// The closure receives the anchor without the leading `#`.
+    /// Pass it to [`crate::scroll_to_anchor`] to scroll to the heading.
+    ///
+    /// If this isn't set, such links go to
+    /// [`MarkWidget::on_clicking_link`] like any other link.
+    ///
+    /// See [`MarkState::anchors`] for the list of anchors in the document.
+    #[must_use]
+    pub fn on_jumping_to_anchor(mut self, f: impl Fn(String) -> M + 'static) -> Self {
+        self.fn_jumping_to_anchor = Some(Box::new(f));
+        self
+    }
+
+    /// Finds out where `[[Page Name]]` and `[[Page Name|label]]`
+    /// links go, for wikis and knowledge bases.
+    ///
+    /// ```no_run
+    /// # use frostmark::{MarkWidget, MarkState, ResolvedWikilink};
+    /// # #[derive(Clone)]
+    /// # enum Message { OpenPage(String) }
+    /// # struct E { mark_state: MarkState, pages: Vec<String> }
+    /// # impl E { fn e(&self) {
+    /// # let m: MarkWidget<'_, Message> =
+    /// MarkWidget::new(&self.mark_state)
+    ///     .on_resolving_wikilink(|page| {
+    ///         self.pages
+    ///             .iter()
+    ///             .any(|n| n == page)
+    ///             .then(|| ResolvedWikilink::Message(Message::OpenPage(page.to_owned())))
+    ///     })
+    /// # ; } }
+    /// ```
+    ///
+    /// The closure gets the page name (eg: `Page Name`), and returns
+    /// a URL or message for it (see [`ResolvedWikilink`]).
+    /// Return `None` for pages that don't exist: the link is shown in
+    /// [`crate::Style::unresolved_link_color`] (red) and clicking it
+    /// sends the page name to [`MarkWidget::on_clicking_link`]
+    /// (eg: for creating the page).
+    ///
+    /// # Notes:
+    /// - Markdown wikilinks need the `wikilinks` crate feature.
+    ///   In HTML, they're `<a data-wikilink="true" href="Page%20Name">`.
+    /// - Without this, wikilinks are regular links to the page name.
+    #[must_use]
+    pub fn on_resolving_wikilink(
+        mut self,
+        f: impl Fn(&str) -> Option<ResolvedWikilink<M>> + 'a,
+    ) -> Self {
+        self.fn_resolving_wikilink = Some(Box::new(f));
+        self
+    }
+
+    /// Makes task list checkboxes (`- [ ] item` in Markdown)
+    /// clickable, sending a message when one is toggled.
+    ///
+    /// The checkbox doesn't change by itself: edit your source
+    /// and call [`MarkState::reparse`] with it. For Markdown,
+    /// [`TaskToggle::source_range`] is where the `[ ]` of the
+    /// item is, so you can replace it.
+    ///
+    /// ```no_run
+    /// # use frostmark::{MarkWidget, MarkState, TaskToggle};
+    /// # #[derive(Clone)]
+    /// # enum Message { ToggleTask(TaskToggle) }
+    /// # struct E {mark_state: MarkState} impl E { fn e(&self) {
+    /// # let m: MarkWidget<'_, Message> =
+    /// MarkWidget::new(&self.mark_state)
+    ///     .on_toggling_task(|toggle| Message::ToggleTask(toggle))
+    /// # ; } }
+    /// # fn update(source: &mut String, state: &mut MarkState, toggle: TaskToggle) {
+    /// // in your update function
+    /// if let Some(range) = toggle.source_range {
+    ///     source.replace_range(range, if toggle.checked { "[x]" } else { "[ ]" });
+    ///     state.reparse(source);
+    /// }
+    /// # }
+    /// ```
+    ///
+    /// Only checkboxes inside list items count as tasks.
+    /// See [`MarkState::task_progress`] for how many are done.
+    #[must_use]
+    pub fn on_toggling_task(mut self, f: impl Fn(TaskToggle) -> M + 'static) -> Self {
+        self.fn_toggling_task = Some(Arc::new(f));
+        self
+    }
+
+    /// When a `<form>` in the document is submitted
+    /// (with a submit button, or by pressing Enter in a text field),
+    /// send a message with its fields.
+    ///
+    /// ```no_run
+    /// # use frostmark::{MarkWidget, MarkState, FormSubmit};
+    /// # #[derive(Clone)]
+    /// # enum Message { SubmitForm(FormSubmit) }
+    /// # struct E {mark_state: MarkState} impl E { fn e(&self) {
+    /// # let m: MarkWidget<'_, Message> =
+    /// MarkWidget::new(&self.mark_state)
+    ///     .on_submitting_form(|form| Message::SubmitForm(form))
+    /// # ; } }
+    /// ```
+    ///
+    /// The values of fields are kept in the [`MarkState`],
+    /// so [`MarkWidget::on_updating_state`] is needed
+    /// for them to be editable.
+    #[must_use]
+    pub fn on_submitting_form(mut self, f: impl Fn(FormSubmit) -> M + 'static) -> Self {
+        self.fn_submitting_form = Some(Box::new(f));
+        self
+    }
+
     /// Customizes how images are drawn in your widget.
     ///
     /// ```ignore
@@ -261,6 +501,151 @@ impl<'a, M: 'a, T: 'a> MarkWidget<'a, M, T> {
         self
     }
 
+    /// Shows emoji as images, for fonts without color emoji.
+    ///
+    /// The closure gets an emoji (eg: `🎉`, or `👨‍💻` for
+    /// joined ones) and returns the URL of its image,
+    /// which is then drawn through [`MarkWidget::on_drawing_image`]
+    /// at the size of the text around it.
+    /// Return `None` to keep it as text.
+    ///
+    /// ```no_run
+    /// # use frostmark::{MarkWidget, MarkState};
+    /// # #[derive(Clone)]
+    /// # enum Message {}
+    /// # struct E {mark_state: MarkState} impl E { fn e(&self) {
+    /// # let m: MarkWidget<'_, Message> =
+    /// MarkWidget::new(&self.mark_state)
+    ///     // Twemoji names its images after the codepoints (eg: `1f389.png`)
+    ///     .on_drawing_emoji(|emoji| {
+    ///         let name: Vec<String> = emoji
+    ///             .chars()
+    ///             .filter(|c| *c != '\u{fe0f}')
+    ///             .map(|c| format!("{:x}", c as u32))
+    ///             .collect();
+    ///         Some(format!("twemoji/{}.png", name.join("-")))
+    ///     })
+    ///     .on_drawing_image(|info| {
+    ///         // Load and draw `info.url` like any other image
+    /// #       todo!()
+    ///     })
+    /// # ; } }
+    /// ```
+    ///
+    /// # Notes:
+    /// - Shortcodes like `:tada:` are turned into emoji
+    ///   with the `shortcodes` crate feature.
+    /// - Use [`MarkState::find_emojis`] to get the
+    ///   emoji in the document, for loading their images.
+    /// - Emoji in code aren't replaced.
+    #[must_use]
+    pub fn on_drawing_emoji(mut self, f: impl Fn(&str) -> Option<String> + 'a) -> Self {
+        self.fn_drawing_emoji = Some(Box::new(f));
+        self
+    }
+
+    /// Draws code blocks yourself, for example to turn
+    /// ` ```mermaid ` blocks into diagrams or show ` ```csv ` as a table.
+    ///
+    /// ```no_run
+    /// # use frostmark::{MarkWidget, MarkState};
+    /// # use iced::widget;
+    /// # #[derive(Clone)]
+    /// # enum Message {}
+    /// # struct E {mark_state: MarkState} impl E { fn e(&self) {
+    /// # let m: MarkWidget<'_, Message> =
+    /// MarkWidget::new(&self.mark_state)
+    ///     .on_drawing_code_block(|info| match info.language {
+    ///         Some("diff") => Some(
+    ///             widget::column(info.code.lines().map(|line| {
+    ///                 let color = match line.chars().next() {
+    ///                     Some('+') => Some(iced::Color::from_rgb(0.1, 0.6, 0.1)),
+    ///                     Some('-') => Some(iced::Color::from_rgb(0.8, 0.1, 0.1)),
+    ///                     _ => None,
+    ///                 };
+    ///                 widget::text(line)
+    ///                     .font(iced::Font::MONOSPACE)
+    ///                     .color_maybe(color)
+    ///                     .into()
+    ///             }))
+    ///             .into(),
+    ///         ),
+    ///         _ => None,
+    ///     })
+    /// # ; } }
+    /// ```
+    ///
+    /// The closure gets the language, the full info string
+    /// and the code, see [`CodeBlockInfo`].
+    /// Return `None` to draw the usual (selectable,
+    /// maybe highlighted) code block.
+    ///
+    /// # Notes:
+    /// - This is called for `<pre><code>` blocks (like the ones
+    ///   from Markdown) that only have plain text inside.
+    /// - [`MarkWidget::on_rendering_element`] goes first,
+    ///   so this isn't called if that one handles `<pre>`.
+    #[must_use]
+    pub fn on_drawing_code_block(
+        mut self,
+        f: impl Fn(CodeBlockInfo<'a>) -> Option<Element<'a, M, T>> + 'a,
+    ) -> Self {
+        self.fn_drawing_code_block = Some(Box::new(f));
+        self
+    }
+
+    /// Renders elements yourself, such as your own tags
+    /// (eg: `<user-mention>`, `<badge>`) or built-in ones
+    /// you want to look different.
+    ///
+    /// ```no_run
+    /// # use frostmark::{MarkWidget, MarkState};
+    /// # use iced::widget;
+    /// # #[derive(Clone)]
+    /// # enum Message {}
+    /// # struct E {mark_state: MarkState} impl E { fn e(&self) {
+    /// # let m: MarkWidget<'_, Message> =
+    /// MarkWidget::new(&self.mark_state)
+    ///     .on_rendering_element(|info| match info.tag {
+    ///         "badge" => Some(
+    ///             widget::container(info.children())
+    ///                 .padding(2)
+    ///                 .style(widget::container::rounded_box)
+    ///                 .into(),
+    ///         ),
+    ///         "user-mention" => {
+    ///             let name = info.attr("user").unwrap_or("someone");
+    ///             Some(widget::text!("@{name}").into())
+    ///         }
+    ///         _ => None,
+    ///     })
+    /// # ; } }
+    /// ```
+    ///
+    /// The closure gets the tag name, its attributes
+    /// and a way to render its children, see [`ElementInfo`].
+    /// Return `None` to render the element like frostmark
+    /// normally would.
+    ///
+    /// # Notes:
+    /// - This is called for every element in the document,
+    ///   so keep it quick.
+    /// - Elements you don't know (or haven't overridden)
+    ///   are laid out inline, like `<span>`. Wrap what you return
+    ///   in a [`iced::widget::Column`] with `Length::Fill` width
+    ///   if it should take up its own line.
+    /// - The children are only rendered when you call
+    ///   [`ElementInfo::children`], so don't call it
+    ///   for tags you don't handle.
+    #[must_use]
+    pub fn on_rendering_element(
+        mut self,
+        f: impl Fn(ElementInfo<'a, '_, M, T>) -> Option<Element<'a, M, T>> + 'a,
+    ) -> Self {
+        self.fn_rendering_element = Some(Box::new(f));
+        self
+    }
+
     /// Passes a message when the internal state of the document is updated.
     ///
     /// # Usage:
@@ -306,12 +691,81 @@ impl<'a, M: 'a, T: 'a> MarkWidget<'a, M, T> {
 
     /// Change the color of different kinds of text
     /// in the document using [`crate::Style`].
+    ///
+    /// Colors set here take priority over the ones
+    /// from the theme (see [`MarkWidget::class`]).
     #[must_use]
     pub fn style(mut self, style: crate::Style) -> Self {
         self.style = Some(style);
         self
     }
 
+    /// Sets the style class of the document,
+    /// picking its colors based on the theme (see [`crate::Catalog`]).
+    ///
+    /// ```no_run
+    /// # use frostmark::{MarkWidget, MarkState};
+    /// # #[derive(Clone)]
+    /// # enum Message {}
+    /// # struct E {mark_state: MarkState} impl E { fn e(&self) {
+    /// let style: frostmark::StyleFn<'_, iced::Theme> = Box::new(|theme| frostmark::Style {
+    ///     heading_color: Some(theme.palette().primary),
+    ///     ..frostmark::Style::from(theme)
+    /// });
+    /// # let m: MarkWidget<'_, Message> =
+    /// MarkWidget::new(&self.mark_state).class(style)
+    /// # ; } }
+    /// ```
+    #[must_use]
+    pub fn class(mut self, class: impl Into<T::Class<'a>>) -> Self
+    where
+        T: crate::Catalog,
+    {
+        let class = class.into();
+        self.class = Some(Rc::new(move |theme: &T| theme.style(&class)));
+        self
+    }
+
+    /// Tells the widget which theme your app is using.
+    ///
+    /// Most of the document (code blocks, quotes, rules, headings, etc)
+    /// follows the theme by itself, but some colors are part
+    /// of the text and needed before it's drawn:
+    /// link text, highlights and inline code.
+    /// Pass in your app's theme here for those to match it too,
+    /// otherwise they use the defaults from [`crate::Style`].
+    ///
+    /// ```no_run
+    /// # use frostmark::{MarkWidget, MarkState};
+    /// # #[derive(Clone)]
+    /// # enum Message {}
+    /// # struct E {mark_state: MarkState, theme: iced::Theme} impl E { fn e(&self) {
+    /// # let m: MarkWidget<'_, Message> =
+    /// MarkWidget::new(&self.mark_state).theme(&self.theme)
+    /// # ; } }
+    /// ```
+    #[must_use]
+    pub fn theme(mut self, theme: &T) -> Self
+    where
+        T: crate::Catalog + Clone,
+    {
+        self.theme = Some(theme.clone());
+        self
+    }
+
+    /// Sets the color scheme used for syntax highlighting
+    /// in code blocks (default: [`crate::Style::highlight_theme`]).
+    ///
+    /// Code blocks are highlighted based on the language
+    /// of the fence (eg: ` ```rust `), or the `language-*` class
+    /// of the `<code>` element. Unknown languages are left as plain text.
+    #[must_use]
+    #[cfg(feature = "highlighting")]
+    pub fn highlight_theme(mut self, theme: crate::HighlightTheme) -> Self {
+        self.highlight_theme = Some(theme);
+        self
+    }
+
     /// Styles link buttons.
     ///
     /// Link buttons are links with non-text content (eg: images).
@@ -354,11 +808,76 @@ impl<'a, M: 'a, T: 'a> MarkWidget<'a, M, T> {
         self.paragraph_spacing = Some(spacing);
         self
     }
+
+    /// Whether text in the document can be selected
+    /// by click-dragging over it (default: false).
+    ///
+    /// Text is selected a character at a time, across as many
+    /// paragraphs as the drag goes over. Code blocks are selected whole.
+    /// `Ctrl+A` selects the whole document, and `Ctrl+C` copies
+    /// the selection as plain text (`Cmd` on macOS).
+    ///
+    /// To get the selection in other formats, call
+    /// [`MarkState::selected_text`]. This needs
+    /// [`MarkWidget::on_updating_state`] to be set.
+    #[must_use]
+    pub fn selectable(mut self, selectable: bool) -> Self {
+        self.selectable = selectable;
+        self
+    }
+
+    /// Only builds the parts of the document near
+    /// the visible area (default: false).
+    ///
+    /// Use this for very large documents inside a
+    /// [`iced::widget::Scrollable`], so that rendering takes
+    /// about the same time no matter how long the document is.
+    ///
+    /// The rest of the document is replaced by empty space,
+    /// sized by a rough guess until it's scrolled into view
+    /// (so the scrollbar may shift a bit while scrolling).
+    /// [`crate::scroll_to_anchor`] and [`MarkState::scroll_to_match`]
+    /// still work, but may land slightly off for far-away targets.
+    /// Copying the whole selection (`Ctrl+A`, `Ctrl+C`) includes the
+    /// parts that aren't built, though they can't be selected partly.
+    #[must_use]
+    pub fn virtualized(mut self, virtualized: bool) -> Self {
+        self.virtualized = virtualized;
+        self
+    }
+}
+
+impl<'a, M: 'a, T: crate::Catalog + 'a> MarkWidget<'a, M, T> {
+    /// Gets the style of the document for a theme,
+    /// with the colors from [`MarkWidget::style`] on top.
+    pub(crate) fn class_fn(&self) -> FStyle<'a, T> {
+        let class = self.class.clone().unwrap_or_else(|| {
+            let class = T::default();
+            Rc::new(move |theme: &T| theme.style(&class))
+        });
+        let style = self.style.unwrap_or_default();
+        Rc::new(move |theme: &T| style.or(class(theme)))
+    }
+
+    /// The colors needed while building the document
+    /// (see [`MarkWidget::theme`]).
+    pub(crate) fn colors(&self) -> crate::Style {
+        if let Some(colors) = self.colors.get() {
+            return colors;
+        }
+        let colors = match &self.theme {
+            Some(theme) => self.class_fn()(theme),
+            None => self.style.unwrap_or_default(),
+        };
+        self.colors.set(Some(colors));
+        colors
+    }
 }
 
 #[derive(Default)]
 pub enum RenderedSpan<'a, M, T> {
-    Spans(Vec<widget::text::Span<'a, M, Font>>),
+    /// Spans of text, and the DOM nodes they came from.
+    Spans(Vec<widget::text::Span<'a, M, Font>>, Vec<Handle>),
     Elem(Element<'a, M, T>, Emp),
     #[default]
     None,
@@ -367,7 +886,7 @@ pub enum RenderedSpan<'a, M, T> {
 impl<M, T> std::fmt::Debug for RenderedSpan<'_, M, T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            RenderedSpan::Spans(spans) => {
+            RenderedSpan::Spans(spans, _) => {
                 write!(f, "Rs::Spans ")?;
                 f.debug_list()
                     .entries(spans.iter().map(|n| &*n.text))
@@ -386,7 +905,7 @@ where
 {
     pub fn is_empty(&self) -> bool {
         match self {
-            RenderedSpan::Spans(spans) => spans.is_empty(),
+            RenderedSpan::Spans(spans, _) => spans.is_empty(),
             RenderedSpan::Elem(_, e) => matches!(e, Emp::Empty),
             RenderedSpan::None => true,
         }
@@ -395,7 +914,17 @@ where
     // btw it supports clone so it's fine if we dont ref
     pub fn render(self) -> Element<'a, M, T> {
         match self {
-            RenderedSpan::Spans(spans) => widget::rich_text(spans).into(),
+            RenderedSpan::Spans(spans, nodes) => {
+                if nodes.is_empty() {
+                    widget::rich_text(spans)
+                        .on_link_click(std::convert::identity)
+                        .into()
+                } else {
+                    let plain = plain_spans(&spans);
+                    let text = widget::rich_text(spans).on_link_click(std::convert::identity);
+                    SelectableBlock::new(text, nodes).with_spans(plain).into()
+                }
+            }
             RenderedSpan::Elem(element, _) => element,
             RenderedSpan::None => widget::Column::new().into(),
         }
@@ -415,12 +944,13 @@ where
             (Rs::None, rhs) => rhs,
             (lhs, Rs::None) => lhs,
 
-            (Rs::Spans(mut spans1), Rs::Spans(spans2)) => {
+            (Rs::Spans(mut spans1, mut nodes1), Rs::Spans(spans2, nodes2)) => {
                 spans1.extend(spans2);
-                Rs::Spans(spans1)
+                nodes1.extend(nodes2);
+                Rs::Spans(spans1, nodes1)
             }
 
-            (r @ Rs::Spans(_), Rs::Elem(element, e)) => Rs::Elem(
+            (r @ Rs::Spans(..), Rs::Elem(element, e)) => Rs::Elem(
                 widget::row![r.render()]
                     .push(e.has_something().then_some(element))
                     .spacing(5)
@@ -428,7 +958,7 @@ where
                     .into(),
                 Emp::NonEmpty,
             ),
-            (Rs::Elem(element, e), r @ Rs::Spans(_)) => Rs::Elem(
+            (Rs::Elem(element, e), r @ Rs::Spans(..)) => Rs::Elem(
                 widget::Row::new()
                     .push(e.has_something().then_some(element))
                     .push(r.render())
@@ -489,3 +1019,101 @@ pub struct ImageInfo<'a> {
     pub width: Option<f32>,
     pub height: Option<f32>,
 }
+
+/// A code block, for drawing it yourself
+/// in [`MarkWidget::on_drawing_code_block`].
+#[non_exhaustive]
+pub struct CodeBlockInfo<'a> {
+    /// The language (eg: `rust` for ` ```rust title="main.rs" `),
+    /// if there is one.
+    pub language: Option<&'a str>,
+    /// Everything after the ` ``` ` (eg: `rust title="main.rs"`),
+    /// or an empty string.
+    pub info_string: String,
+    /// The code, without the final newline.
+    pub code: &'a str,
+}
+
+/// An element of the document, for rendering it yourself
+/// in [`MarkWidget::on_rendering_element`].
+#[non_exhaustive]
+pub struct ElementInfo<'a, 'b, M, T> {
+    /// The tag name, in lowercase (eg: `badge` for `<Badge>`).
+    pub tag: &'a str,
+    /// The attributes of the element, as `(name, value)` pairs.
+    pub attrs: Vec<(&'a str, &'a str)>,
+    pub(crate) children: &'b dyn Fn() -> Element<'a, M, T>,
+}
+
+impl<'a, M, T> ElementInfo<'a, '_, M, T> {
+    /// Renders the contents of the element.
+    ///
+    /// This renders the whole subtree each time it's called,
+    /// so only call it (once) for elements you draw yourself.
+    #[must_use]
+    pub fn children(&self) -> Element<'a, M, T> {
+        (self.children)()
+    }
+
+    /// Gets the value of an attribute, if it's there.
+    #[must_use]
+    pub fn attr(&self, name: &str) -> Option<&'a str> {
+        self.attrs
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Where a `[[wikilink]]` goes,
+/// from [`MarkWidget::on_resolving_wikilink`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolvedWikilink<M> {
+    /// A URL, opened like any other link
+    /// (through [`MarkWidget::on_clicking_link`]).
+    Url(String),
+    /// A message to send when the link is clicked.
+    Message(M),
+}
+
+/// A kind of alert, for [`MarkWidget::alert_kind`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertKind {
+    /// Shown at the top of the alert (eg: `Note`).
+    pub title: String,
+    /// Shown before the title (eg: `ⓘ`).
+    pub icon: String,
+    /// Color of the title, the bar on the left
+    /// and (faintly) the background.
+    pub color: iced::Color,
+}
+
+/// A heading in the document outline,
+/// returned by [`MarkState::outline`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Heading {
+    /// 1 for `<h1>`, 2 for `<h2>`, and so on.
+    pub level: u8,
+    /// The plain text of the heading, without formatting.
+    pub text: String,
+    /// The anchor id, to pass to [`crate::scroll_to_anchor`].
+    pub anchor: String,
+    /// Headings nested under this one (eg: `<h3>` below an `<h2>`).
+    pub children: Vec<Heading>,
+}
+
+/// A task list checkbox that was clicked,
+/// sent to [`MarkWidget::on_toggling_task`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaskToggle {
+    /// Which task this is, counting from 0 in document order.
+    pub index: usize,
+    /// The byte range of the `[ ]` or `[x]` of this item
+    /// in the Markdown source.
+    ///
+    /// `None` for HTML documents and checkboxes
+    /// written as HTML (`<input type="checkbox">`).
+    pub source_range: Option<Range<usize>>,
+    /// Whether the task should now be checked.
+    pub checked: bool,
+}
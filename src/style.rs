@@ -1,15 +1,245 @@
+use iced::theme::palette;
+
 /// The style of a [`crate::MarkWidget`]
 /// that affects how it's rendered.
+///
+/// Any color left as `None` falls back to the one
+/// from the theme (see [`Catalog`]), and then to the default.
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub struct Style {
     /// Color of regular text.
     pub text_color: Option<iced::Color>,
     /// Color of link **text**.
     ///
-    /// Default: `#5A6B9E`
+    /// Default: the theme's primary color (see [`crate::MarkWidget::theme`]),
+    /// or `#5A6B9E`
     pub link_color: Option<iced::Color>,
+    /// Color of `[[wikilinks]]` that [`crate::MarkWidget::on_resolving_wikilink`]
+    /// couldn't resolve (to pages that don't exist yet).
+    ///
+    /// Default: the theme's danger color (see [`crate::MarkWidget::theme`]),
+    /// or `#CF222E`
+    pub unresolved_link_color: Option<iced::Color>,
     /// Background color for text highlights (`<mark>` element).
     ///
-    /// Default: `#F7D84B`
+    /// Default: the theme's warning color (see [`crate::MarkWidget::theme`]),
+    /// or `#F7D84B`
     pub highlight_color: Option<iced::Color>,
+    /// Color of highlighted text (`<mark>` element).
+    ///
+    /// Default: text color, or black/white if that's
+    /// hard to read on [`Style::highlight_color`]
+    pub highlight_text_color: Option<iced::Color>,
+    /// Color of table cell borders.
+    ///
+    /// Default: text color at 30% opacity
+    pub table_border_color: Option<iced::Color>,
+    /// Background color of selected text.
+    ///
+    /// Default: `#3D7EFF` at 30% opacity
+    pub selection_color: Option<iced::Color>,
+    /// Background color of matches from [`crate::MarkState::find`].
+    ///
+    /// Default: `#F7D84B` at 50% opacity
+    pub find_match_color: Option<iced::Color>,
+    /// Background color of the current match from [`crate::MarkState::find`].
+    ///
+    /// Default: `#FF9632`
+    pub find_current_color: Option<iced::Color>,
+    /// Color of heading text (`<h1>` to `<h6>`).
+    ///
+    /// Default: text color
+    pub heading_color: Option<iced::Color>,
+    /// Color of inline code text (like `this`).
+    ///
+    /// Default: text color
+    pub inline_code_color: Option<iced::Color>,
+    /// Background color of inline code.
+    ///
+    /// Default: none
+    pub inline_code_background: Option<iced::Color>,
+    /// Background color of code blocks.
+    ///
+    /// Default: from the theme's text editor style
+    pub code_block_background: Option<iced::Color>,
+    /// Border color of code blocks.
+    ///
+    /// Default: from the theme's text editor style
+    pub code_block_border_color: Option<iced::Color>,
+    /// Color of the bar on the left of quotes (`<blockquote>`).
+    ///
+    /// Default: from the theme's rule style
+    pub quote_bar_color: Option<iced::Color>,
+    /// Color of horizontal rules (`<hr>`).
+    ///
+    /// Default: from the theme's rule style
+    pub rule_color: Option<iced::Color>,
+    /// Color of list bullets and numbers.
+    ///
+    /// Default: text color
+    pub list_marker_color: Option<iced::Color>,
+    /// Color of the arrow and line next to dropdowns (`<details>`).
+    ///
+    /// Default: text color
+    pub details_marker_color: Option<iced::Color>,
+    /// Color of `> [!NOTE]` alerts (their title, bar and background).
+    ///
+    /// Default: the theme's primary color, or `#0969DA`
+    pub note_color: Option<iced::Color>,
+    /// Color of `> [!TIP]` alerts.
+    ///
+    /// Default: the theme's success color, or `#1A7F37`
+    pub tip_color: Option<iced::Color>,
+    /// Color of `> [!IMPORTANT]` alerts.
+    ///
+    /// Default: `#8250DF`
+    pub important_color: Option<iced::Color>,
+    /// Color of `> [!WARNING]` alerts.
+    ///
+    /// Default: the theme's warning color, or `#9A6700`
+    pub warning_color: Option<iced::Color>,
+    /// Color of `> [!CAUTION]` alerts.
+    ///
+    /// Default: the theme's danger color, or `#CF222E`
+    pub caution_color: Option<iced::Color>,
+    /// Color scheme for syntax highlighting in code blocks.
+    ///
+    /// Default: light or dark, like the theme (see [`crate::MarkWidget::theme`]),
+    /// or [`crate::HighlightTheme::Dark`]
+    #[cfg(feature = "highlighting")]
+    pub highlight_theme: Option<crate::HighlightTheme>,
+}
+
+impl Style {
+    /// Fills in the colors that aren't set with the ones from `other`.
+    pub(crate) fn or(self, other: Self) -> Self {
+        Self {
+            text_color: self.text_color.or(other.text_color),
+            link_color: self.link_color.or(other.link_color),
+            unresolved_link_color: self.unresolved_link_color.or(other.unresolved_link_color),
+            highlight_color: self.highlight_color.or(other.highlight_color),
+            highlight_text_color: self.highlight_text_color.or(other.highlight_text_color),
+            table_border_color: self.table_border_color.or(other.table_border_color),
+            selection_color: self.selection_color.or(other.selection_color),
+            find_match_color: self.find_match_color.or(other.find_match_color),
+            find_current_color: self.find_current_color.or(other.find_current_color),
+            heading_color: self.heading_color.or(other.heading_color),
+            inline_code_color: self.inline_code_color.or(other.inline_code_color),
+            inline_code_background: self.inline_code_background.or(other.inline_code_background),
+            code_block_background: self.code_block_background.or(other.code_block_background),
+            code_block_border_color: self
+                .code_block_border_color
+                .or(other.code_block_border_color),
+            quote_bar_color: self.quote_bar_color.or(other.quote_bar_color),
+            rule_color: self.rule_color.or(other.rule_color),
+            list_marker_color: self.list_marker_color.or(other.list_marker_color),
+            details_marker_color: self.details_marker_color.or(other.details_marker_color),
+            note_color: self.note_color.or(other.note_color),
+            tip_color: self.tip_color.or(other.tip_color),
+            important_color: self.important_color.or(other.important_color),
+            warning_color: self.warning_color.or(other.warning_color),
+            caution_color: self.caution_color.or(other.caution_color),
+            #[cfg(feature = "highlighting")]
+            highlight_theme: self.highlight_theme.or(other.highlight_theme),
+        }
+    }
+}
+
+impl From<&iced::Theme> for Style {
+    fn from(theme: &iced::Theme) -> Self {
+        let palette = theme.extended_palette();
+        let background = palette.background.base.color;
+        let text = palette.background.base.text;
+
+        // The brighter/darker version of a color if the normal one
+        // is hard to read (eg: primary on dark themes)
+        let readable = |pair: palette::Pair, strong: palette::Pair, weak: palette::Pair| {
+            [pair.color, strong.color, weak.color]
+                .into_iter()
+                .find(|color| color.is_readable_on(background))
+                .unwrap_or(pair.color)
+        };
+        let link = readable(
+            palette.primary.base,
+            palette.primary.strong,
+            palette.primary.weak,
+        );
+
+        let danger = readable(
+            palette.danger.base,
+            palette.danger.strong,
+            palette.danger.weak,
+        );
+
+        let highlight = palette.warning.weak.color;
+
+        Self {
+            text_color: Some(text),
+            link_color: Some(link),
+            unresolved_link_color: Some(danger),
+            highlight_color: Some(highlight),
+            highlight_text_color: Some(palette::readable(highlight, text)),
+            inline_code_background: Some(palette.background.weak.color),
+            inline_code_color: Some(palette.background.weak.text),
+            code_block_background: Some(palette.background.weakest.color),
+            code_block_border_color: Some(palette.background.strong.color),
+            quote_bar_color: Some(palette.background.strong.color),
+            rule_color: Some(palette.background.strong.color),
+            note_color: Some(link),
+            tip_color: Some(readable(
+                palette.success.base,
+                palette.success.strong,
+                palette.success.weak,
+            )),
+            warning_color: Some(readable(
+                palette.warning.base,
+                palette.warning.strong,
+                palette.warning.weak,
+            )),
+            caution_color: Some(danger),
+            #[cfg(feature = "highlighting")]
+            highlight_theme: Some(if palette.is_dark {
+                crate::HighlightTheme::Dark
+            } else {
+                crate::HighlightTheme::Light
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// The theme catalog of a [`crate::MarkWidget`],
+/// for styling documents differently based on the theme.
+///
+/// This is implemented for [`iced::Theme`]
+/// (see `Style::from(&iced::Theme)` for the defaults).
+/// If you have a custom theme, implement this for it.
+///
+/// Colors that need to be known before drawing
+/// (link text, highlights, inline code)
+/// only follow the theme if it's passed to [`crate::MarkWidget::theme`].
+pub trait Catalog {
+    /// The item class of the [`Catalog`].
+    type Class<'a>;
+
+    /// The default class produced by the [`Catalog`].
+    fn default<'a>() -> Self::Class<'a>;
+
+    /// The [`Style`] of a class.
+    fn style(&self, class: &Self::Class<'_>) -> Style;
+}
+
+/// A styling function for a [`crate::MarkWidget`].
+pub type StyleFn<'a, Theme> = Box<dyn Fn(&Theme) -> Style + 'a>;
+
+impl Catalog for iced::Theme {
+    type Class<'a> = StyleFn<'a, Self>;
+
+    fn default<'a>() -> Self::Class<'a> {
+        Box::new(|theme| Style::from(theme))
+    }
+
+    fn style(&self, class: &Self::Class<'_>) -> Style {
+        class(self)
+    }
 }
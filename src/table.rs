@@ -0,0 +1,473 @@
+use iced::{
+    advanced::{
+        layout, mouse, overlay, renderer,
+        widget::{tree, Operation, Tree},
+        Clipboard, Layout, Shell, Widget,
+    },
+    Alignment, Color, Element, Event, Length, Rectangle, Size, Vector,
+};
+
+/// Where a cell sits in the grid, and how much of it it covers.
+#[derive(Debug, Clone, Copy)]
+pub struct CellInfo {
+    pub row: usize,
+    pub column: usize,
+    pub rowspan: usize,
+    pub colspan: usize,
+    pub align_x: Alignment,
+}
+
+/// Places cells on a table's grid, skipping the slots
+/// taken by cells spanning down from earlier rows.
+pub struct TableGrid {
+    /// For every row, the row its group
+    /// (`<thead>`, `<tbody>`, ..) ends before.
+    group_ends: Vec<usize>,
+    occupied: Vec<Vec<bool>>,
+}
+
+impl TableGrid {
+    /// `groups` has the row group of every row, in order.
+    pub fn new<G: PartialEq>(groups: &[G]) -> Self {
+        let group_ends = (0..groups.len())
+            .map(|row| {
+                groups[row..]
+                    .iter()
+                    .position(|n| *n != groups[row])
+                    .map_or(groups.len(), |n| row + n)
+            })
+            .collect();
+        Self {
+            group_ends,
+            occupied: vec![Vec::new(); groups.len()],
+        }
+    }
+
+    /// How many rows a cell starting in `row` can span,
+    /// as cells can't span past the end of their row group.
+    pub fn rows_left(&self, row: usize) -> usize {
+        self.group_ends[row] - row
+    }
+
+    /// Places a cell in the first free column of `row`,
+    /// starting from `column`, and returns that column.
+    pub fn place(
+        &mut self,
+        row: usize,
+        mut column: usize,
+        colspan: usize,
+        rowspan: usize,
+    ) -> usize {
+        while self.occupied[row].get(column).copied().unwrap_or(false) {
+            column += 1;
+        }
+        let end = column.saturating_add(colspan);
+        let last = row.saturating_add(rowspan).min(self.group_ends[row]);
+        for slots in &mut self.occupied[row..last] {
+            if slots.len() < end {
+                slots.resize(end, false);
+            }
+            slots[column..end].fill(true);
+        }
+        column
+    }
+}
+
+type FBorderColor<'a, T> = Box<dyn Fn(&T) -> Option<Color> + 'a>;
+
+/// A grid of cells with `colspan`/`rowspan` support,
+/// where every column is sized to fit its content.
+///
+/// Used for rendering `<table>` elements.
+pub struct Table<'a, M, T, R> {
+    elements: Vec<Element<'a, M, T, R>>,
+    cells: Vec<CellInfo>,
+    rows: usize,
+    columns: usize,
+    padding: f32,
+    border_width: f32,
+    border_color: Option<FBorderColor<'a, T>>,
+}
+
+#[derive(Default)]
+struct Metrics {
+    columns: Vec<f32>,
+    rows: Vec<f32>,
+}
+
+impl<'a, M, T, R> Table<'a, M, T, R> {
+    pub fn new(cells: Vec<(CellInfo, Element<'a, M, T, R>)>) -> Self {
+        let (cells, elements): (Vec<_>, Vec<_>) = cells.into_iter().unzip();
+        let rows = cells.iter().map(|n| n.row + n.rowspan).max().unwrap_or(0);
+        let columns = cells
+            .iter()
+            .map(|n| n.column + n.colspan)
+            .max()
+            .unwrap_or(0);
+        Self {
+            elements,
+            cells,
+            rows,
+            columns,
+            padding: 5.0,
+            border_width: 1.0,
+            border_color: None,
+        }
+    }
+
+    pub fn border_color(mut self, f: impl Fn(&T) -> Option<Color> + 'a) -> Self {
+        self.border_color = Some(Box::new(f));
+        self
+    }
+
+    fn spacing(&self) -> f32 {
+        self.padding * 2.0 + self.border_width
+    }
+
+    /// Offset of the border line before the `i`th track.
+    fn offset(&self, tracks: &[f32], i: usize) -> f32 {
+        tracks[..i].iter().map(|n| n + self.spacing()).sum()
+    }
+
+    fn span_size(&self, tracks: &[f32], start: usize, span: usize) -> f32 {
+        tracks[start..start + span].iter().sum::<f32>() + (span - 1) as f32 * self.spacing()
+    }
+}
+
+/// Grows the tracks covered by a spanning cell
+/// (evenly) until the cell fits in them.
+fn distribute(tracks: &mut [f32], needed: f32) {
+    let current: f32 = tracks.iter().sum();
+    if needed > current && !tracks.is_empty() {
+        #[allow(clippy::cast_precision_loss)]
+        let extra = (needed - current) / tracks.len() as f32;
+        for track in tracks {
+            *track += extra;
+        }
+    }
+}
+
+impl<M, T, R: iced::advanced::Renderer> Widget<M, T, R> for Table<'_, M, T, R> {
+    fn size(&self) -> Size<Length> {
+        Size::new(Length::Shrink, Length::Shrink)
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<Metrics>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(Metrics::default())
+    }
+
+    fn children(&self) -> Vec<Tree> {
+        self.elements.iter().map(Tree::new).collect()
+    }
+
+    fn diff(&self, tree: &mut Tree) {
+        tree.diff_children(&self.elements);
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn layout(&mut self, tree: &mut Tree, renderer: &R, limits: &layout::Limits) -> layout::Node {
+        let spacing = self.spacing();
+        let available = limits.max().width;
+        let overhead = self.columns as f32 * spacing + self.border_width;
+
+        // First pass: find how wide every cell wants to be
+        let mut columns = vec![0.0; self.columns];
+        let mut wanted = Vec::with_capacity(self.cells.len());
+        for ((element, state), cell) in self
+            .elements
+            .iter_mut()
+            .zip(&mut tree.children)
+            .zip(&self.cells)
+        {
+            let limits = layout::Limits::new(
+                Size::ZERO,
+                Size::new((available - overhead).max(0.0), f32::INFINITY),
+            );
+            let width = element
+                .as_widget_mut()
+                .layout(state, renderer, &limits)
+                .size()
+                .width;
+            let width = if width.is_finite() { width } else { 0.0 };
+            if cell.colspan == 1 {
+                columns[cell.column] = f32::max(columns[cell.column], width);
+            }
+            wanted.push(width);
+        }
+        for (cell, width) in self.cells.iter().zip(&wanted) {
+            if cell.colspan > 1 {
+                let inner = (cell.colspan - 1) as f32 * spacing;
+                distribute(
+                    &mut columns[cell.column..cell.column + cell.colspan],
+                    width - inner,
+                );
+            }
+        }
+
+        // Squeeze the columns if we overflow
+        let content: f32 = columns.iter().sum();
+        if available.is_finite() && content + overhead > available && content > 0.0 {
+            let factor = (available - overhead).max(0.0) / content;
+            for column in &mut columns {
+                *column *= factor;
+            }
+        }
+
+        // Second pass: lay out cells with their final widths
+        let mut rows = vec![0.0; self.rows];
+        let mut nodes = Vec::with_capacity(self.cells.len());
+        let widths: Vec<f32> = self
+            .cells
+            .iter()
+            .map(|cell| self.span_size(&columns, cell.column, cell.colspan))
+            .collect();
+        for (((element, state), cell), width) in self
+            .elements
+            .iter_mut()
+            .zip(&mut tree.children)
+            .zip(&self.cells)
+            .zip(widths)
+        {
+            let limits = layout::Limits::new(Size::ZERO, Size::new(width, f32::INFINITY));
+            let node = element.as_widget_mut().layout(state, renderer, &limits);
+            if cell.rowspan == 1 {
+                rows[cell.row] = f32::max(rows[cell.row], node.size().height);
+            }
+            nodes.push(node);
+        }
+        for (cell, node) in self.cells.iter().zip(&nodes) {
+            if cell.rowspan > 1 {
+                let inner = (cell.rowspan - 1) as f32 * spacing;
+                distribute(
+                    &mut rows[cell.row..cell.row + cell.rowspan],
+                    node.size().height - inner,
+                );
+            }
+        }
+
+        // Third pass: position everything
+        let inset = self.border_width + self.padding;
+        for (node, cell) in nodes.iter_mut().zip(&self.cells) {
+            node.move_to_mut((
+                self.offset(&columns, cell.column) + inset,
+                self.offset(&rows, cell.row) + inset,
+            ));
+            node.align_mut(
+                cell.align_x,
+                Alignment::Start,
+                Size::new(
+                    self.span_size(&columns, cell.column, cell.colspan),
+                    self.span_size(&rows, cell.row, cell.rowspan),
+                ),
+            );
+        }
+
+        let size = Size::new(
+            self.offset(&columns, self.columns) + self.border_width,
+            self.offset(&rows, self.rows) + self.border_width,
+        );
+
+        let metrics = tree.state.downcast_mut::<Metrics>();
+        metrics.columns = columns;
+        metrics.rows = rows;
+
+        layout::Node::with_children(size, nodes)
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut R,
+        theme: &T,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        for ((element, state), layout) in self
+            .elements
+            .iter()
+            .zip(&tree.children)
+            .zip(layout.children())
+        {
+            element
+                .as_widget()
+                .draw(state, renderer, theme, style, layout, cursor, viewport);
+        }
+
+        if self.border_width <= 0.0 {
+            return;
+        }
+
+        let metrics = tree.state.downcast_ref::<Metrics>();
+        let bounds = layout.bounds();
+        let color = self
+            .border_color
+            .as_ref()
+            .and_then(|f| f(theme))
+            .unwrap_or_else(|| style.text_color.scale_alpha(0.3));
+        let mut line = |x: f32, y: f32, width: f32, height: f32| {
+            renderer.fill_quad(
+                renderer::Quad {
+                    bounds: Rectangle {
+                        x: bounds.x + x,
+                        y: bounds.y + y,
+                        width,
+                        height,
+                    },
+                    snap: true,
+                    ..renderer::Quad::default()
+                },
+                color,
+            );
+        };
+
+        line(0.0, 0.0, bounds.width, self.border_width);
+        line(0.0, 0.0, self.border_width, bounds.height);
+
+        // Every cell draws its right and bottom edges,
+        // so spanning cells don't get lines through them.
+        for cell in &self.cells {
+            let left = self.offset(&metrics.columns, cell.column);
+            let right = self.offset(&metrics.columns, cell.column + cell.colspan);
+            let top = self.offset(&metrics.rows, cell.row);
+            let bottom = self.offset(&metrics.rows, cell.row + cell.rowspan);
+
+            line(
+                right,
+                top,
+                self.border_width,
+                bottom - top + self.border_width,
+            );
+            line(
+                left,
+                bottom,
+                right - left + self.border_width,
+                self.border_width,
+            );
+        }
+    }
+
+    fn operate(
+        &mut self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &R,
+        operation: &mut dyn Operation,
+    ) {
+        for ((element, state), layout) in self
+            .elements
+            .iter_mut()
+            .zip(&mut tree.children)
+            .zip(layout.children())
+        {
+            element
+                .as_widget_mut()
+                .operate(state, layout, renderer, operation);
+        }
+    }
+
+    fn update(
+        &mut self,
+        tree: &mut Tree,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &R,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, M>,
+        viewport: &Rectangle,
+    ) {
+        for ((element, state), layout) in self
+            .elements
+            .iter_mut()
+            .zip(&mut tree.children)
+            .zip(layout.children())
+        {
+            element.as_widget_mut().update(
+                state, event, layout, cursor, renderer, clipboard, shell, viewport,
+            );
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &R,
+    ) -> mouse::Interaction {
+        self.elements
+            .iter()
+            .zip(&tree.children)
+            .zip(layout.children())
+            .map(|((element, state), layout)| {
+                element
+                    .as_widget()
+                    .mouse_interaction(state, layout, cursor, viewport, renderer)
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'b>,
+        renderer: &R,
+        viewport: &Rectangle,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, M, T, R>> {
+        overlay::from_children(
+            &mut self.elements,
+            tree,
+            layout,
+            renderer,
+            viewport,
+            translation,
+        )
+    }
+}
+
+impl<'a, M: 'a, T: 'a, R: iced::advanced::Renderer + 'a> From<Table<'a, M, T, R>>
+    for Element<'a, M, T, R>
+{
+    fn from(value: Table<'a, M, T, R>) -> Self {
+        Element::new(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TableGrid;
+
+    #[test]
+    fn cells_skip_spanned_slots() {
+        // One row in `<thead>`, four in `<tbody>`
+        let mut grid = TableGrid::new(&[0, 1, 1, 1, 1]);
+
+        // `rowspan="0"` in the head doesn't reach the body
+        assert_eq!(grid.rows_left(0), 1);
+        assert_eq!(grid.place(0, 0, 1, grid.rows_left(0)), 0);
+        assert_eq!(grid.place(0, 1, 1, 1), 1);
+
+        // `A` takes the first column of the next row too
+        assert_eq!(grid.place(1, 0, 1, 2), 0);
+        assert_eq!(grid.place(1, 1, 2, 1), 1);
+        assert_eq!(grid.place(2, 0, 1, 1), 1);
+        assert_eq!(grid.place(2, 2, 1, 1), 2);
+
+        // `F` goes down to the end of the body
+        assert_eq!(grid.rows_left(3), 2);
+        assert_eq!(grid.place(3, 0, 1, 1), 0);
+        assert_eq!(grid.place(3, 1, 1, grid.rows_left(3)), 1);
+        assert_eq!(grid.place(3, 2, 1, 1), 2);
+        assert_eq!(grid.place(4, 0, 1, 1), 0);
+        assert_eq!(grid.place(4, 1, 1, 1), 2);
+    }
+}
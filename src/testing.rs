@@ -0,0 +1,105 @@
+//! Helpers for tests that need a real renderer (eg: to click on text).
+
+use std::{
+    any::Any,
+    future::Future,
+    task::{Context, Poll, Waker},
+};
+
+use iced::{
+    advanced::{
+        clipboard, layout, mouse,
+        renderer::Headless,
+        widget::{Id, Operation, Tree},
+        Layout, Shell,
+    },
+    Element, Event, Font, Pixels, Point, Rectangle, Size,
+};
+
+use crate::{selection::Block, state::get_text};
+
+pub(crate) fn renderer() -> iced::Renderer {
+    let future = <iced::Renderer as Headless>::new(Font::DEFAULT, Pixels(16.0), Some("tiny-skia"));
+    let mut future = std::pin::pin!(future);
+    // Making a tiny-skia renderer doesn't wait on anything
+    match future
+        .as_mut()
+        .poll(&mut Context::from_waker(Waker::noop()))
+    {
+        Poll::Ready(renderer) => renderer.expect("tiny-skia renderer"),
+        Poll::Pending => unreachable!(),
+    }
+}
+
+/// Finds the text of every [`Block`], built or not.
+#[derive(Default)]
+struct Blocks(Vec<(String, Rectangle)>);
+
+impl Operation for Blocks {
+    fn traverse(&mut self, operate: &mut dyn FnMut(&mut dyn Operation)) {
+        operate(self);
+    }
+
+    fn custom(&mut self, _id: Option<&Id>, bounds: Rectangle, state: &mut dyn Any) {
+        if let Some(block) = state.downcast_ref::<Block>() {
+            let mut text = String::new();
+            for node in &*block.nodes {
+                get_text(node, &mut text);
+            }
+            self.0.push((text, bounds));
+        }
+    }
+}
+
+/// Lays out an element, then returns the text and bounds
+/// of every block of it that can be selected or found.
+pub(crate) fn blocks<M>(element: &mut Element<'_, M>) -> Vec<(String, Rectangle)> {
+    let renderer = renderer();
+    let mut tree = Tree::new(&*element);
+    let limits = layout::Limits::new(Size::ZERO, Size::new(800.0, f32::INFINITY));
+    let node = element
+        .as_widget_mut()
+        .layout(&mut tree, &renderer, &limits);
+
+    let mut blocks = Blocks::default();
+    element
+        .as_widget_mut()
+        .operate(&mut tree, Layout::new(&node), &renderer, &mut blocks);
+    blocks.0
+}
+
+/// Lays out an element, then clicks on the point
+/// `at` picks in it. Returns the messages it sent.
+pub(crate) fn click<M>(element: &mut Element<'_, M>, at: impl Fn(Layout<'_>) -> Point) -> Vec<M> {
+    let renderer = renderer();
+    let mut tree = Tree::new(&*element);
+    let size = Size::new(800.0, 10_000.0);
+    let node = element.as_widget_mut().layout(
+        &mut tree,
+        &renderer,
+        &layout::Limits::new(Size::ZERO, size),
+    );
+
+    let cursor = mouse::Cursor::Available(at(Layout::new(&node)));
+    let mut messages = Vec::new();
+    for event in [
+        mouse::Event::CursorMoved {
+            position: cursor.position().unwrap(),
+        },
+        mouse::Event::ButtonPressed(mouse::Button::Left),
+        mouse::Event::ButtonReleased(mouse::Button::Left),
+    ] {
+        let mut shell = Shell::new(&mut messages);
+        element.as_widget_mut().update(
+            &mut tree,
+            &Event::Mouse(event),
+            Layout::new(&node),
+            cursor,
+            &renderer,
+            &mut clipboard::Null,
+            &mut shell,
+            &Rectangle::with_size(size),
+        );
+    }
+    messages
+}
@@ -0,0 +1,176 @@
+use iced::{widget, Element, Font};
+
+use crate::structs::Heading;
+
+type FSelect<M> = Box<dyn Fn(String) -> M>;
+
+/// A table of contents for a document,
+/// built from [`crate::MarkState::outline`].
+///
+/// Clicking an entry sends the heading's anchor to
+/// [`TocWidget::on_selecting`], which you can pass to
+/// [`crate::scroll_to_anchor`] to jump to it.
+///
+/// ```no_run
+/// # use frostmark::{MarkState, MarkWidget, TocWidget, Heading};
+/// # use iced::widget;
+/// # #[derive(Clone)]
+/// # enum Message { JumpTo(String) }
+/// # struct App { state: MarkState, outline: Vec<Heading> }
+/// # impl App { fn view(&self) -> iced::Element<'_, Message> {
+/// // Store `self.state.outline()` when you create the state
+/// widget::row![
+///     TocWidget::new(&self.outline).on_selecting(Message::JumpTo),
+///     widget::scrollable(MarkWidget::new(&self.state)).id("document"),
+/// ]
+/// .into()
+/// # } }
+/// ```
+pub struct TocWidget<'a, Message> {
+    outline: &'a [Heading],
+    fn_select: Option<FSelect<Message>>,
+    active: Option<&'a str>,
+    max_depth: u8,
+    font: Font,
+    text_size: f32,
+    indent: f32,
+    spacing: f32,
+}
+
+impl<'a, M: 'a> TocWidget<'a, M> {
+    /// Creates a table of contents for the given outline.
+    #[must_use]
+    pub fn new(outline: &'a [Heading]) -> Self {
+        Self {
+            outline,
+            fn_select: None,
+            active: None,
+            max_depth: 6,
+            font: Font::DEFAULT,
+            text_size: 14.0,
+            indent: 12.0,
+            spacing: 4.0,
+        }
+    }
+
+    /// When clicking an entry, send a message with its anchor.
+    #[must_use]
+    pub fn on_selecting(mut self, f: impl Fn(String) -> M + 'static) -> Self {
+        self.fn_select = Some(Box::new(f));
+        self
+    }
+
+    /// Highlights the entry with this anchor (in bold),
+    /// for example the section currently being read.
+    #[must_use]
+    pub fn active(mut self, anchor: Option<&'a str>) -> Self {
+        self.active = anchor;
+        self
+    }
+
+    /// How many levels of nested headings to show (default: 6, all of them).
+    #[must_use]
+    pub fn max_depth(mut self, depth: u8) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// Sets the font of the entries.
+    #[must_use]
+    pub fn font(mut self, font: Font) -> Self {
+        self.font = font;
+        self
+    }
+
+    /// Sets the size of text (default: 14.0).
+    #[must_use]
+    pub fn text_size(mut self, size: impl Into<iced::Pixels>) -> Self {
+        self.text_size = size.into().0;
+        self
+    }
+
+    /// How far each nesting level is indented (default: 12.0).
+    #[must_use]
+    pub fn indent(mut self, indent: f32) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Vertical spacing between entries (default: 4.0).
+    #[must_use]
+    pub fn spacing(mut self, spacing: f32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    fn push_entries<T: widget::text::Catalog + 'a>(
+        &self,
+        headings: &[Heading],
+        depth: u8,
+        column: &mut Vec<Element<'a, M, T>>,
+    ) where
+        M: Clone + 'static,
+    {
+        if depth >= self.max_depth {
+            return;
+        }
+        for heading in headings {
+            let mut font = self.font;
+            if self.active == Some(heading.anchor.as_str()) {
+                font.weight = iced::font::Weight::Bold;
+            }
+            let span: widget::text::Span<'a, M, Font> = widget::span(heading.text.clone())
+                .font(font)
+                .size(self.text_size)
+                .link_maybe(self.fn_select.as_ref().map(|f| f(heading.anchor.clone())));
+
+            column.push(
+                widget::row![
+                    widget::space().width(self.indent * f32::from(depth)),
+                    widget::rich_text([span]).on_link_click(std::convert::identity),
+                ]
+                .into(),
+            );
+            self.push_entries(&heading.children, depth + 1, column);
+        }
+    }
+}
+
+impl<'a, M: Clone + 'static, T: widget::text::Catalog + 'a> From<TocWidget<'a, M>>
+    for Element<'a, M, T>
+{
+    fn from(value: TocWidget<'a, M>) -> Self {
+        let mut column = Vec::new();
+        value.push_entries(value.outline, 0, &mut column);
+        widget::column(column).spacing(value.spacing).into()
+    }
+}
+
+#[cfg(all(test, feature = "iced-tiny-skia"))]
+mod tests {
+    use super::TocWidget;
+    use crate::{structs::Heading, testing::click};
+
+    fn heading(text: &str, children: Vec<Heading>) -> Heading {
+        Heading {
+            level: 1,
+            text: text.to_owned(),
+            anchor: text.to_lowercase(),
+            children,
+        }
+    }
+
+    #[test]
+    fn clicking_selects_anchor() {
+        let outline = [heading("Intro", vec![heading("Setup", Vec::new())])];
+        let mut element: iced::Element<'_, String> =
+            TocWidget::new(&outline).on_selecting(|n| n).into();
+
+        let messages = click(&mut element, |layout| {
+            // The text of the second entry, after its indent
+            let row = layout.children().nth(1).unwrap();
+            row.children().nth(1).unwrap().bounds().center()
+        });
+        assert_eq!(messages, ["setup"]);
+    }
+}
@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+use iced::{
+    advanced::{
+        layout, mouse, overlay, renderer,
+        widget::{tree, Operation, Tree},
+        Clipboard, Layout, Shell, Widget,
+    },
+    Element, Event, Length, Rectangle, Size, Vector,
+};
+
+use crate::{anchor::anchor_id, ir::IrBlock, selection::Block};
+
+/// How far outside the visible area (in pixels)
+/// blocks are still built, so that scrolling a bit
+/// doesn't need a new layout every time.
+const OVERSCAN: f32 = 1000.0;
+
+/// Visible height assumed before the first update
+/// tells us how big the viewport actually is.
+const INITIAL_HEIGHT: f32 = 2000.0;
+
+/// A column of document blocks that only builds
+/// the ones near the visible area, with empty space
+/// standing in for the rest.
+///
+/// Used by [`crate::MarkWidget::virtualized`].
+pub(crate) struct VirtualColumn<'a, M, T, R> {
+    blocks: &'a [IrBlock],
+    build: Box<dyn Fn(usize) -> Element<'a, M, T, R> + 'a>,
+    /// Blocks built so far, with their index.
+    built: Vec<(usize, Element<'a, M, T, R>)>,
+    spacing: f32,
+    text_size: f32,
+}
+
+#[derive(Default)]
+struct State {
+    /// Measured heights of blocks that were built before.
+    heights: Vec<Option<f32>>,
+    /// Width the heights were measured at.
+    width: f32,
+    /// Top and bottom of the visible area, relative to the widget.
+    visible: Option<(f32, f32)>,
+    /// The area blocks were built for in the last layout.
+    built_area: (f32, f32),
+    /// Top of every block, from the last layout.
+    tops: Vec<f32>,
+    /// Trees of the built blocks, in the same order.
+    trees: Vec<(usize, Tree)>,
+}
+
+impl<'a, M, T, R> VirtualColumn<'a, M, T, R> {
+    pub fn new(
+        blocks: &'a [IrBlock],
+        build: impl Fn(usize) -> Element<'a, M, T, R> + 'a,
+        spacing: f32,
+        text_size: f32,
+    ) -> Self {
+        Self {
+            blocks,
+            build: Box::new(build),
+            built: Vec::new(),
+            spacing,
+            text_size,
+        }
+    }
+}
+
+/// Guesses the height of a block that hasn't been laid out yet.
+#[allow(clippy::cast_precision_loss)]
+fn estimate(block: &IrBlock, width: f32, text_size: f32, spacing: f32) -> f32 {
+    let line_height = text_size * 1.3;
+    let per_line = (width / (text_size * 0.5)).max(1.0);
+    let lines = block.lines.max(1) as f32 + (block.chars as f32 / per_line).floor();
+    lines * line_height + (block.lines as f32 * spacing) + block.images as f32 * 100.0
+}
+
+impl<M, T, R: iced::advanced::Renderer> Widget<M, T, R> for VirtualColumn<'_, M, T, R> {
+    fn size(&self) -> Size<Length> {
+        Size::new(Length::Fill, Length::Shrink)
+    }
+
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn layout(&mut self, tree: &mut Tree, renderer: &R, limits: &layout::Limits) -> layout::Node {
+        let state = tree.state.downcast_mut::<State>();
+        let width = limits.max().width;
+
+        if (state.width - width).abs() > f32::EPSILON {
+            state.heights.clear();
+            state.width = width;
+        }
+        state.heights.resize(self.blocks.len(), None);
+
+        let (top, bottom) = state.visible.unwrap_or((0.0, INITIAL_HEIGHT));
+        let area = (top - OVERSCAN, bottom + OVERSCAN);
+        state.built_area = area;
+
+        let mut old_elements: HashMap<usize, Element<'_, M, T, R>> = self.built.drain(..).collect();
+        let mut old_trees: HashMap<usize, Tree> = state.trees.drain(..).collect();
+
+        let mut y = 0.0;
+        let mut nodes = Vec::new();
+        state.tops.clear();
+        for (i, block) in self.blocks.iter().enumerate() {
+            if i > 0 {
+                y += self.spacing;
+            }
+            state.tops.push(y);
+
+            let height = state.heights[i]
+                .unwrap_or_else(|| estimate(block, width, self.text_size, self.spacing));
+            if y + height < area.0 || y > area.1 {
+                y += height;
+                continue;
+            }
+
+            let mut element = old_elements.remove(&i).unwrap_or_else(|| (self.build)(i));
+            let mut tree = old_trees
+                .remove(&i)
+                .unwrap_or_else(|| Tree::new(element.as_widget()));
+            tree.diff(element.as_widget());
+
+            let node = element.as_widget_mut().layout(
+                &mut tree,
+                renderer,
+                &layout::Limits::new(Size::ZERO, Size::new(width, f32::INFINITY)),
+            );
+            let height = node.size().height;
+            state.heights[i] = Some(height);
+            nodes.push(node.move_to((0.0, y)));
+
+            self.built.push((i, element));
+            state.trees.push((i, tree));
+            y += height;
+        }
+
+        layout::Node::with_children(Size::new(width, y), nodes)
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut R,
+        theme: &T,
+        style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_ref::<State>();
+        for (((_, element), (_, tree)), layout) in
+            self.built.iter().zip(&state.trees).zip(layout.children())
+        {
+            if layout.bounds().intersects(viewport) {
+                element
+                    .as_widget()
+                    .draw(tree, renderer, theme, style, layout, cursor, viewport);
+            }
+        }
+    }
+
+    fn operate(
+        &mut self,
+        tree: &mut Tree,
+        layout: Layout<'_>,
+        renderer: &R,
+        operation: &mut dyn Operation,
+    ) {
+        let state = tree.state.downcast_mut::<State>();
+        let bounds = layout.bounds();
+        let (text_size, spacing) = (self.text_size, self.spacing);
+
+        operation.traverse(&mut |operation| {
+            let mut built = self
+                .built
+                .iter_mut()
+                .zip(&mut state.trees)
+                .zip(layout.children())
+                .peekable();
+
+            for (i, block) in self.blocks.iter().enumerate() {
+                if let Some((((_, element), (_, tree)), layout)) =
+                    built.next_if(|(((n, _), _), _)| *n == i)
+                {
+                    element
+                        .as_widget_mut()
+                        .operate(tree, layout, renderer, operation);
+                    continue;
+                }
+
+                // Let things like `scroll_to_anchor` find
+                // blocks that aren't built, at a rough position.
+                let Some(top) = state.tops.get(i) else {
+                    continue;
+                };
+                let height = state
+                    .heights
+                    .get(i)
+                    .copied()
+                    .flatten()
+                    .unwrap_or_else(|| estimate(block, state.width, text_size, spacing));
+                let block_bounds = Rectangle {
+                    y: bounds.y + top,
+                    height,
+                    ..bounds
+                };
+                for anchor in &block.anchors {
+                    operation.container(Some(&anchor_id(anchor)), block_bounds);
+                }
+                if !block.texts.is_empty() {
+                    let mut block = Block::unbuilt(block.texts.clone());
+                    operation.custom(None, block_bounds, &mut block);
+                }
+            }
+        });
+    }
+
+    fn update(
+        &mut self,
+        tree: &mut Tree,
+        event: &Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        renderer: &R,
+        clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, M>,
+        viewport: &Rectangle,
+    ) {
+        let state = tree.state.downcast_mut::<State>();
+
+        let bounds = layout.bounds();
+        let visible = (
+            viewport.y - bounds.y,
+            viewport.y + viewport.height - bounds.y,
+        );
+        if state.visible != Some(visible) {
+            state.visible = Some(visible);
+            let (top, bottom) = state.built_area;
+            if visible.0 < top || visible.1 > bottom {
+                shell.invalidate_layout();
+                shell.request_redraw();
+            }
+        }
+
+        for (((_, element), (_, tree)), layout) in self
+            .built
+            .iter_mut()
+            .zip(&mut state.trees)
+            .zip(layout.children())
+        {
+            element.as_widget_mut().update(
+                tree, event, layout, cursor, renderer, clipboard, shell, viewport,
+            );
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        viewport: &Rectangle,
+        renderer: &R,
+    ) -> mouse::Interaction {
+        let state = tree.state.downcast_ref::<State>();
+        self.built
+            .iter()
+            .zip(&state.trees)
+            .zip(layout.children())
+            .map(|(((_, element), (_, tree)), layout)| {
+                element
+                    .as_widget()
+                    .mouse_interaction(tree, layout, cursor, viewport, renderer)
+            })
+            .max()
+            .unwrap_or_default()
+    }
+
+    fn overlay<'b>(
+        &'b mut self,
+        tree: &'b mut Tree,
+        layout: Layout<'b>,
+        renderer: &R,
+        viewport: &Rectangle,
+        translation: Vector,
+    ) -> Option<overlay::Element<'b, M, T, R>> {
+        let state = tree.state.downcast_mut::<State>();
+        let children: Vec<_> = self
+            .built
+            .iter_mut()
+            .zip(&mut state.trees)
+            .zip(layout.children())
+            .filter_map(|(((_, element), (_, tree)), layout)| {
+                element
+                    .as_widget_mut()
+                    .overlay(tree, layout, renderer, viewport, translation)
+            })
+            .collect();
+
+        (!children.is_empty()).then(|| overlay::Group::with_children(children).overlay())
+    }
+}
+
+impl<'a, M: 'a, T: 'a, R: iced::advanced::Renderer + 'a> From<VirtualColumn<'a, M, T, R>>
+    for Element<'a, M, T, R>
+{
+    fn from(value: VirtualColumn<'a, M, T, R>) -> Self {
+        Element::new(value)
+    }
+}
+
+#[cfg(all(test, feature = "iced-tiny-skia"))]
+mod tests {
+    use iced::Element;
+
+    use crate::{MarkState, MarkWidget};
+
+    #[test]
+    fn unbuilt_blocks_can_be_found() {
+        let html: String = (0..300).map(|i| format!("<p>para {i}</p>")).collect();
+        let state = MarkState::with_html(&html);
+        let mut element: Element<'_, ()> = MarkWidget::new(&state)
+            .virtualized(true)
+            .selectable(true)
+            .into();
+
+        let blocks = crate::testing::blocks(&mut element);
+        let (last, bounds) = blocks.last().unwrap();
+        assert_eq!(blocks.len(), 300);
+        assert_eq!(last, "para 299");
+        // Far below what gets built at first
+        assert!(bounds.y > super::INITIAL_HEIGHT + super::OVERSCAN);
+    }
+}
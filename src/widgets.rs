@@ -1,5 +1,3 @@
-use std::sync::Arc;
-
 use iced::{advanced, widget, Element, Font, Length};
 
 use crate::structs::FStyleLinkButton;